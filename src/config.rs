@@ -1,10 +1,14 @@
-//! Configuration file parsing
+//! Configuration file parsing and per-profile user settings
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use crate::error::AccuChekError;
+use crate::stats::{default_time_bins, AnalysisOptions, TimeBin, DEFAULT_TESTS_PER_DAY};
+use crate::tagging::{self, TagRule};
+use crate::units::{GlucoseUnit, Thresholds};
 
 /// Get the application data directory (OS-specific)
 /// - Windows: C:\Users\<user>\AppData\Roaming\accuchek
@@ -44,6 +48,16 @@ pub fn config_file_path() -> PathBuf {
     get_data_dir().join("config.txt")
 }
 
+/// Get the directory holding automatic database backups
+pub fn backup_dir() -> PathBuf {
+    get_data_dir().join("backups")
+}
+
+/// Get the profile list file path (in data directory)
+pub fn profiles_file_path() -> PathBuf {
+    get_data_dir().join("profiles.json")
+}
+
 /// Get the settings file path (in data directory)
 pub fn settings_file_path() -> PathBuf {
     get_data_dir().join("settings.json")
@@ -56,6 +70,8 @@ pub struct Config {
     pub devices: HashMap<String, bool>,
     /// Path to SQLite database file (default: accuchek.db)
     pub database_path: Option<String>,
+    /// Number of automatic backups to keep (default: 5)
+    pub backup_keep: Option<usize>,
 }
 
 impl Config {
@@ -82,6 +98,8 @@ impl Config {
                 // Handle special config keys
                 if key == "database_path" {
                     config.database_path = Some(value.to_string());
+                } else if key == "backup_keep" {
+                    config.backup_keep = value.parse().ok();
                 } else {
                     config.devices.insert(key.to_string(), value == "1");
                 }
@@ -105,6 +123,11 @@ impl Config {
         Some((key, value))
     }
 
+    /// Number of automatic backups to keep before the oldest is removed
+    pub fn backup_keep(&self) -> usize {
+        self.backup_keep.unwrap_or(5)
+    }
+
     /// Check if a specific vendor/device combination is whitelisted
     pub fn is_device_valid(&self, vendor_id: u16, device_id: u16) -> bool {
         let key = format!("vendor_0x{:04x}_device_0x{:04x}", vendor_id, device_id);
@@ -113,8 +136,6 @@ impl Config {
     
     /// Create a default config file at the given path
     pub fn create_default<P: AsRef<Path>>(path: P) -> io::Result<()> {
-        use std::io::Write;
-        
         let contents = r#"# Accu-Chek Configuration File
 # 
 # Device whitelist: vendor_0xXXXX_device_0xYYYY 1
@@ -127,6 +148,9 @@ vendor_0x173a_device_0x21d8 1  # Relion Platinum model 982
 
 # Optional: Custom database path (uncomment to override default)
 # database_path C:\path\to\custom\accuchek.db
+
+# Optional: Number of automatic backups kept before each sync (default 5)
+# backup_keep 5
 "#;
         
         // Ensure parent directory exists
@@ -139,3 +163,113 @@ vendor_0x173a_device_0x21d8 1  # Relion Platinum model 982
         Ok(())
     }
 }
+
+// ============= Settings =============
+
+/// Persistent user settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppSettings {
+    pub thresholds: Thresholds,
+    pub glucose_unit: GlucoseUnit,
+    /// List excluded readings in the Readings tab; statistics always leave them out
+    #[serde(default)]
+    pub show_excluded: bool,
+    /// Rules for tagging readings automatically at import
+    #[serde(default = "tagging::default_rules")]
+    pub tag_rules: Vec<TagRule>,
+    /// Windows of the day used by the time bin box plot and PDF page
+    #[serde(default = "default_time_bins")]
+    pub time_bins: Vec<TimeBin>,
+    /// Days of readings the Dashboard's A1c estimate covers
+    #[serde(default = "default_a1c_window")]
+    pub a1c_window_days: u32,
+    /// Width of the AGP modal-day bins in minutes (15 or 60)
+    #[serde(default = "default_agp_bin_minutes")]
+    pub agp_bin_minutes: u16,
+    /// Longest time from a before_meal to its after_meal reading, in minutes
+    #[serde(default = "default_meal_pair_window")]
+    pub meal_pair_window_minutes: u16,
+    /// Tests per day the Dashboard's testing card measures against
+    #[serde(default = "default_tests_per_day")]
+    pub tests_per_day_target: u32,
+    /// Days per period in the Dashboard's "last N days vs the N before" comparison
+    #[serde(default = "default_comparison_days")]
+    pub comparison_days: u32,
+    /// Add the period comparison page to PDF exports
+    #[serde(default)]
+    pub pdf_comparison: bool,
+    /// Epoch range the trend charts are zoomed to; all dates when unset
+    #[serde(default)]
+    pub chart_range: Option<[i64; 2]>,
+}
+
+fn default_a1c_window() -> u32 {
+    90
+}
+
+fn default_agp_bin_minutes() -> u16 {
+    60
+}
+
+fn default_meal_pair_window() -> u16 {
+    180
+}
+
+fn default_tests_per_day() -> u32 {
+    DEFAULT_TESTS_PER_DAY
+}
+
+fn default_comparison_days() -> u32 {
+    14
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            thresholds: Thresholds::default(),
+            glucose_unit: GlucoseUnit::MgDl,
+            show_excluded: false,
+            tag_rules: tagging::default_rules(),
+            time_bins: default_time_bins(),
+            a1c_window_days: default_a1c_window(),
+            agp_bin_minutes: default_agp_bin_minutes(),
+            meal_pair_window_minutes: default_meal_pair_window(),
+            tests_per_day_target: default_tests_per_day(),
+            comparison_days: default_comparison_days(),
+            pdf_comparison: false,
+            chart_range: None,
+        }
+    }
+}
+
+impl AppSettings {
+    /// Load settings from a profile's settings file, or defaults if it is missing or unreadable
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        if path.exists() {
+            if let Ok(contents) = fs::read_to_string(path) {
+                if let Ok(settings) = serde_json::from_str(&contents) {
+                    return settings;
+                }
+            }
+        }
+        Self::default()
+    }
+    
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            if let Ok(mut file) = File::create(path) {
+                let _ = file.write_all(json.as_bytes());
+            }
+        }
+    }
+    
+    /// Analysis options matching these settings
+    pub fn analysis_options(&self) -> AnalysisOptions {
+        let mut options = AnalysisOptions::new(self.thresholds, &self.time_bins);
+        options.agp_bin_minutes = self.agp_bin_minutes;
+        options.meal_pair_window_minutes = self.meal_pair_window_minutes;
+        options.tests_per_day_target = self.tests_per_day_target;
+        options
+    }
+}
@@ -21,6 +21,24 @@ pub struct GlucoseReading {
     pub mmol_l: f64,
 }
 
+/// Identification and clock state reported by a meter during a download
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MeterInfo {
+    /// USB product string
+    pub product: String,
+    /// Serial number from the production specification attribute
+    pub serial: Option<String>,
+    /// Meter clock minus computer clock, in seconds (positive = meter ahead)
+    pub clock_drift_secs: Option<i64>,
+}
+
+/// Result of downloading a meter
+#[derive(Debug)]
+pub struct Download {
+    pub meter: MeterInfo,
+    pub readings: Vec<GlucoseReading>,
+}
+
 /// Represents an Accu-Chek USB device
 #[derive(Debug)]
 pub struct AccuChekDevice {
@@ -176,7 +194,7 @@ fn check_device<T: UsbContext>(
 fn operate_device<T: UsbContext>(
     device: &rusb::Device<T>,
     accu_chek: &AccuChekDevice,
-) -> Result<Vec<GlucoseReading>, AccuChekError> {
+) -> Result<Download, AccuChekError> {
     // Open device
     let handle = device.open()?;
 
@@ -207,6 +225,10 @@ fn operate_device<T: UsbContext>(
     let mut phase_index = 1;
     let mut readings: Vec<GlucoseReading> = Vec::new();
     let mut reading_id = 0;
+    let mut meter = MeterInfo {
+        product: accu_chek.product.clone(),
+        ..Default::default()
+    };
 
     // Helper: bulk write
     let bulk_out = |handle: &DeviceHandle<T>, msg_name: &str, data: &[u8], phase: &mut i32| -> Result<(), AccuChekError> {
@@ -354,7 +376,9 @@ fn operate_device<T: UsbContext>(
         if ret_code == APDU_TYPE_ASSOCIATION_ABORT {
             return Err(AccuChekError::AssociationAborted);
         }
-        let _ = bytes_read; // silence warning
+
+        parse_mds_attributes(&buffer[..bytes_read], &mut meter);
+        info!("Meter serial = {:?}, clock drift = {:?}s", meter.serial, meter.clock_drift_secs);
     }
 
     // Phase 8: Send action request
@@ -482,7 +506,7 @@ fn operate_device<T: UsbContext>(
     }
 
     info!("Closing USB device");
-    Ok(readings)
+    Ok(Download { meter, readings })
 }
 
 /// Find object of a given class in config buffer
@@ -537,6 +561,82 @@ fn get_attr(buffer: &[u8], attribute_count: u16, attr_requested_class: u16) -> R
     Err(AccuChekError::Protocol("Attribute not found".to_string()))
 }
 
+/// Read the serial number and clock from the MDS attribute answer
+fn parse_mds_attributes(buffer: &[u8], meter: &mut MeterInfo) {
+    if buffer.len() < 18 {
+        return;
+    }
+
+    let attribute_count = read_be16(buffer, 14);
+    let mut offset = 18;
+
+    for _i in 0..attribute_count {
+        if offset + 4 > buffer.len() {
+            break;
+        }
+        let attr_class = read_be16(buffer, offset);
+        let attr_size = read_be16(buffer, offset + 2) as usize;
+        offset += 4;
+
+        let Some(value) = buffer.get(offset..offset + attr_size) else {
+            break;
+        };
+        match attr_class {
+            MDC_ATTR_ID_PROD_SPECN => meter.serial = parse_serial_number(value),
+            MDC_ATTR_TIME_ABS => {
+                let now = chrono::Local::now().naive_local().and_utc().timestamp();
+                meter.clock_drift_secs = parse_absolute_time(value).map(|meter_time| meter_time - now);
+            }
+            _ => {}
+        }
+        offset += attr_size;
+    }
+}
+
+/// Find the serial-number entry (spec-type 1) in a production specification
+fn parse_serial_number(value: &[u8]) -> Option<String> {
+    if value.len() < 4 {
+        return None;
+    }
+
+    let count = read_be16(value, 0);
+    let mut offset = 4;
+
+    for _i in 0..count {
+        if offset + 6 > value.len() {
+            break;
+        }
+        let spec_type = read_be16(value, offset);
+        let spec_size = read_be16(value, offset + 4) as usize;
+        offset += 6;
+
+        let spec = value.get(offset..offset + spec_size)?;
+        if spec_type == 1 {
+            let serial = String::from_utf8_lossy(spec)
+                .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .to_string();
+            return (!serial.is_empty()).then_some(serial);
+        }
+        offset += spec_size;
+    }
+
+    None
+}
+
+/// Decode a BCD absolute time attribute as epoch seconds (local time taken as UTC)
+fn parse_absolute_time(value: &[u8]) -> Option<i64> {
+    if value.len() < 7 {
+        return None;
+    }
+
+    let cvt = |x: u8| -> u32 { (((x >> 4) & 0x0F) * 10 + (x & 0x0F)) as u32 };
+    let year = (cvt(value[0]) * 100 + cvt(value[1])) as i32;
+
+    chrono::NaiveDate::from_ymd_opt(year, cvt(value[2]), cvt(value[3]))
+        .and_then(|d| d.and_hms_opt(cvt(value[4]), cvt(value[5]), cvt(value[6])))
+        .map(|dt| dt.and_utc().timestamp())
+}
+
 /// Parse glucose readings from a data segment
 fn parse_data(buffer: &[u8], readings: &mut Vec<GlucoseReading>, reading_id: &mut usize) {
     let nb_entries = read_be16(buffer, 30);
@@ -603,7 +703,7 @@ pub fn find_and_operate_accuchek(
     context: &Context,
     config: &Config,
     device_index: Option<usize>,
-) -> Result<Vec<GlucoseReading>, AccuChekError> {
+) -> Result<Download, AccuChekError> {
     // Get list of all USB devices
     info!("Getting list of all USB devices in system from libusb");
     let devices = context.devices()?;
@@ -638,3 +738,60 @@ pub fn find_and_operate_accuchek(
     // Operate device
     operate_device(device, accu_chek)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MDS attribute answer: a production specification with a model and a
+    /// serial entry, followed by the absolute time 2024/01/15 08:30:00
+    const MDS_ATTRIBUTES: [u8; 66] = [
+        0xE7, 0x00, 0x00, 0x3E, 0x00, 0x3C, 0x00, 0x01, 0x02, 0x03, 0x00, 0x36, 0x00, 0x00,
+        0x00, 0x02, 0x00, 0x30,
+        // MDC_ATTR_ID_PROD_SPECN: two entries
+        0x09, 0x2D, 0x00, 0x20,
+        0x00, 0x02, 0x00, 0x1C,
+        0x00, 0x02, 0x00, 0x00, 0x00, 0x06, b'G', b'u', b'i', b'd', b'e', b' ',
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, b'9', b'2', b'4', b'1', b'3', b'5', b'7', b'0', 0x00, 0x00,
+        // MDC_ATTR_TIME_ABS
+        0x09, 0x87, 0x00, 0x08,
+        0x20, 0x24, 0x01, 0x15, 0x08, 0x30, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_parse_mds_attributes() {
+        let mut meter = MeterInfo::default();
+        parse_mds_attributes(&MDS_ATTRIBUTES, &mut meter);
+        assert_eq!(meter.serial.as_deref(), Some("92413570"));
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+            .and_hms_opt(8, 30, 0).unwrap()
+            .and_utc().timestamp();
+        let now = chrono::Local::now().naive_local().and_utc().timestamp();
+        let drift = meter.clock_drift_secs.unwrap();
+        assert!((drift - (expected - now)).abs() <= 5);
+
+        // A buffer cut off inside the time attribute still yields the serial
+        let mut meter = MeterInfo::default();
+        parse_mds_attributes(&MDS_ATTRIBUTES[..62], &mut meter);
+        assert_eq!(meter.serial.as_deref(), Some("92413570"));
+        assert_eq!(meter.clock_drift_secs, None);
+
+        // Cut off inside the serial entry, or before the attribute list
+        let mut meter = MeterInfo::default();
+        parse_mds_attributes(&MDS_ATTRIBUTES[..50], &mut meter);
+        assert_eq!(meter.serial, None);
+        parse_mds_attributes(&MDS_ATTRIBUTES[..10], &mut meter);
+        assert_eq!(meter.serial, None);
+    }
+
+    #[test]
+    fn test_parse_absolute_time() {
+        let expected = chrono::NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+            .and_hms_opt(8, 30, 0).unwrap()
+            .and_utc().timestamp();
+        assert_eq!(parse_absolute_time(&MDS_ATTRIBUTES[58..]), Some(expected));
+        assert_eq!(parse_absolute_time(&[0x20, 0x24, 0x01, 0x15, 0x08, 0x30]), None);
+        // Month 13 is not a date
+        assert_eq!(parse_absolute_time(&[0x20, 0x24, 0x13, 0x15, 0x08, 0x30, 0x00, 0x00]), None);
+    }
+}
@@ -36,4 +36,22 @@ pub enum AccuChekError {
 
     #[error("Storage error: {0}")]
     Storage(#[from] rusqlite::Error),
+
+    #[error("Wrong passphrase for encrypted database")]
+    WrongPassphrase,
+
+    #[error("Cannot restore backup: {0}")]
+    Restore(String),
+
+    #[error("Database {0} is damaged; restore it from a backup")]
+    DamagedDatabase(String),
+
+    #[error("Unknown profile: {0} (see 'accuchek profile list')")]
+    UnknownProfile(String),
+
+    #[error("Cannot read profile list {0}; fix or remove the file")]
+    DamagedProfiles(String),
+
+    #[error("{0}")]
+    Usage(String),
 }
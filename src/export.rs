@@ -4,13 +4,16 @@
 //! comprehensive glucose reports with multiple visualization pages.
 
 use printpdf::*;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-use crate::storage::StoredReading;
+use crate::insights;
+use crate::storage::{Event, EventKind, StoredReading};
 use crate::units::{GlucoseUnit, Thresholds, GlucoseRange};
-use crate::stats::ExportStatistics;
+use crate::stats::{Analysis, BasicStats, TimeInRange, Direction, EpisodeKind, PercentileBand, PeriodComparison, VariabilityStats, LARGE_EXCURSION_MGDL, LONG_GAP_HOURS, STABLE_CV_PERCENT};
+use crate::timeaxis::{self, TickStep, READING_LINE_BREAK};
 
 // ============= Chart Axis Ranges =============
 
@@ -95,6 +98,40 @@ impl PdfColors {
 
 // ============= PDF Drawing Helpers =============
 
+/// Maps epoch seconds onto the width of a chart and labels its dates
+struct TimeAxis {
+    start: i64,
+    end: i64,
+    chart_x: f32,
+    chart_width: f32,
+}
+
+impl TimeAxis {
+    /// About this many ticks fit under a chart
+    const TICKS: f64 = 8.0;
+
+    fn new(start: i64, end: i64, chart_x: f32, chart_width: f32) -> Self {
+        Self { start, end, chart_x, chart_width }
+    }
+
+    fn x(&self, epoch: i64) -> f32 {
+        if self.end <= self.start {
+            return self.chart_x + self.chart_width / 2.0;
+        }
+        self.chart_x + ((epoch - self.start) as f64 / (self.end - self.start) as f64) as f32 * self.chart_width
+    }
+
+    /// Vertical grid lines with their labels below the chart
+    fn draw_ticks(&self, ops: &mut Vec<Op>, chart_y: f32, chart_height: f32) {
+        let step = TickStep::at_least((self.end - self.start) as f64 / Self::TICKS);
+        for tick in step.ticks(self.start, self.end) {
+            let x = self.x(tick);
+            ops.extend(PdfOps::line(x, chart_y, x, chart_y + chart_height, PdfColors::gray(), 0.1));
+            ops.extend(PdfOps::text(&step.format(tick), 6.0, x - 4.0, chart_y - 4.0, BuiltinFont::Helvetica, PdfColors::gray()));
+        }
+    }
+}
+
 struct PdfOps;
 
 impl PdfOps {
@@ -179,6 +216,23 @@ impl PdfOps {
         ops
     }
 
+    fn polygon_fill(points: &[(f32, f32)], color: Color) -> Vec<Op> {
+        vec![
+            Op::SetFillColor { col: color },
+            Op::DrawPolygon {
+                polygon: Polygon {
+                    rings: vec![PolygonRing {
+                        points: points.iter()
+                            .map(|&(x, y)| LinePoint { p: Point::new(Mm(x), Mm(y)), bezier: false })
+                            .collect(),
+                    }],
+                    mode: PaintMode::Fill,
+                    winding_order: WindingOrder::NonZero,
+                },
+            },
+        ]
+    }
+
     fn point(x: f32, y: f32, radius: f32, color: Color) -> Vec<Op> {
         Self::rect_fill(x - radius, y - radius, radius * 2.0, radius * 2.0, color)
     }
@@ -188,7 +242,11 @@ impl PdfOps {
 
 pub struct PdfExporter<'a> {
     readings: &'a [StoredReading],
-    stats: &'a ExportStatistics,
+    events: &'a [Event],
+    stats: &'a Analysis,
+    comparison: Option<&'a PeriodComparison>,
+    /// Tag the readings were limited to, if any
+    tag: Option<&'a str>,
     thresholds: Thresholds,
     unit: GlucoseUnit,
 }
@@ -196,11 +254,33 @@ pub struct PdfExporter<'a> {
 impl<'a> PdfExporter<'a> {
     pub fn new(
         readings: &'a [StoredReading],
-        stats: &'a ExportStatistics,
+        events: &'a [Event],
+        stats: &'a Analysis,
         thresholds: Thresholds,
         unit: GlucoseUnit,
     ) -> Self {
-        Self { readings, stats, thresholds, unit }
+        Self { readings, events, stats, comparison: None, tag: None, thresholds, unit }
+    }
+
+    /// Add a page comparing two periods after the charts
+    pub fn with_comparison(mut self, comparison: &'a PeriodComparison) -> Self {
+        self.comparison = Some(comparison);
+        self
+    }
+
+    /// Note on the summary page that only readings with this tag are included
+    pub fn with_tag(mut self, tag: &'a str) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// Summaries of the events linked to a reading, for one kind or a set of kinds
+    fn event_column(events: &[&Event], kinds: &[EventKind]) -> String {
+        let parts: Vec<String> = events.iter()
+            .filter(|e| kinds.contains(&e.kind))
+            .map(|e| e.summary())
+            .collect();
+        if parts.is_empty() { "-".to_string() } else { parts.join(", ") }
     }
 
     /// Get Y-axis range for charts based on unit
@@ -246,16 +326,29 @@ impl<'a> PdfExporter<'a> {
     pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
         let mut doc = PdfDocument::new("Accu-Chek Glucose Report");
 
-        let mut pages = vec![
-            self.build_summary_page(),
-            self.build_histogram_page(),
-            self.build_hourly_page(),
-            self.build_time_bins_page(),
-            self.build_daily_tir_page(),
-            self.build_chart_page(),
+        // Summary pages with their footer titles, numbered in this order
+        let mut summary = vec![
+            ("Summary", self.build_summary_page()),
+            ("Distribution Histogram", self.build_histogram_page()),
+            ("Time of Day Analysis", self.build_hourly_page()),
+            ("Clinical Time Periods", self.build_time_bins_page()),
+            ("Ambulatory Glucose Profile", self.build_agp_page()),
+            ("Daily TIR Trend", self.build_daily_tir_page()),
+            ("Glucose Trend Chart", self.build_chart_page()),
+            ("Insights", self.build_insights_page()),
+            ("Weekday and Weekly Patterns", self.build_weekly_page()),
+            ("Testing Adherence", self.build_adherence_page()),
         ];
+        if let Some(comparison) = self.comparison {
+            summary.push(("Period Comparison", self.build_comparison_page(comparison)));
+        }
+        let mut pages: Vec<PdfPage> = summary.into_iter()
+            .enumerate()
+            .map(|(i, (title, ops))| Self::page(ops, &format!("Page {} - {}", i + 1, title)))
+            .collect();
 
-        // Add data pages
+        // Add data pages, numbered after the summary pages
+        let summary_pages = pages.len();
         let readings_per_page = 35;
         let total_pages = self.readings.len().div_ceil(readings_per_page);
 
@@ -263,7 +356,10 @@ impl<'a> PdfExporter<'a> {
             let start_idx = page_num * readings_per_page;
             let end_idx = std::cmp::min(start_idx + readings_per_page, self.readings.len());
             let page_readings = &self.readings[start_idx..end_idx];
-            pages.push(self.build_data_page(page_readings, page_num + 1, total_pages));
+            pages.push(Self::page(
+                self.build_data_page(page_readings),
+                &format!("Page {} of {} - Data", summary_pages + page_num + 1, summary_pages + total_pages),
+            ));
         }
 
         doc.with_pages(pages);
@@ -279,7 +375,43 @@ impl<'a> PdfExporter<'a> {
         Ok(())
     }
 
-    fn build_summary_page(&self) -> PdfPage {
+    /// A page with its content and a footer line
+    fn page(mut ops: Vec<Op>, footer: &str) -> PdfPage {
+        ops.extend(PdfOps::text(footer, 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
+        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+    }
+
+    /// GMI and eA1c per window, in the right column of the summary page; returns the lowest y used
+    fn draw_a1c_estimates(&self, ops: &mut Vec<Op>, top: f32) -> f32 {
+        let x = PAGE_WIDTH_MM / 2.0 + 15.0;
+        let mut y = top;
+
+        ops.extend(PdfOps::text("Estimated A1c", 12.0, x, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 6.0;
+        for estimate in &self.stats.a1c {
+            ops.extend(PdfOps::text(
+                &format!("Last {} days: GMI {:.1}% ({:.0} mmol/mol)", estimate.window_days, estimate.gmi_percent, estimate.gmi_mmol_mol),
+                9.0, x, y, BuiltinFont::Helvetica, PdfColors::black(),
+            ));
+            y -= 4.5;
+            ops.extend(PdfOps::text(
+                &format!("eA1c {:.1}% ({:.0} mmol/mol), {} readings", estimate.ea1c_percent, estimate.ea1c_mmol_mol, estimate.readings),
+                9.0, x + 4.0, y, BuiltinFont::Helvetica, PdfColors::black(),
+            ));
+            y -= 4.5;
+            if estimate.low_density() {
+                ops.extend(PdfOps::text(
+                    &format!("Sparse data: {:.1} readings/day, {} of {} days", estimate.readings_per_day(), estimate.days_with_readings, estimate.window_days),
+                    7.0, x + 4.0, y, BuiltinFont::HelveticaOblique, PdfColors::orange(),
+                ));
+                y -= 4.0;
+            }
+            y -= 1.5;
+        }
+        y
+    }
+
+    fn build_summary_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
@@ -290,6 +422,10 @@ impl<'a> PdfExporter<'a> {
         // Date
         let date_str = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
         ops.extend(PdfOps::text(&format!("Generated: {}", date_str), 10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+        if let Some(tag) = self.tag {
+            y -= 5.0;
+            ops.extend(PdfOps::text(&format!("Readings tagged \"{}\" only", tag), 10.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        }
         y -= 15.0;
 
         ops.extend(PdfOps::line(MARGIN_MM, y, PAGE_WIDTH_MM - MARGIN_MM, y, PdfColors::gray(), 0.5));
@@ -299,8 +435,7 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::text("Summary Statistics", 14.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
         y -= 10.0;
 
-        if !self.readings.is_empty() {
-            let stats = &self.stats.basic;
+        if let Some(stats) = &self.stats.basic {
             let first_date = self.readings.first().map(|r| r.timestamp.as_str()).unwrap_or("N/A");
             let last_date = self.readings.last().map(|r| r.timestamp.as_str()).unwrap_or("N/A");
 
@@ -308,6 +443,7 @@ impl<'a> PdfExporter<'a> {
             y -= 7.0;
             ops.extend(PdfOps::text(&format!("Date Range: {} to {}", first_date, last_date), 11.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::black()));
             y -= 7.0;
+            let a1c_bottom = self.draw_a1c_estimates(&mut ops, y);
             
             ops.extend(PdfOps::text(&format!("Average: {:.1} mg/dL ({:.2} mmol/L)", stats.mgdl.mean, stats.mmol.mean), 11.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::black()));
             y -= 7.0;
@@ -316,7 +452,7 @@ impl<'a> PdfExporter<'a> {
             y -= 7.0;
             
             ops.extend(PdfOps::text(&format!("Maximum: {} mg/dL ({:.1} mmol/L)", stats.mgdl.max, stats.mmol.max), 11.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::for_value(stats.mgdl.max, &self.thresholds)));
-            y -= 15.0;
+            y = y.min(a1c_bottom) - 15.0;
         }
 
         // Time in Range section
@@ -382,12 +518,10 @@ impl<'a> PdfExporter<'a> {
         }
 
         // Footer
-        ops.extend(PdfOps::text("Page 1 - Summary", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
     }
 
-    fn build_histogram_page(&self) -> PdfPage {
+    fn build_histogram_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
@@ -400,11 +534,10 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::text(&format!("n = {} readings | {}", self.readings.len(), bin_desc), 10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
         y -= 15.0;
 
-        if self.stats.histogram.is_empty() {
+        let Some(stats) = self.stats.basic.as_ref().filter(|_| !self.stats.histogram.is_empty()) else {
             ops.extend(PdfOps::text("No data available", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
-            ops.extend(PdfOps::text("Page 2 - Distribution Histogram", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-            return PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops);
-        }
+            return ops;
+        };
 
         // Chart area
         let chart_x = MARGIN_MM + 15.0;
@@ -454,17 +587,17 @@ impl<'a> PdfExporter<'a> {
         y -= 25.0;
 
         // Statistics
+        let stats_top = y;
         ops.extend(PdfOps::text("Distribution Statistics", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
         y -= 10.0;
 
-        let stats = &self.stats.basic;
         let (mean_str, ci_str, median_str, sd_str, range_str) = match self.unit {
             GlucoseUnit::MgDl => {
                 let (ci_low, ci_high) = stats.mgdl.confidence_interval_95();
                 (
                     format!("Mean: {:.1} mg/dL", stats.mgdl.mean),
                     format!("95% CI: {:.1} - {:.1} mg/dL", ci_low, ci_high),
-                    format!("Median: {} mg/dL", stats.mgdl.median),
+                    format!("Median: {:.0} mg/dL", stats.mgdl.median),
                     format!("Standard Deviation: {:.1} mg/dL", stats.mgdl.std_dev),
                     format!("Range: {} - {} mg/dL", stats.mgdl.min, stats.mgdl.max),
                 )
@@ -491,12 +624,49 @@ impl<'a> PdfExporter<'a> {
         y -= 6.0;
         ops.extend(PdfOps::text(&range_str, 10.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::black()));
 
-        ops.extend(PdfOps::text("Page 2 - Distribution Histogram", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
+        if let Some(variability) = &self.stats.variability {
+            self.draw_variability(&mut ops, variability, stats_top);
+        }
 
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
     }
 
-    fn build_hourly_page(&self) -> PdfPage {
+    /// Variability and risk indices in the right column of the histogram page
+    fn draw_variability(&self, ops: &mut Vec<Op>, variability: &VariabilityStats, top: f32) {
+        let x = PAGE_WIDTH_MM / 2.0 + 15.0;
+        let mut y = top;
+
+        ops.extend(PdfOps::text("Glycemic Variability", 12.0, x, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 10.0;
+
+        let cv_color = if variability.is_stable() { PdfColors::green() } else { PdfColors::orange() };
+        ops.extend(PdfOps::text(
+            &format!("CV: {:.1}% (target <= {:.0}%)", variability.cv_percent, STABLE_CV_PERCENT),
+            10.0, x + 5.0, y, BuiltinFont::Helvetica, cv_color,
+        ));
+        y -= 6.0;
+
+        let mage = match (variability.mage(self.unit), self.unit) {
+            (Some(mage), GlucoseUnit::MgDl) => format!("MAGE: {:.0} mg/dL", mage),
+            (Some(mage), GlucoseUnit::MmolL) => format!("MAGE: {:.1} mmol/L", mage),
+            (None, _) => "MAGE: -".to_string(),
+        };
+        let (stable, days) = self.stats.stable_days();
+        let lines = [
+            mage,
+            format!("LBGI: {:.1}   HBGI: {:.1}", variability.lbgi, variability.hbgi),
+            format!("ADRR: {:.1}", variability.adrr),
+            format!("J-index: {:.1}", variability.j_index),
+            format!("GRI: {:.0} (zone {})", variability.gri, variability.gri_zone()),
+            format!("Days with CV <= {:.0}%: {}/{}", STABLE_CV_PERCENT, stable, days),
+        ];
+        for line in lines {
+            ops.extend(PdfOps::text(&line, 10.0, x + 5.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+            y -= 6.0;
+        }
+    }
+
+    fn build_hourly_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
@@ -586,13 +756,78 @@ impl<'a> PdfExporter<'a> {
             let label_x = chart_x + (hour as f32 + 1.0) * (chart_width / 25.0) - 3.0;
             ops.extend(PdfOps::text(&format!("{:02}:00", hour), 6.0, label_x, y, BuiltinFont::Helvetica, PdfColors::black()));
         }
+        y -= 15.0;
 
-        ops.extend(PdfOps::text("Page 3 - Time of Day Analysis", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
+        self.draw_episodes(&mut ops, y);
 
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
     }
 
-    fn build_time_bins_page(&self) -> PdfPage {
+    /// Episode counts and the most recent episodes, below the hourly chart
+    fn draw_episodes(&self, ops: &mut Vec<Op>, top: f32) {
+        const MAX_LISTED: usize = 15;
+        let report = &self.stats.episodes;
+        let mut y = top;
+
+        ops.extend(PdfOps::text("Hypo- and Hyperglycemia Episodes", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 7.0;
+        let lines = [
+            (format!("Hypo episodes: {} ({:.1}/week), {} severe (<{} mg/dL)",
+                report.count(EpisodeKind::Hypo), report.count_per_week(EpisodeKind::Hypo),
+                report.severe_count(EpisodeKind::Hypo), Thresholds::VERY_LOW_MGDL), PdfColors::red()),
+            (format!("Nocturnal hypos (00:00-06:00): {} ({:.1}/week)",
+                report.nocturnal_hypo_count(), report.nocturnal_hypo_per_week()), PdfColors::red()),
+            (format!("Hyper episodes: {} ({:.1}/week), {} severe (>{} mg/dL)",
+                report.count(EpisodeKind::Hyper), report.count_per_week(EpisodeKind::Hyper),
+                report.severe_count(EpisodeKind::Hyper), Thresholds::VERY_HIGH_MGDL), PdfColors::orange()),
+        ];
+        for (line, color) in lines {
+            ops.extend(PdfOps::text(&line, 10.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, color));
+            y -= 6.0;
+        }
+
+        if report.episodes.is_empty() {
+            return;
+        }
+        y -= 4.0;
+
+        let col_x = [MARGIN_MM, MARGIN_MM + 30.0, MARGIN_MM + 62.0, MARGIN_MM + 94.0, MARGIN_MM + 112.0, MARGIN_MM + 135.0];
+        ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 5.0, PAGE_WIDTH_MM - 2.0 * MARGIN_MM, 7.0, PdfColors::light_gray()));
+        let nadir_peak = format!("Nadir/Peak ({})", self.unit.label());
+        for (x, header) in col_x.iter().zip(["Type", "Start", "End", "Readings", nadir_peak.as_str(), "At"]) {
+            ops.extend(PdfOps::text(header, 8.0, *x, y - 3.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        }
+        y -= 10.0;
+
+        for episode in report.episodes.iter().rev().take(MAX_LISTED) {
+            let color = match episode.kind {
+                EpisodeKind::Hypo => PdfColors::red(),
+                EpisodeKind::Hyper => PdfColors::orange(),
+            };
+            let mut label = episode.kind.label().to_string();
+            if episode.severe {
+                label.push_str(", severe");
+            }
+            if episode.is_nocturnal() {
+                label.push_str(", night");
+            }
+            ops.extend(PdfOps::text(&label, 8.0, col_x[0], y, BuiltinFont::Helvetica, color.clone()));
+            ops.extend(PdfOps::text(&episode.start, 8.0, col_x[1], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&episode.end, 8.0, col_x[2], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&episode.readings.to_string(), 8.0, col_x[3], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&self.unit.format_value(episode.extreme_mgdl, episode.extreme_mmol), 8.0, col_x[4], y, BuiltinFont::HelveticaBold, color));
+            ops.extend(PdfOps::text(&episode.extreme_time, 8.0, col_x[5], y, BuiltinFont::Helvetica, PdfColors::black()));
+            y -= 5.0;
+        }
+        if report.episodes.len() > MAX_LISTED {
+            ops.extend(PdfOps::text(
+                &format!("Most recent {} of {} episodes", MAX_LISTED, report.episodes.len()),
+                8.0, MARGIN_MM, y - 2.0, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+        }
+    }
+
+    fn build_time_bins_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
@@ -680,14 +915,237 @@ impl<'a> PdfExporter<'a> {
         for (i, stat) in self.stats.time_bins.iter().enumerate() {
             let label_x = chart_x + (i as f32 + 1.0) * box_width;
             ops.extend(PdfOps::text(&stat.name, 6.0, label_x, y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&stat.description, 5.0, label_x, y - 3.0, BuiltinFont::Helvetica, PdfColors::gray()));
         }
 
-        ops.extend(PdfOps::text("Page 4 - Clinical Time Periods", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
+        self.draw_meal_pairs(&mut ops, y - 15.0);
 
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
+    }
+
+    /// Pre-/post-meal excursion tables per time bin and weekday, below the time bin boxplots
+    fn draw_meal_pairs(&self, ops: &mut Vec<Op>, top: f32) {
+        // Time bin rows that fit between the boxplots and the footer next to the 7 weekdays
+        const MAX_LISTED: usize = 6;
+        let report = &self.stats.meal_pairs;
+        let mut y = top;
+
+        ops.extend(PdfOps::text("Pre-/Post-Meal Excursions", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 6.0;
+        ops.extend(PdfOps::text(
+            &format!("before_meal readings paired with the next after_meal reading up to {} min later", report.window_minutes),
+            9.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+        y -= 8.0;
+
+        let Some(overall) = &report.overall else {
+            ops.extend(PdfOps::text("No before/after meal pairs in this period", 10.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::gray()));
+            return;
+        };
+
+        let unit = self.unit.label();
+        let col_x = [MARGIN_MM, MARGIN_MM + 40.0, MARGIN_MM + 58.0, MARGIN_MM + 88.0, MARGIN_MM + 118.0, MARGIN_MM + 148.0];
+        let headers = [
+            "Group".to_string(), "Pairs".to_string(), format!("Before ({})", unit),
+            format!("After ({})", unit), format!("Excursion ({})", unit), format!(">{} mg/dL", LARGE_EXCURSION_MGDL),
+        ];
+        ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 5.0, PAGE_WIDTH_MM - 2.0 * MARGIN_MM, 7.0, PdfColors::light_gray()));
+        for (x, header) in col_x.iter().zip(&headers) {
+            ops.extend(PdfOps::text(header, 8.0, *x, y - 3.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        }
+        y -= 10.0;
+
+        let time_bins = &report.by_time_bin[..report.by_time_bin.len().min(MAX_LISTED)];
+        let groups = std::iter::once(overall).chain(time_bins).chain(&report.by_weekday);
+        for (i, group) in groups.enumerate() {
+            // Gaps between the overall row, the time bins and the weekdays
+            if i == 1 || i == 1 + time_bins.len() {
+                y -= 2.0;
+            }
+            let font = if i == 0 { BuiltinFont::HelveticaBold } else { BuiltinFont::Helvetica };
+            let excursion_color = if group.mean_excursion_mgdl > LARGE_EXCURSION_MGDL as f64 { PdfColors::orange() } else { PdfColors::black() };
+            let cells = [
+                group.label.clone(),
+                group.pairs.to_string(),
+                format!("{:.1}", group.mean_before(self.unit)),
+                format!("{:.1}", group.mean_after(self.unit)),
+                format!("{:+.1} (max {:+.1})", group.mean_excursion(self.unit), group.max_excursion(self.unit)),
+                format!("{} ({:.0}%)", group.large, group.large as f64 / group.pairs as f64 * 100.0),
+            ];
+            for (column, (x, cell)) in col_x.iter().zip(&cells).enumerate() {
+                let color = if column == 4 { excursion_color.clone() } else { PdfColors::black() };
+                ops.extend(PdfOps::text(cell, 8.0, *x, y, font, color));
+            }
+            y -= 5.5;
+        }
+        if report.by_time_bin.len() > MAX_LISTED {
+            ops.extend(PdfOps::text(
+                &format!("First {} of {} time bins", MAX_LISTED, report.by_time_bin.len()),
+                8.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+            y -= 5.5;
+        }
+
+        if report.unpaired_before + report.unpaired_after > 0 {
+            y -= 3.0;
+            ops.extend(PdfOps::text(
+                &format!("Unpaired: {} before_meal and {} after_meal readings", report.unpaired_before, report.unpaired_after),
+                8.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+        }
+    }
+
+    fn build_agp_page(&self) -> Vec<Op> {
+        let mut ops = Vec::new();
+        let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+        let agp = &self.stats.agp;
+
+        ops.extend(PdfOps::text("Ambulatory Glucose Profile (AGP)", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 8.0;
+
+        let Some(metrics) = &agp.metrics else {
+            ops.extend(PdfOps::text("No data available", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+            return ops;
+        };
+
+        ops.extend(PdfOps::text(
+            &format!("{} to {} ({} days), {}-minute bins", metrics.start_date, metrics.end_date, metrics.days, agp.bin_minutes),
+            10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+        y -= 12.0;
+
+        // Headline metrics (left) and range targets (right)
+        let metrics_top = y;
+        ops.extend(PdfOps::text("Glucose Statistics and Targets", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 7.0;
+        let mean = match self.unit {
+            GlucoseUnit::MgDl => format!("{:.0} mg/dL", metrics.mean_mgdl),
+            GlucoseUnit::MmolL => format!("{:.1} mmol/L", metrics.mean_mmol),
+        };
+        let lines = [
+            format!("Readings: {} ({:.1}/day)", metrics.readings, metrics.readings_per_day()),
+            format!("Days with readings: {}/{}", metrics.days_with_readings, metrics.days),
+            format!("Mean glucose: {}", mean),
+            format!("GMI: {:.1}%", metrics.gmi_percent),
+            format!("Glucose variability (CV): {:.1}% (goal <= {:.0}%)", metrics.cv_percent, STABLE_CV_PERCENT),
+        ];
+        for line in lines {
+            ops.extend(PdfOps::text(&line, 10.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+            y -= 6.0;
+        }
+
+        let x = PAGE_WIDTH_MM / 2.0 + 5.0;
+        let mut target_y = metrics_top - 7.0;
+        for target in metrics.range_targets() {
+            ops.extend(PdfOps::rect_fill(x, target_y - 0.5, 3.0, 3.0, PdfColors::for_range(target.range)));
+            ops.extend(PdfOps::text(&target.label(self.unit), 9.0, x + 5.0, target_y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&format!("{:.1}%", target.percent), 9.0, x + 50.0, target_y, BuiltinFont::HelveticaBold, PdfColors::black()));
+            let color = if target.met { PdfColors::green() } else { PdfColors::red() };
+            ops.extend(PdfOps::text(&format!("goal {}", target.target), 9.0, x + 65.0, target_y, BuiltinFont::Helvetica, color));
+            target_y -= 6.0;
+        }
+        y = y.min(target_y) - 6.0;
+
+        // Time in ranges as one stacked bar, very low on the left
+        let bar_width = PAGE_WIDTH_MM - 2.0 * MARGIN_MM;
+        let mut bar_x = MARGIN_MM;
+        for range in [GlucoseRange::VeryLow, GlucoseRange::Low, GlucoseRange::InRange, GlucoseRange::High, GlucoseRange::VeryHigh] {
+            let width = bar_width * (metrics.tir.percentage(range) / 100.0) as f32;
+            if width > 0.0 {
+                ops.extend(PdfOps::rect_fill(bar_x, y - 6.0, width, 6.0, PdfColors::for_range(range)));
+                bar_x += width;
+            }
+        }
+        ops.extend(PdfOps::rect_stroke(MARGIN_MM, y - 6.0, bar_width, 6.0, PdfColors::gray(), 0.3));
+        y -= 20.0;
+
+        // Modal day chart
+        ops.extend(PdfOps::text("Modal Day", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 5.0;
+
+        let chart_x = MARGIN_MM + 15.0;
+        let chart_height = 90.0;
+        let chart_y = y - chart_height;
+        let chart_width = PAGE_WIDTH_MM - 2.0 * MARGIN_MM - 20.0;
+
+        ops.extend(PdfOps::rect_stroke(chart_x, chart_y, chart_width, chart_height, PdfColors::black(), 0.5));
+
+        let (y_min, y_max) = self.y_range();
+        let to_y = |value: f64| chart_y + ((value as f32).clamp(y_min, y_max) - y_min) / (y_max - y_min) * chart_height;
+        let to_x = |minute: f32| chart_x + minute / 1440.0 * chart_width;
+
+        // Target range shading
+        let low_y = to_y(self.threshold_low() as f64);
+        let high_y = to_y(self.threshold_high() as f64);
+        ops.extend(PdfOps::rect_fill(chart_x, low_y, chart_width, high_y - low_y, PdfColors::light_gray()));
+
+        // Percentile bands, one quadrilateral per pair of neighbouring bins with readings
+        let half_bin = agp.bin_minutes as f32 / 2.0;
+        let bands: Vec<Option<(f32, PercentileBand)>> = agp.bins.iter()
+            .map(|bin| Some((to_x(bin.start_minute as f32 + half_bin), bin.band(self.unit)?)))
+            .collect();
+        let outer = Color::Rgb(Rgb { r: 0.75, g: 0.82, b: 0.93, icc_profile: None });
+        let inner = Color::Rgb(Rgb { r: 0.5, g: 0.64, b: 0.85, icc_profile: None });
+        for (interquartile, color) in [(false, &outer), (true, &inner)] {
+            let bounds = |b: &PercentileBand| if interquartile { (b.p25, b.p75) } else { (b.p5, b.p95) };
+            for w in bands.windows(2) {
+                if let (Some((x0, b0)), Some((x1, b1))) = (w[0], w[1]) {
+                    let ((low0, high0), (low1, high1)) = (bounds(&b0), bounds(&b1));
+                    ops.extend(PdfOps::polygon_fill(
+                        &[(x0, to_y(low0)), (x1, to_y(low1)), (x1, to_y(high1)), (x0, to_y(high0))],
+                        color.clone(),
+                    ));
+                }
+            }
+        }
+        for w in bands.windows(2) {
+            if let (Some((x0, b0)), Some((x1, b1))) = (w[0], w[1]) {
+                ops.extend(PdfOps::line(x0, to_y(b0.p50), x1, to_y(b1.p50), PdfColors::blue(), 1.5));
+            }
+        }
+
+        // Threshold lines
+        ops.extend(PdfOps::line(chart_x, low_y, chart_x + chart_width, low_y, PdfColors::red(), 0.5));
+        ops.extend(PdfOps::line(chart_x, high_y, chart_x + chart_width, high_y, PdfColors::orange(), 0.5));
+
+        // Y-axis labels
+        match self.unit {
+            GlucoseUnit::MgDl => {
+                for val in MGDL_AXIS_LABELS {
+                    ops.extend(PdfOps::text(&format!("{}", val), 6.0, MARGIN_MM, to_y(val as f64) - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
+                }
+            }
+            GlucoseUnit::MmolL => {
+                for val in MMOL_AXIS_LABELS {
+                    ops.extend(PdfOps::text(&format!("{:.0}", val), 6.0, MARGIN_MM, to_y(val as f64) - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
+                }
+            }
+        }
+
+        // X-axis labels
+        y = chart_y - 5.0;
+        for hour in (0..=24).step_by(3) {
+            ops.extend(PdfOps::text(&format!("{:02}:00", hour), 6.0, to_x(hour as f32 * 60.0) - 3.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+        }
+        y -= 10.0;
+
+        // Legend
+        ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 0.5, 6.0, 3.0, outer));
+        ops.extend(PdfOps::text("5th-95th percentile", 8.0, MARGIN_MM + 8.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+        ops.extend(PdfOps::rect_fill(MARGIN_MM + 45.0, y - 0.5, 6.0, 3.0, inner));
+        ops.extend(PdfOps::text("25th-75th percentile", 8.0, MARGIN_MM + 53.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+        ops.extend(PdfOps::line(MARGIN_MM + 92.0, y + 1.0, MARGIN_MM + 98.0, y + 1.0, PdfColors::blue(), 1.5));
+        ops.extend(PdfOps::text("Median", 8.0, MARGIN_MM + 100.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+        y -= 8.0;
+        ops.extend(PdfOps::text(
+            "Readings from every day are pooled by time of day. Ranges follow the international consensus on time in range.",
+            8.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+
+        ops
     }
 
-    fn build_daily_tir_page(&self) -> PdfPage {
+    fn build_daily_tir_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
@@ -700,8 +1158,7 @@ impl<'a> PdfExporter<'a> {
 
         if self.stats.daily.is_empty() {
             ops.extend(PdfOps::text("No daily TIR data available", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
-            ops.extend(PdfOps::text("Page 5 - Daily TIR Trend", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-            return PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops);
+            return ops;
         }
 
         // TIR trend chart
@@ -722,27 +1179,28 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::text("0%", 6.0, MARGIN_MM, chart_y - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
         ops.extend(PdfOps::text("100%", 6.0, MARGIN_MM, chart_y + chart_height - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
 
-        // Draw TIR line
-        let n = self.stats.daily.len();
-        if n > 1 {
-            let x_step = chart_width / (n - 1) as f32;
-            for i in 0..n - 1 {
-                let x1 = chart_x + i as f32 * x_step;
-                let x2 = chart_x + (i + 1) as f32 * x_step;
-                let y1 = chart_y + (self.stats.daily[i].tir.in_range_percent() as f32 / 100.0) * chart_height;
-                let y2 = chart_y + (self.stats.daily[i + 1].tir.in_range_percent() as f32 / 100.0) * chart_height;
-                ops.extend(PdfOps::line(x1, y1, x2, y2, PdfColors::green(), 1.0));
+        // Draw TIR line over whole days, broken where a day has no readings
+        let days: Vec<(i64, f64)> = self.stats.daily.iter()
+            .filter_map(|d| Some((timeaxis::day_midpoint(&d.date)?, d.tir.in_range_percent())))
+            .collect();
+        if let (Some(first), Some(last)) = (days.first(), days.last()) {
+            let axis = TimeAxis::new(first.0 - timeaxis::DAY / 2, last.0 + timeaxis::DAY / 2, chart_x, chart_width);
+            axis.draw_ticks(&mut ops, chart_y, chart_height);
+            let to_y = |tir: f64| chart_y + (tir as f32 / 100.0) * chart_height;
+
+            let times: Vec<i64> = days.iter().map(|d| d.0).collect();
+            for segment in timeaxis::segments(&times, timeaxis::DAY) {
+                for pair in days[segment].windows(2) {
+                    ops.extend(PdfOps::line(axis.x(pair[0].0), to_y(pair[0].1), axis.x(pair[1].0), to_y(pair[1].1), PdfColors::green(), 1.0));
+                }
             }
-            
-            for i in 0..n {
-                let x = chart_x + i as f32 * x_step;
-                let y_pos = chart_y + (self.stats.daily[i].tir.in_range_percent() as f32 / 100.0) * chart_height;
-                let color = if self.stats.daily[i].tir.in_range_percent() >= 70.0 { PdfColors::green() } else { PdfColors::orange() };
-                ops.extend(PdfOps::point(x, y_pos, 1.5, color));
+            for &(epoch, tir) in &days {
+                let color = if tir >= 70.0 { PdfColors::green() } else { PdfColors::orange() };
+                ops.extend(PdfOps::point(axis.x(epoch), to_y(tir), 1.5, color));
             }
         }
 
-        y = chart_y - 10.0;
+        y = chart_y - 15.0;
 
         // Summary stats
         let avg_tir: f64 = self.stats.daily.iter().map(|d| d.tir.in_range_percent()).sum::<f64>() / self.stats.daily.len() as f64;
@@ -751,22 +1209,26 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::text(&format!("Average TIR: {:.1}%", avg_tir), 10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::black()));
         ops.extend(PdfOps::text(&format!("Days at >=70% goal: {}/{} ({:.1}%)", days_at_goal, self.stats.daily.len(), (days_at_goal as f64 / self.stats.daily.len() as f64) * 100.0), 10.0, MARGIN_MM + 60.0, y, BuiltinFont::Helvetica, PdfColors::black()));
 
-        ops.extend(PdfOps::text("Page 5 - Daily TIR Trend", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
     }
 
-    fn build_chart_page(&self) -> PdfPage {
+    fn build_chart_page(&self) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
         ops.extend(PdfOps::text("Glucose Trend Chart", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
-        y -= 20.0;
+        y -= 8.0;
+        if let (Some(first), Some(last)) = (self.readings.first(), self.readings.last()) {
+            ops.extend(PdfOps::text(
+                &format!("{} to {} | lines break at gaps over {} h", first.timestamp, last.timestamp, READING_LINE_BREAK / timeaxis::HOUR),
+                10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+        }
+        y -= 12.0;
 
         if self.readings.is_empty() {
             ops.extend(PdfOps::text("No data to display", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
-            ops.extend(PdfOps::text("Page 6 - Glucose Trend Chart", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-            return PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops);
+            return ops;
         }
 
         let chart_x = MARGIN_MM + 15.0;
@@ -809,36 +1271,26 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::line(chart_x, low_y, chart_x + chart_width, low_y, PdfColors::red(), 0.8));
         ops.extend(PdfOps::line(chart_x, high_y, chart_x + chart_width, high_y, PdfColors::orange(), 0.8));
 
-        // Data points and lines
-        let n = self.readings.len();
-        if n > 1 {
-            let x_step = chart_width / (n - 1) as f32;
-            
-            for i in 0..n - 1 {
-                let x1 = chart_x + i as f32 * x_step;
-                let x2 = chart_x + (i + 1) as f32 * x_step;
-                let val1 = self.reading_value(&self.readings[i]);
-                let val2 = self.reading_value(&self.readings[i + 1]);
-                let y1 = chart_y + ((val1 - y_min) / y_range) * chart_height;
-                let y2 = chart_y + ((val2 - y_min) / y_range) * chart_height;
-                
-                let y1_clamped = y1.max(chart_y).min(chart_y + chart_height);
-                let y2_clamped = y2.max(chart_y).min(chart_y + chart_height);
-                
-                ops.extend(PdfOps::line(x1, y1_clamped, x2, y2_clamped, PdfColors::blue(), 0.8));
+        // Data points and lines against time, broken at long gaps between readings
+        let axis = TimeAxis::new(self.readings[0].epoch, self.readings[self.readings.len() - 1].epoch, chart_x, chart_width);
+        axis.draw_ticks(&mut ops, chart_y, chart_height);
+        let to_y = |value: f32| (chart_y + ((value - y_min) / y_range) * chart_height).max(chart_y).min(chart_y + chart_height);
+
+        let times: Vec<i64> = self.readings.iter().map(|r| r.epoch).collect();
+        for segment in timeaxis::segments(&times, READING_LINE_BREAK) {
+            for pair in self.readings[segment].windows(2) {
+                let y1 = to_y(self.reading_value(&pair[0]));
+                let y2 = to_y(self.reading_value(&pair[1]));
+                ops.extend(PdfOps::line(axis.x(pair[0].epoch), y1, axis.x(pair[1].epoch), y2, PdfColors::blue(), 0.8));
             }
+        }
 
-            for i in 0..n {
-                let x = chart_x + i as f32 * x_step;
-                let val = self.reading_value(&self.readings[i]);
-                let y_val = ((val - y_min) / y_range) * chart_height;
-                let y_pos = (chart_y + y_val).max(chart_y).min(chart_y + chart_height);
-                let color = self.value_color(self.readings[i].mg_dl, self.readings[i].mmol_l);
-                ops.extend(PdfOps::point(x, y_pos, 1.5, color));
-            }
+        for reading in self.readings {
+            let color = self.value_color(reading.mg_dl, reading.mmol_l);
+            ops.extend(PdfOps::point(axis.x(reading.epoch), to_y(self.reading_value(reading)), 1.5, color));
         }
 
-        y = chart_y - 15.0;
+        y = chart_y - 20.0;
 
         // Legend
         ops.extend(PdfOps::text("Legend:", 10.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
@@ -862,19 +1314,304 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::line(MARGIN_MM + 130.0, y + 2.0, MARGIN_MM + 142.0, y + 2.0, PdfColors::orange(), 1.0));
         ops.extend(PdfOps::text(&high_label, 9.0, MARGIN_MM + 145.0, y, BuiltinFont::Helvetica, PdfColors::black()));
 
-        ops.extend(PdfOps::text("Page 6 - Glucose Trend Chart", 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
+        ops
+    }
 
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+    fn build_insights_page(&self) -> Vec<Op> {
+        let mut ops = Vec::new();
+        let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+
+        ops.extend(PdfOps::text("Insights", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 8.0;
+        ops.extend(PdfOps::text(
+            "Recurring lows and highs by time of day, meal tag and weekday, with their supporting counts",
+            10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+        y -= 15.0;
+
+        let insights = insights::detect(self.stats, self.readings, self.thresholds);
+        if insights.is_empty() {
+            ops.extend(PdfOps::text("No recurring patterns found", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+        }
+        for insight in &insights {
+            if y < MARGIN_MM + 15.0 {
+                break;
+            }
+            let (label, color) = match insight.range() {
+                Some(range) => (range.label(), PdfColors::for_range(range)),
+                None => ("Pattern", PdfColors::black()),
+            };
+            ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 7.0, 2.0, 10.0, color.clone()));
+            ops.extend(PdfOps::text(label, 9.0, MARGIN_MM + 5.0, y, BuiltinFont::HelveticaBold, color));
+            ops.extend(PdfOps::text(&insight.describe(self.unit, &self.thresholds), 9.0, MARGIN_MM + 5.0, y - 5.0, BuiltinFont::Helvetica, PdfColors::black()));
+            y -= 14.0;
+        }
+
+        ops
     }
 
-    fn build_data_page(&self, readings: &[StoredReading], page_num: usize, total_pages: usize) -> PdfPage {
+    fn build_weekly_page(&self) -> Vec<Op> {
+        const MAX_WEEKS: usize = 16;
+        let mut ops = Vec::new();
+        let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+
+        ops.extend(PdfOps::text("Weekday and Weekly Patterns", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 8.0;
+        ops.extend(PdfOps::text(
+            &format!("Target range: {} | {} ISO weeks", self.thresholds.format_range(self.unit), self.stats.weekly.len()),
+            10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+        y -= 15.0;
+
+        if self.stats.weekdays.is_empty() {
+            ops.extend(PdfOps::text("No weekday data available", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+            return ops;
+        }
+
+        // Weekday boxplots
+        let chart_x = MARGIN_MM + 15.0;
+        let chart_y = y - 55.0;
+        let chart_width = PAGE_WIDTH_MM - 2.0 * MARGIN_MM - 20.0;
+        let chart_height = 55.0;
+        ops.extend(PdfOps::rect_fill(chart_x, chart_y, chart_width, chart_height, PdfColors::light_gray()));
+        ops.extend(PdfOps::rect_stroke(chart_x, chart_y, chart_width, chart_height, PdfColors::black(), 0.5));
+
+        let (y_min, y_max) = self.y_range();
+        let to_y = |value: f64| chart_y + ((value as f32 - y_min) / (y_max - y_min)).clamp(0.0, 1.0) * chart_height;
+        let low_y = to_y(self.threshold_low() as f64);
+        let high_y = to_y(self.threshold_high() as f64);
+        ops.extend(PdfOps::line(chart_x, low_y, chart_x + chart_width, low_y, PdfColors::red(), 0.5));
+        ops.extend(PdfOps::line(chart_x, high_y, chart_x + chart_width, high_y, PdfColors::orange(), 0.5));
+        ops.extend(PdfOps::text(&self.thresholds.low_display(self.unit), 6.0, MARGIN_MM, low_y - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
+        ops.extend(PdfOps::text(&self.thresholds.high_display(self.unit), 6.0, MARGIN_MM, high_y - 1.5, BuiltinFont::Helvetica, PdfColors::gray()));
+
+        let slot = chart_width / 7.0;
+        for day in &self.stats.weekdays {
+            let s = &day.stats;
+            let band = s.band(self.unit);
+            let x = chart_x + day.day_of_week as f32 * slot;
+            let (box_x, box_width, whisker_x) = (x + slot * 0.2, slot * 0.6, x + slot * 0.5);
+            ops.extend(PdfOps::line(whisker_x, to_y(s.min(self.unit)), whisker_x, to_y(band.p25), PdfColors::black(), 0.3));
+            ops.extend(PdfOps::line(whisker_x, to_y(band.p75), whisker_x, to_y(s.max(self.unit)), PdfColors::black(), 0.3));
+            let box_height = (to_y(band.p75) - to_y(band.p25)).max(1.0);
+            ops.extend(PdfOps::rect_fill(box_x, to_y(band.p25), box_width, box_height, self.value_color(s.mgdl.mean as u16, s.mmol.mean)));
+            ops.extend(PdfOps::rect_stroke(box_x, to_y(band.p25), box_width, box_height, PdfColors::black(), 0.3));
+            ops.extend(PdfOps::line(box_x, to_y(band.p50), box_x + box_width, to_y(band.p50), PdfColors::black(), 0.8));
+            ops.extend(PdfOps::text(day.label(), 7.0, whisker_x - 2.5, chart_y - 5.0, BuiltinFont::Helvetica, PdfColors::black()));
+        }
+        y = chart_y - 15.0;
+
+        // Weekday and weekly tables share their columns
+        let col_x = [MARGIN_MM, MARGIN_MM + 45.0, MARGIN_MM + 62.0, MARGIN_MM + 82.0, MARGIN_MM + 110.0, MARGIN_MM + 158.0];
+        let table_header = |ops: &mut Vec<Op>, y: f32, first: &str| {
+            ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 5.0, PAGE_WIDTH_MM - 2.0 * MARGIN_MM, 7.0, PdfColors::light_gray()));
+            let mean = format!("Mean ({})", self.unit.label());
+            for (x, header) in col_x.iter().zip([first, "Days", "Readings", mean.as_str(), "Time in range", "CV"]) {
+                ops.extend(PdfOps::text(header, 8.0, *x, y - 3.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+            }
+        };
+        let table_row = |ops: &mut Vec<Op>, y: f32, label: &str, days: usize, stats: &BasicStats, tir: &TimeInRange, variability: Option<&VariabilityStats>| {
+            ops.extend(PdfOps::text(label, 8.0, col_x[0], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&days.to_string(), 8.0, col_x[1], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&stats.count().to_string(), 8.0, col_x[2], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&format!("{:.1}", stats.mean(self.unit)), 8.0, col_x[3], y, BuiltinFont::Helvetica,
+                self.value_color(stats.mgdl.mean as u16, stats.mmol.mean)));
+            ops.extend(PdfOps::progress_bar(col_x[4], y - 1.0, 30.0, 3.5, tir.in_range_percent() as f32 / 100.0, PdfColors::green()));
+            ops.extend(PdfOps::text(&format!("{:.0}%", tir.in_range_percent()), 8.0, col_x[4] + 33.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+            let cv = variability.map_or("-".to_string(), |v| format!("{:.1}%", v.cv_percent));
+            ops.extend(PdfOps::text(&cv, 8.0, col_x[5], y, BuiltinFont::Helvetica, PdfColors::black()));
+        };
+
+        ops.extend(PdfOps::text("By Day of the Week", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 6.0;
+        table_header(&mut ops, y, "Day");
+        y -= 10.0;
+        for day in &self.stats.weekdays {
+            table_row(&mut ops, y, day.label(), day.days_with_readings, &day.stats, &day.tir, day.variability.as_ref());
+            y -= 5.5;
+        }
+
+        y -= 8.0;
+        let shown = self.stats.weekly.len().min(MAX_WEEKS);
+        ops.extend(PdfOps::text(
+            &format!("By ISO Week (last {} of {})", shown, self.stats.weekly.len()),
+            12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black(),
+        ));
+        y -= 6.0;
+        table_header(&mut ops, y, "Week");
+        y -= 10.0;
+        for week in &self.stats.weekly[self.stats.weekly.len() - shown..] {
+            let label = format!("{} ({})", week.label(), week.week_start);
+            table_row(&mut ops, y, &label, week.days_with_readings, &week.stats, &week.tir, week.variability.as_ref());
+            y -= 5.5;
+        }
+
+        ops
+    }
+
+    fn build_adherence_page(&self) -> Vec<Op> {
+        const MAX_GAPS: usize = 20;
+        let mut ops = Vec::new();
+        let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+
+        ops.extend(PdfOps::text("Testing Adherence", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 8.0;
+
+        let Some(adherence) = &self.stats.adherence else {
+            ops.extend(PdfOps::text("No readings available", 12.0, MARGIN_MM, y - 15.0, BuiltinFont::Helvetica, PdfColors::gray()));
+            return ops;
+        };
+
+        ops.extend(PdfOps::text(
+            &format!("Target: {} tests per day | {} tests over {} days", adherence.target_per_day, adherence.tests, adherence.days),
+            10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+        ));
+        y -= 15.0;
+
+        let status = |ok: bool| if ok { PdfColors::green() } else { PdfColors::orange() };
+        let longest_gap = adherence.longest_gap.as_ref()
+            .map_or("-".to_string(), |gap| format!("{:.0} h ({} to {})", gap.hours, gap.from, gap.to));
+        let rows = [
+            ("Tests per day:", format!("{:.1}", adherence.tests_per_day()), status(adherence.meets_target())),
+            ("Days on target:", format!("{} of {} ({:.0}%)", adherence.days_on_target, adherence.days, adherence.days_on_target_percent()), PdfColors::black()),
+            ("Current streak:", format!("{} days", adherence.current_streak), PdfColors::black()),
+            ("Longest streak:", format!("{} days", adherence.longest_streak), PdfColors::black()),
+            ("Days without tests:", adherence.zero_test_days.len().to_string(), status(adherence.zero_test_days.is_empty())),
+            ("Longest gap:", longest_gap, status(adherence.long_gaps.is_empty())),
+        ];
+        for (label, value, color) in rows {
+            ops.extend(PdfOps::text(label, 10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&value, 10.0, MARGIN_MM + 45.0, y, BuiltinFont::HelveticaBold, color));
+            y -= 6.0;
+        }
+
+        // Tests per time bin
+        y -= 8.0;
+        ops.extend(PdfOps::text("Tests per Time Bin", 12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 6.0;
+        let col_x = [MARGIN_MM, MARGIN_MM + 50.0, MARGIN_MM + 75.0, MARGIN_MM + 100.0];
+        for (x, header) in col_x.iter().zip(["Time Bin", "Tests", "Per Day", "Days Tested"]) {
+            ops.extend(PdfOps::text(header, 9.0, *x, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        }
+        y -= 2.0;
+        ops.extend(PdfOps::line(MARGIN_MM, y, PAGE_WIDTH_MM - MARGIN_MM, y, PdfColors::gray(), 0.5));
+        y -= 5.0;
+        for bin in &adherence.by_time_bin {
+            let percent = bin.days_percent(adherence.days);
+            ops.extend(PdfOps::text(&format!("{} ({})", bin.name, bin.description), 8.0, col_x[0], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&bin.tests.to_string(), 8.0, col_x[1], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&format!("{:.1}", bin.per_day(adherence.days)), 8.0, col_x[2], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::progress_bar(col_x[3], y - 1.0, 30.0, 3.5, percent as f32 / 100.0, PdfColors::blue()));
+            ops.extend(PdfOps::text(&format!("{:.0}%", percent), 8.0, col_x[3] + 33.0, y, BuiltinFont::Helvetica, PdfColors::black()));
+            y -= 5.5;
+        }
+
+        // Flagged gaps, most recent first
+        y -= 8.0;
+        ops.extend(PdfOps::text(
+            &format!("Gaps of {:.0} h or More ({})", LONG_GAP_HOURS, adherence.long_gaps.len()),
+            12.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black(),
+        ));
+        y -= 7.0;
+        if adherence.long_gaps.is_empty() {
+            ops.extend(PdfOps::text("No long gaps between tests", 9.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+            y -= 5.5;
+        }
+        for gap in adherence.long_gaps.iter().rev().take(MAX_GAPS) {
+            if y < MARGIN_MM + 20.0 {
+                break;
+            }
+            ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 1.0, 2.0, 3.5, PdfColors::orange()));
+            ops.extend(PdfOps::text(
+                &format!("{} to {}: {:.0} h without a test", gap.from, gap.to, gap.hours),
+                8.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::black(),
+            ));
+            y -= 5.5;
+        }
+        if adherence.long_gaps.len() > MAX_GAPS && y >= MARGIN_MM + 20.0 {
+            ops.extend(PdfOps::text(
+                &format!("... and {} earlier gaps", adherence.long_gaps.len() - MAX_GAPS),
+                8.0, MARGIN_MM + 5.0, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+        }
+
+        ops
+    }
+
+    fn build_comparison_page(&self, comparison: &PeriodComparison) -> Vec<Op> {
+        let mut ops = Vec::new();
+        let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
+
+        ops.extend(PdfOps::text("Period Comparison", 16.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
+        y -= 8.0;
+        for (label, period) in [("Previous", &comparison.previous), ("Current", &comparison.current)] {
+            ops.extend(PdfOps::text(
+                &format!("{}: {} ({} readings)", label, period.range.description(), period.tir.total),
+                10.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+            y -= 6.0;
+        }
+        y -= 9.0;
+
+        let deltas = comparison.deltas(self.unit);
+        if deltas.is_empty() {
+            ops.extend(PdfOps::text("Not enough readings in both periods to compare", 12.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray()));
+        } else {
+            let col_x = [MARGIN_MM, MARGIN_MM + 45.0, MARGIN_MM + 75.0, MARGIN_MM + 105.0, MARGIN_MM + 140.0];
+            ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 6.0, PAGE_WIDTH_MM - 2.0 * MARGIN_MM, 8.0, PdfColors::light_gray()));
+            for (x, header) in col_x.iter().zip(["Metric", "Previous", "Current", "Change", "Significance"]) {
+                ops.extend(PdfOps::text(header, 9.0, *x, y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+            }
+            y -= 12.0;
+
+            for delta in &deltas {
+                let (verdict, color) = match delta.direction {
+                    Direction::Improved => ("better", PdfColors::green()),
+                    Direction::Worsened => ("worse", PdfColors::red()),
+                    Direction::Unchanged => ("same", PdfColors::gray()),
+                };
+                let significance = match delta.significant {
+                    Some(true) => "significant",
+                    Some(false) => "not significant",
+                    None => "-",
+                };
+                ops.extend(PdfOps::text(delta.metric, 9.0, col_x[0], y, BuiltinFont::Helvetica, PdfColors::black()));
+                ops.extend(PdfOps::text(&format!("{:.1} {}", delta.previous, delta.unit), 9.0, col_x[1], y, BuiltinFont::Helvetica, PdfColors::black()));
+                ops.extend(PdfOps::text(&format!("{:.1} {}", delta.current, delta.unit), 9.0, col_x[2], y, BuiltinFont::Helvetica, PdfColors::black()));
+                ops.extend(PdfOps::text(&format!("{:+.1} ({})", delta.change, verdict), 9.0, col_x[3], y, BuiltinFont::HelveticaBold, color));
+                ops.extend(PdfOps::text(significance, 9.0, col_x[4], y, BuiltinFont::Helvetica, PdfColors::black()));
+                y -= 7.0;
+            }
+
+            y -= 8.0;
+            ops.extend(PdfOps::text(
+                "Significance: two-sided z-test at p < 0.05 on the readings of each period.",
+                8.0, MARGIN_MM, y, BuiltinFont::Helvetica, PdfColors::gray(),
+            ));
+        }
+
+        ops
+    }
+
+    fn build_data_page(&self, readings: &[StoredReading]) -> Vec<Op> {
         let mut ops = Vec::new();
         let mut y = PAGE_HEIGHT_MM - MARGIN_MM;
 
         ops.extend(PdfOps::text("Glucose Readings", 14.0, MARGIN_MM, y, BuiltinFont::HelveticaBold, PdfColors::black()));
         y -= 15.0;
 
-        let col_x = [MARGIN_MM, MARGIN_MM + 32.0, MARGIN_MM + 48.0, MARGIN_MM + 64.0, MARGIN_MM + 80.0, MARGIN_MM + 130.0];
+        let col_x = [
+            MARGIN_MM, MARGIN_MM + 28.0, MARGIN_MM + 41.0, MARGIN_MM + 54.0,
+            MARGIN_MM + 70.0, MARGIN_MM + 84.0, MARGIN_MM + 106.0, MARGIN_MM + 130.0, MARGIN_MM + 152.0,
+        ];
+
+        // Events linked to the readings on this page
+        let mut events_by_reading: HashMap<i64, Vec<&Event>> = HashMap::new();
+        for event in self.events {
+            if let Some(reading_id) = event.reading_id {
+                events_by_reading.entry(reading_id).or_default().push(event);
+            }
+        }
 
         // Header background
         ops.extend(PdfOps::rect_fill(MARGIN_MM, y - 6.0, PAGE_WIDTH_MM - 2.0 * MARGIN_MM, 8.0, PdfColors::light_gray()));
@@ -889,8 +1626,11 @@ impl<'a> PdfExporter<'a> {
         ops.extend(PdfOps::text(col1_label, 8.0, col_x[1], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
         ops.extend(PdfOps::text(col2_label, 8.0, col_x[2], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
         ops.extend(PdfOps::text("Status", 8.0, col_x[3], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
-        ops.extend(PdfOps::text("Notes", 8.0, col_x[4], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
-        ops.extend(PdfOps::text("Tags", 8.0, col_x[5], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        ops.extend(PdfOps::text("Carbs", 8.0, col_x[4], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        ops.extend(PdfOps::text("Insulin", 8.0, col_x[5], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        ops.extend(PdfOps::text("Activity/Meds", 8.0, col_x[6], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        ops.extend(PdfOps::text("Notes", 8.0, col_x[7], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
+        ops.extend(PdfOps::text("Tags", 8.0, col_x[8], y - 4.0, BuiltinFont::HelveticaBold, PdfColors::black()));
         y -= 10.0;
 
         ops.extend(PdfOps::line(MARGIN_MM, y, PAGE_WIDTH_MM - MARGIN_MM, y, PdfColors::gray(), 0.5));
@@ -919,19 +1659,34 @@ impl<'a> PdfExporter<'a> {
             ops.extend(PdfOps::text(&reading.timestamp, 7.0, col_x[0], y, BuiltinFont::Helvetica, PdfColors::black()));
             ops.extend(PdfOps::text(&val1, 7.0, col_x[1], y, BuiltinFont::Helvetica, value_color));
             ops.extend(PdfOps::text(&val2, 7.0, col_x[2], y, BuiltinFont::Helvetica, PdfColors::black()));
-            ops.extend(PdfOps::text(range.status(), 7.0, col_x[3], y, BuiltinFont::Helvetica, status_color));
+            if reading.excluded {
+                ops.extend(PdfOps::text("EXCLUDED", 7.0, col_x[3], y, BuiltinFont::Helvetica, PdfColors::gray()));
+            } else {
+                ops.extend(PdfOps::text(range.status(), 7.0, col_x[3], y, BuiltinFont::Helvetica, status_color));
+            }
+            
+            let events = events_by_reading.get(&reading.id).map(Vec::as_slice).unwrap_or_default();
+            let carbs = Self::event_column(events, &[EventKind::Carbs]);
+            let insulin = Self::event_column(events, &[EventKind::Insulin]);
+            let other = Self::event_column(events, &[EventKind::Exercise, EventKind::Medication]);
+            ops.extend(PdfOps::text(&truncate_chars(&carbs, 10), 7.0, col_x[4], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&truncate_chars(&insulin, 16), 7.0, col_x[5], y, BuiltinFont::Helvetica, PdfColors::black()));
+            ops.extend(PdfOps::text(&truncate_chars(&other, 18), 7.0, col_x[6], y, BuiltinFont::Helvetica, PdfColors::black()));
             
             let note = reading.note.as_deref().unwrap_or("-");
-            let note_display = if note.is_empty() { "-" } else if note.len() > 30 { &note[..30] } else { note };
-            ops.extend(PdfOps::text(note_display, 7.0, col_x[4], y, BuiltinFont::Helvetica, PdfColors::black()));
+            let note_display = if note.is_empty() { "-".to_string() } else { truncate_chars(note, 18) };
+            ops.extend(PdfOps::text(&note_display, 7.0, col_x[7], y, BuiltinFont::Helvetica, PdfColors::black()));
             
             let tags = reading.tags.as_deref().unwrap_or("-");
-            let tags_display = if tags.is_empty() { "-" } else { tags };
-            ops.extend(PdfOps::text(tags_display, 7.0, col_x[5], y, BuiltinFont::Helvetica, PdfColors::gray()));
+            let tags_display = if tags.is_empty() { "-".to_string() } else { truncate_chars(tags, 16) };
+            ops.extend(PdfOps::text(&tags_display, 7.0, col_x[8], y, BuiltinFont::Helvetica, PdfColors::gray()));
         }
 
-        ops.extend(PdfOps::text(&format!("Page {} of {} - Data", page_num + 6, total_pages + 6), 8.0, MARGIN_MM, MARGIN_MM, BuiltinFont::Helvetica, PdfColors::gray()));
-
-        PdfPage::new(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), ops)
+        ops
     }
 }
+
+/// Shorten text to at most `max` characters so it fits its column
+fn truncate_chars(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
@@ -9,70 +9,67 @@
 //! - PDF export functionality
 
 use eframe::egui;
-use egui_plot::{Line, Plot, PlotPoints, Bar, BarChart, BoxElem, BoxPlot, BoxSpread, Points};
+use egui_plot::{Line, Plot, PlotPoints, Bar, BarChart, BoxElem, BoxPlot, BoxSpread, Points, Polygon};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
-use std::fs;
-use std::io::Write;
-use serde::{Deserialize, Serialize};
 
-use crate::config::Config;
+use crate::config::{AppSettings, Config};
+use crate::profile::{Profile, Profiles};
 use crate::device::find_and_operate_accuchek;
-use crate::storage::{Storage, StoredReading};
+use crate::storage::{timestamp_to_epoch, ChangeSource, Event, EventKind, HistoryEntry, ImportReport, Storage, StoredReading, SyncSession};
 use crate::units::{GlucoseUnit, Thresholds, GlucoseRange};
-use crate::stats::{BasicStats, TimeInRange, DailyStats, HourlyStats, TimeBinStats, HistogramBin, CalendarDay, ExportStatistics};
 use crate::export::PdfExporter;
+use crate::insights::{self, Insight};
+use crate::timeaxis::{self, READING_LINE_BREAK};
+use crate::tagging::{RuleCondition, TagRule};
+use crate::stats::{default_time_bins, DailyStats, WeeklyEpisodes, WeeklyStats, RateOfChange, RollingPoint, MAX_RATE_GAP_MINUTES, RAPID_RATE_MGDL_PER_MIN, Adherence, LONG_GAP_HOURS, distinct_tags, format_minute, has_tag, AgpBin, AgpReport, Analysis, DateRange, Direction, EpisodeKind, ExcursionSummary, MealPairReport, PeriodComparison, A1C_WINDOWS, AGP_BIN_MINUTES, AFTER_MEAL_TAG, BEFORE_MEAL_TAG, LARGE_EXCURSION_MGDL, MEAL_PAIR_WINDOWS, STABLE_CV_PERCENT, PercentileBand, VariabilityStats, AnalysisOptions, BasicStats, CalendarDay, TimeBin, TimeInRange};
 
 /// Type alias for reading list items (index, label, selected, note, tags)
 type ReadingListItem = (usize, String, bool, Option<String>, Option<String>);
 
-// ============= Settings =============
+// ============= Sync Management =============
 
-/// Persistent user settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppSettings {
-    pub thresholds: Thresholds,
-    pub glucose_unit: GlucoseUnit,
+pub enum SyncMessage {
+    Started,
+    Success { report: ImportReport, total_from_device: usize, profile: String },
+    Error(String),
 }
 
-impl Default for AppSettings {
-    fn default() -> Self {
-        Self {
-            thresholds: Thresholds::default(),
-            glucose_unit: GlucoseUnit::MgDl,
-        }
-    }
+/// Load config.txt from the data directory, then the current directory
+fn load_config() -> Config {
+    Config::load(crate::config::config_file_path())
+        .or_else(|_| Config::load("config.txt"))
+        .unwrap_or_default()
 }
 
-impl AppSettings {
-    pub fn load() -> Self {
-        let path = crate::config::settings_file_path();
-        if path.exists() {
-            if let Ok(contents) = fs::read_to_string(&path) {
-                if let Ok(settings) = serde_json::from_str(&contents) {
-                    return settings;
-                }
-            }
-        }
-        Self::default()
-    }
-    
-    pub fn save(&self) {
-        let path = crate::config::settings_file_path();
-        if let Ok(json) = serde_json::to_string_pretty(self) {
-            if let Ok(mut file) = fs::File::create(&path) {
-                let _ = file.write_all(json.as_bytes());
-            }
-        }
-    }
-}
+/// Download the meter and import its readings, filling in the session as it goes
+///
+/// `target` starts as the selected profile and is switched to the profile the
+/// meter is assigned to.
+fn run_sync(config: &Config, target: &mut Profile, session: &mut SyncSession) -> Result<(ImportReport, usize), String> {
+    let context = rusb::Context::new().map_err(|e| format!("USB error: {}", e))?;
+    let download = find_and_operate_accuchek(&context, config, None).map_err(|e| format!("{}", e))?;
+    session.set_download(&download);
 
-// ============= Sync Management =============
+    *target = Profiles::load()
+        .and_then(|mut profiles| profiles.route_meter(download.meter.serial.as_deref(), &target.name))
+        .map_err(|e| format!("{}", e))?;
+    target.ensure_dir().map_err(|e| format!("Cannot create profile folder: {}", e))?;
+    let db_path = target.database_path(config);
+    if crate::storage::is_encrypted(&db_path) && !crate::storage::has_passphrase(&db_path) {
+        return Err(format!("Profile '{}' is encrypted; open it once to unlock it, then sync again", target.name));
+    }
 
-pub enum SyncMessage {
-    Started,
-    Success { new_count: usize, total_from_device: usize },
-    Error(String),
+    let storage = Storage::new(&db_path).map_err(|e| format!("Cannot open database: {}", e))?;
+    if let Err(e) = storage.auto_backup(&target.backup_dir(), config.backup_keep()) {
+        log::warn!("Could not back up database: {}", e);
+    }
+    let report = storage.import_readings(&download.readings)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if let Err(e) = storage.apply_tag_rules(&AppSettings::load(target.settings_path()).tag_rules, false) {
+        log::warn!("Could not apply tagging rules: {}", e);
+    }
+    Ok((report, download.readings.len()))
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -127,7 +124,12 @@ enum ChartView {
     Histogram,
     TimeOfDay,
     DailyTrend,
+    Trend,
     TimeBins,
+    Agp,
+    Episodes,
+    Meals,
+    Weekly,
     Calendar,
 }
 
@@ -141,40 +143,61 @@ enum ExportStatus {
 // ============= Data Container =============
 
 struct AppData {
+    /// Readings used for statistics, without excluded ones
     readings: Vec<StoredReading>,
-    basic_stats: Option<BasicStats>,
-    time_in_range: Option<TimeInRange>,
-    daily_stats: Vec<DailyStats>,
-    hourly_stats: Vec<HourlyStats>,
-    time_bin_stats: Vec<TimeBinStats>,
-    histogram_bins: Vec<HistogramBin>,
-    calendar_data: Vec<CalendarDay>,
+    /// Every stored reading, for the Readings tab
+    all_readings: Vec<StoredReading>,
+    analysis: Analysis,
+    /// Recurring patterns in `readings`, lows first
+    insights: Vec<Insight>,
+    excluded_count: i64,
+    sync_sessions: Vec<SyncSession>,
+    events: Vec<Event>,
 }
 
 impl AppData {
     fn empty() -> Self {
         Self {
             readings: Vec::new(),
-            basic_stats: None,
-            time_in_range: None,
-            daily_stats: Vec::new(),
-            hourly_stats: Vec::new(),
-            time_bin_stats: Vec::new(),
-            histogram_bins: Vec::new(),
-            calendar_data: Vec::new(),
+            all_readings: Vec::new(),
+            analysis: Analysis::run::<StoredReading>(&[], &AnalysisOptions::new(Thresholds::default(), &[])),
+            insights: Vec::new(),
+            excluded_count: 0,
+            sync_sessions: Vec::new(),
+            events: Vec::new(),
         }
     }
 
-    fn load(storage: &Storage, thresholds: Thresholds) -> Self {
+    fn load(storage: &Storage, settings: &AppSettings) -> Self {
+        let all_readings = storage.get_all_readings().unwrap_or_default();
+        let readings: Vec<StoredReading> = all_readings.iter().filter(|r| !r.excluded).cloned().collect();
+        let analysis = Analysis::run(&readings, &settings.analysis_options());
+        Self {
+            insights: insights::detect(&analysis, &readings, settings.thresholds),
+            analysis,
+            readings,
+            all_readings,
+            excluded_count: storage.count_excluded().unwrap_or(0),
+            sync_sessions: storage.get_sync_sessions().unwrap_or_default(),
+            events: storage.get_events().unwrap_or_default(),
+        }
+    }
+}
+
+/// Readings carrying one tag with their own analysis, for tag-segmented charts and exports
+struct TagSegment {
+    tag: String,
+    readings: Vec<StoredReading>,
+    analysis: Analysis,
+}
+
+impl TagSegment {
+    fn new(tag: &str, readings: &[StoredReading], settings: &AppSettings) -> Self {
+        let readings: Vec<StoredReading> = readings.iter().filter(|r| has_tag(*r, tag)).cloned().collect();
         Self {
-            readings: storage.get_all_readings().unwrap_or_default(),
-            basic_stats: storage.get_basic_stats().ok().flatten(),
-            time_in_range: storage.get_time_in_range(thresholds).ok(),
-            daily_stats: storage.get_daily_stats(thresholds).unwrap_or_default(),
-            hourly_stats: storage.get_hourly_stats().unwrap_or_default(),
-            time_bin_stats: storage.get_time_bin_stats().unwrap_or_default(),
-            histogram_bins: storage.get_histogram(20).unwrap_or_default(),
-            calendar_data: storage.get_calendar_data(thresholds).unwrap_or_default(),
+            tag: tag.to_string(),
+            analysis: Analysis::run(&readings, &settings.analysis_options()),
+            readings,
         }
     }
 }
@@ -186,14 +209,42 @@ pub struct AccuChekApp {
     data: AppData,
     settings: AppSettings,
     
+    // Profiles
+    profiles: Profiles,
+    profile: Profile,
+    new_profile_name: String,
+    
+    // Encryption state
+    locked: bool,
+    encrypted: bool,
+    passphrase_buffer: String,
+    unlock_error: Option<String>,
+    
     // UI state
     current_tab: Tab,
     selected_reading: Option<usize>,
     note_edit_buffer: String,
     tag_edit_buffer: String,
+    exclusion_reason_buffer: String,
+    event_kind: EventKind,
+    event_amount: f64,
+    event_detail_buffer: String,
+    /// Change history of the selected reading, with the ID it was loaded for
+    reading_history: Option<(i64, Vec<HistoryEntry>)>,
     search_query: String,
     current_chart_view: ChartView,
+    /// Tag the Charts tab is limited to, if any
+    chart_segment: Option<TagSegment>,
+    /// Date range a trend chart was zoomed or panned to this frame (`Some(None)` on reset)
+    chart_range_change: std::cell::Cell<Option<Option<[i64; 2]>>>,
+    /// The chart range changed and is saved once the pointer is released
+    chart_range_unsaved: bool,
+    /// Previous start/end and current start/end dates typed on the Dashboard
+    comparison_inputs: [String; 4],
+    comparison: Option<PeriodComparison>,
+    comparison_error: Option<String>,
     show_settings: bool,
+    show_sync_history: bool,
     
     // Sync state
     sync_receiver: Option<Receiver<SyncMessage>>,
@@ -204,6 +255,9 @@ pub struct AccuChekApp {
     export_status: ExportStatus,
     exported_path: Option<std::path::PathBuf>,
     show_export_dialog: bool,
+    show_export_options: bool,
+    /// Tag the PDF report is limited to, if any
+    export_tag: Option<String>,
     
     // Notifications
     notifications: Vec<Notification>,
@@ -211,49 +265,194 @@ pub struct AccuChekApp {
 
 impl Default for AccuChekApp {
     fn default() -> Self {
-        let settings = AppSettings::load();
+        let profiles = Profiles::default();
+        let profile = profiles.last_used().clone();
         Self {
             db_path: "accuchek.db".to_string(),
             data: AppData::empty(),
-            settings,
+            settings: AppSettings::default(),
+            profiles,
+            profile,
+            new_profile_name: String::new(),
+            locked: false,
+            encrypted: false,
+            passphrase_buffer: String::new(),
+            unlock_error: None,
             current_tab: Tab::Dashboard,
             selected_reading: None,
             note_edit_buffer: String::new(),
             tag_edit_buffer: String::new(),
+            exclusion_reason_buffer: String::new(),
+            event_kind: EventKind::Carbs,
+            event_amount: 0.0,
+            event_detail_buffer: String::new(),
+            reading_history: None,
             search_query: String::new(),
             current_chart_view: ChartView::Overview,
+            chart_segment: None,
+            chart_range_change: std::cell::Cell::new(None),
+            chart_range_unsaved: false,
+            comparison_inputs: Default::default(),
+            comparison: None,
+            comparison_error: None,
             show_settings: false,
+            show_sync_history: false,
             sync_receiver: None,
             sync_status: SyncStatus::Idle,
             last_sync_message: String::new(),
             export_status: ExportStatus::Idle,
             exported_path: None,
             show_export_dialog: false,
+            show_export_options: false,
+            export_tag: None,
             notifications: Vec::new(),
         }
     }
 }
 
 impl AccuChekApp {
-    pub fn new(cc: &eframe::CreationContext<'_>, db_path: String) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, profiles: Profiles, profile: Profile) -> Self {
         let mut visuals = egui::Visuals::dark();
         visuals.override_text_color = Some(egui::Color32::from_gray(220));
         cc.egui_ctx.set_visuals(visuals);
         
-        let settings = AppSettings::load();
         let mut app = Self {
-            db_path,
-            settings,
+            profiles,
             ..Default::default()
         };
-        
-        app.refresh_data();
+        app.open_profile(profile);
         app
     }
     
+    /// Switch to a profile, loading its settings and database
+    fn open_profile(&mut self, profile: Profile) {
+        if let Err(e) = profile.ensure_dir() {
+            log::warn!("Could not create profile folder: {}", e);
+        }
+        self.db_path = profile.database_path(&load_config());
+        self.settings = AppSettings::load(profile.settings_path());
+        self.profiles.set_last_used(&profile.name);
+        self.profile = profile;
+        
+        self.selected_reading = None;
+        self.data = AppData::empty();
+        self.passphrase_buffer.clear();
+        self.unlock_error = None;
+        
+        // An encrypted database is loaded once its passphrase has been entered
+        self.locked = crate::storage::is_encrypted(&self.db_path) && !crate::storage::has_passphrase(&self.db_path);
+        if !self.locked {
+            self.refresh_data();
+        }
+    }
+    
+    /// Profile chosen in a profile selector, if it changed
+    fn profile_selector(&self, ui: &mut egui::Ui) -> Option<Profile> {
+        let mut selected = None;
+        egui::ComboBox::from_id_salt("profile_selector")
+            .selected_text(format!("Profile: {}", self.profile.name))
+            .show_ui(ui, |ui| {
+                for profile in &self.profiles.profiles {
+                    if ui.selectable_label(profile.name == self.profile.name, &profile.name).clicked()
+                        && profile.name != self.profile.name
+                    {
+                        selected = Some(profile.clone());
+                    }
+                }
+            });
+        selected
+    }
+    
+    fn add_profile(&mut self) {
+        let name = self.new_profile_name.trim().to_string();
+        match self.profiles.add(&name) {
+            Ok(_) => {
+                self.new_profile_name.clear();
+                self.notifications.push(Notification::new(
+                    format!("Created profile '{}'", name),
+                    NotificationType::Success
+                ));
+            }
+            Err(e) => {
+                self.notifications.push(Notification::new(format!("✗ {}", e), NotificationType::Error));
+            }
+        }
+    }
+    
+    fn unlock(&mut self) {
+        crate::storage::set_passphrase(&self.db_path, Some(std::mem::take(&mut self.passphrase_buffer)));
+        match Storage::new(&self.db_path) {
+            Ok(_) => {
+                self.locked = false;
+                self.unlock_error = None;
+                self.refresh_data();
+            }
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+                crate::storage::set_passphrase(&self.db_path, None);
+                self.unlock_error = Some("Wrong passphrase".to_string());
+            }
+            Err(e) => {
+                crate::storage::set_passphrase(&self.db_path, None);
+                self.unlock_error = Some(format!("Cannot open database: {}", e));
+            }
+        }
+    }
+    
     fn refresh_data(&mut self) {
+        self.reading_history = None;
         if let Ok(storage) = Storage::new(&self.db_path) {
-            self.data = AppData::load(&storage, self.settings.thresholds);
+            self.encrypted = storage.is_encrypted();
+            self.data = AppData::load(&storage, &self.settings);
+        }
+        if let Some(tag) = self.chart_segment.as_ref().map(|segment| segment.tag.clone()) {
+            self.chart_segment = Some(TagSegment::new(&tag, &self.data.readings, &self.settings));
+        }
+        self.update_comparison();
+    }
+    
+    /// Readings and analysis the Charts tab shows: the selected tag's, or all
+    fn chart_data(&self) -> (&[StoredReading], &Analysis) {
+        match &self.chart_segment {
+            Some(segment) => (&segment.readings, &segment.analysis),
+            None => (&self.data.readings, &self.data.analysis),
+        }
+    }
+    
+    fn chart_data_mut(&mut self) -> (&[StoredReading], &mut Analysis) {
+        match &mut self.chart_segment {
+            Some(segment) => (&segment.readings, &mut segment.analysis),
+            None => (&self.data.readings, &mut self.data.analysis),
+        }
+    }
+    
+    /// Limit the Charts tab to readings carrying `tag`, or show all for `None`
+    fn set_chart_tag(&mut self, tag: Option<String>) {
+        self.chart_segment = tag.map(|tag| TagSegment::new(&tag, &self.data.readings, &self.settings));
+    }
+    
+    /// Recompare the typed date ranges, or the last N days if none are typed yet
+    fn update_comparison(&mut self) {
+        if self.comparison_inputs.iter().all(|input| input.is_empty()) {
+            self.compare_last_days(self.settings.comparison_days);
+            return;
+        }
+        let [prev_start, prev_end, cur_start, cur_end] = &self.comparison_inputs;
+        match (DateRange::new(prev_start, prev_end), DateRange::new(cur_start, cur_end)) {
+            (Some(previous), Some(current)) => {
+                self.comparison = Some(PeriodComparison::new(&self.data.readings, previous, current, self.settings.thresholds));
+                self.comparison_error = None;
+            }
+            _ => self.comparison_error = Some("Enter each period as YYYY/MM/DD to YYYY/MM/DD".to_string()),
+        }
+    }
+    
+    /// Compare the last `days` days with the `days` days before them
+    fn compare_last_days(&mut self, days: u32) {
+        self.comparison = PeriodComparison::last_days(&self.data.readings, days, self.settings.thresholds);
+        self.comparison_error = None;
+        if let Some(comparison) = &self.comparison {
+            let (previous, current) = (&comparison.previous.range, &comparison.current.range);
+            self.comparison_inputs = [previous.start.clone(), previous.end.clone(), current.start.clone(), current.end.clone()];
         }
     }
     
@@ -267,45 +466,28 @@ impl AccuChekApp {
         self.sync_status = SyncStatus::Syncing;
         self.last_sync_message = "Connecting to device...".to_string();
         
-        let db_path = self.db_path.clone();
+        let mut target = self.profile.clone();
         
         thread::spawn(move || {
             let _ = tx.send(SyncMessage::Started);
             
-            let config = Config::load(crate::config::config_file_path())
-                .or_else(|_| Config::load("config.txt"))
-                .unwrap_or_default();
-            
-            match rusb::Context::new() {
-                Ok(context) => {
-                    match find_and_operate_accuchek(&context, &config, None) {
-                        Ok(readings) => {
-                            let total = readings.len();
-                            match Storage::new(&db_path) {
-                                Ok(storage) => {
-                                    match storage.import_readings(&readings) {
-                                        Ok(new_count) => {
-                                            let _ = tx.send(SyncMessage::Success { new_count, total_from_device: total });
-                                        }
-                                        Err(e) => {
-                                            let _ = tx.send(SyncMessage::Error(format!("Database error: {}", e)));
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    let _ = tx.send(SyncMessage::Error(format!("Cannot open database: {}", e)));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let _ = tx.send(SyncMessage::Error(format!("{}", e)));
-                        }
-                    }
-                }
-                Err(e) => {
-                    let _ = tx.send(SyncMessage::Error(format!("USB error: {}", e)));
-                }
+            let config = load_config();
+            
+            let mut session = SyncSession::begin();
+            let result = run_sync(&config, &mut target, &mut session);
+            match &result {
+                Ok((report, _)) => session.complete(report),
+                Err(e) => session.fail(e),
             }
+            let db_path = target.database_path(&config);
+            if let Err(e) = Storage::new(&db_path).and_then(|storage| storage.record_sync_session(&session)) {
+                log::warn!("Could not record sync session: {}", e);
+            }
+            
+            let _ = tx.send(match result {
+                Ok((report, total)) => SyncMessage::Success { report, total_from_device: total, profile: target.name },
+                Err(e) => SyncMessage::Error(e),
+            });
         });
     }
     
@@ -328,13 +510,22 @@ impl AccuChekApp {
                 SyncMessage::Started => {
                     self.last_sync_message = "Syncing...".to_string();
                 }
-                SyncMessage::Success { new_count, total_from_device } => {
+                SyncMessage::Success { report, total_from_device, profile } => {
                     self.sync_status = SyncStatus::Success;
                     self.last_sync_message.clear();
-                    self.notifications.push(Notification::new(
-                        format!("✓ Synced! {} new readings ({} from device)", new_count, total_from_device),
-                        NotificationType::Success
-                    ));
+                    let mut message = format!("✓ Synced! {} new readings ({} from device)", report.inserted, total_from_device);
+                    if report.conflicts > 0 || report.rejected > 0 {
+                        message.push_str(&format!(", {} conflicting, {} rejected", report.conflicts, report.rejected));
+                    }
+                    if profile != self.profile.name {
+                        message.push_str(&format!(" into profile '{}'", profile));
+                    }
+                    // Meter assignments may have changed
+                    match Profiles::load() {
+                        Ok(profiles) => self.profiles = profiles,
+                        Err(e) => log::warn!("Could not reload profiles: {}", e),
+                    }
+                    self.notifications.push(Notification::new(message, NotificationType::Success));
                     should_refresh = true;
                     clear_receiver = true;
                 }
@@ -345,6 +536,8 @@ impl AccuChekApp {
                         format!("✗ Sync Error: {}", e),
                         NotificationType::Error
                     ));
+                    // The failed attempt is recorded in the sync history
+                    should_refresh = true;
                     clear_receiver = true;
                 }
             }
@@ -369,13 +562,35 @@ impl AccuChekApp {
             .set_file_name(&default_name)
             .save_file()
         {
-            let export_stats = ExportStatistics::generate(&self.data.readings, self.settings.thresholds);
-            let exporter = PdfExporter::new(
-                &self.data.readings,
-                &export_stats,
+            let segment = self.export_tag.as_deref().map(|tag| TagSegment::new(tag, &self.data.readings, &self.settings));
+            let (readings, analysis) = match &segment {
+                Some(segment) => (&segment.readings[..], &segment.analysis),
+                None => (&self.data.readings[..], &self.data.analysis),
+            };
+            // The Dashboard's comparison covers all readings; redo it for the tag's
+            let tagged_comparison = segment.as_ref().zip(self.comparison.as_ref()).map(|(segment, comparison)| {
+                PeriodComparison::new(
+                    &segment.readings,
+                    comparison.previous.range.clone(),
+                    comparison.current.range.clone(),
+                    self.settings.thresholds,
+                )
+            });
+            let comparison = if segment.is_some() { tagged_comparison.as_ref() } else { self.comparison.as_ref() };
+            
+            let mut exporter = PdfExporter::new(
+                readings,
+                &self.data.events,
+                analysis,
                 self.settings.thresholds,
                 self.settings.glucose_unit,
             );
+            if let Some(comparison) = comparison.filter(|_| self.settings.pdf_comparison) {
+                exporter = exporter.with_comparison(comparison);
+            }
+            if let Some(segment) = &segment {
+                exporter = exporter.with_tag(&segment.tag);
+            }
             
             match exporter.export(&path) {
                 Ok(()) => {
@@ -401,34 +616,73 @@ impl AccuChekApp {
     
     fn save_note(&mut self, id: i64, note: &str) {
         if let Ok(storage) = Storage::new(&self.db_path) {
-            let _ = storage.update_note(id, note);
+            let _ = storage.update_note(id, note, ChangeSource::Gui);
             self.refresh_data();
         }
     }
     
     fn save_tags(&mut self, id: i64, tags: &str) {
         if let Ok(storage) = Storage::new(&self.db_path) {
-            let _ = storage.update_tags(id, tags);
+            let _ = storage.update_tags(id, tags, ChangeSource::Gui);
             self.refresh_data();
         }
     }
     
-    fn get_reading_color(&self, mg_dl: u16) -> egui::Color32 {
-        match self.settings.thresholds.classify(mg_dl) {
-            GlucoseRange::VeryLow => egui::Color32::from_rgb(200, 50, 50),
-            GlucoseRange::Low => egui::Color32::from_rgb(255, 100, 100),
-            GlucoseRange::InRange => egui::Color32::from_rgb(100, 255, 100),
-            GlucoseRange::High => egui::Color32::from_rgb(255, 180, 100),
-            GlucoseRange::VeryHigh => egui::Color32::from_rgb(255, 100, 50),
+    fn exclude_reading(&mut self, id: i64, reason: &str) {
+        if let Ok(storage) = Storage::new(&self.db_path) {
+            let _ = storage.exclude_reading(id, reason, ChangeSource::Gui);
+            self.refresh_data();
+        }
+    }
+    
+    fn restore_reading(&mut self, id: i64) {
+        if let Ok(storage) = Storage::new(&self.db_path) {
+            let _ = storage.restore_reading(id, ChangeSource::Gui);
+            self.refresh_data();
+        }
+    }
+    
+    /// Log an event at the time of a reading, linked to it
+    fn add_event(&mut self, reading: &StoredReading) {
+        let amount = (self.event_kind != EventKind::Medication).then_some(self.event_amount);
+        let detail = Some(self.event_detail_buffer.trim().to_string());
+        let Some(mut event) = Event::new(self.event_kind, &reading.timestamp, amount, detail) else {
+            return;
+        };
+        event.reading_id = Some(reading.id);
+        
+        if let Ok(storage) = Storage::new(&self.db_path) {
+            let _ = storage.add_event(&event);
+            self.event_detail_buffer.clear();
+            self.refresh_data();
+        }
+    }
+    
+    fn delete_event(&mut self, id: i64) {
+        if let Ok(storage) = Storage::new(&self.db_path) {
+            let _ = storage.delete_event(id);
+            self.refresh_data();
         }
     }
     
+    fn load_history(&mut self, id: i64) {
+        let entries = Storage::new(&self.db_path)
+            .and_then(|storage| storage.get_history(id))
+            .unwrap_or_default();
+        self.reading_history = Some((id, entries));
+    }
+    
+    fn get_reading_color(&self, mg_dl: u16) -> egui::Color32 {
+        range_color(self.settings.thresholds.classify(mg_dl))
+    }
+    
     fn filtered_readings(&self) -> Vec<&StoredReading> {
+        let listed = self.data.all_readings.iter().filter(|r| self.settings.show_excluded || !r.excluded);
         if self.search_query.is_empty() {
-            self.data.readings.iter().collect()
+            listed.collect()
         } else {
             let query = self.search_query.to_lowercase();
-            self.data.readings.iter().filter(|r| {
+            listed.filter(|r| {
                 r.timestamp.to_lowercase().contains(&query) ||
                 r.note.as_ref().map(|n| n.to_lowercase().contains(&query)).unwrap_or(false) ||
                 r.tags.as_ref().map(|t| t.to_lowercase().contains(&query)).unwrap_or(false) ||
@@ -449,6 +703,11 @@ impl AccuChekApp {
 
 impl eframe::App for AccuChekApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.locked {
+            self.show_unlock_screen(ctx);
+            return;
+        }
+        
         self.check_sync_status();
         
         if self.sync_status == SyncStatus::Syncing {
@@ -460,19 +719,29 @@ impl eframe::App for AccuChekApp {
             ui.horizontal(|ui| {
                 ui.heading("Accu-Chek Data Manager");
                 
+                if self.sync_status != SyncStatus::Syncing {
+                    if let Some(profile) = self.profile_selector(ui) {
+                        self.open_profile(profile);
+                    }
+                }
+                
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui.button("[Settings]").clicked() {
                         self.show_settings = !self.show_settings;
                     }
                     
                     if ui.button("Export PDF").clicked() {
-                        self.export_pdf();
+                        self.show_export_options = !self.show_export_options;
                     }
                     
                     if ui.button("Refresh").clicked() {
                         self.refresh_data();
                     }
                     
+                    if ui.button("Sync History").clicked() {
+                        self.show_sync_history = !self.show_sync_history;
+                    }
+                    
                     let sync_enabled = self.sync_status != SyncStatus::Syncing;
                     if ui.add_enabled(sync_enabled, egui::Button::new("Sync Device")).clicked() {
                         self.start_sync();
@@ -494,6 +763,16 @@ impl eframe::App for AccuChekApp {
             self.show_settings_window(ctx);
         }
         
+        // Sync history window
+        if self.show_sync_history {
+            self.show_sync_history_window(ctx);
+        }
+        
+        // Export options
+        if self.show_export_options {
+            self.show_export_options_window(ctx);
+        }
+        
         // Export dialog
         if self.show_export_dialog {
             self.show_export_dialog_window(ctx);
@@ -516,6 +795,159 @@ impl eframe::App for AccuChekApp {
 // ============= UI Components =============
 
 impl AccuChekApp {
+    fn show_unlock_screen(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.heading("Encrypted Database");
+                ui.add_space(5.0);
+                ui.monospace(&self.db_path);
+                ui.add_space(5.0);
+                if let Some(profile) = self.profile_selector(ui) {
+                    self.open_profile(profile);
+                }
+                ui.add_space(15.0);
+                
+                ui.label("Passphrase:");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.passphrase_buffer)
+                        .password(true)
+                        .desired_width(250.0)
+                );
+                response.request_focus();
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                
+                ui.add_space(10.0);
+                if ui.button("Unlock").clicked() || submitted {
+                    self.unlock();
+                }
+                
+                if let Some(ref error) = self.unlock_error {
+                    ui.add_space(10.0);
+                    ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+                }
+            });
+        });
+    }
+    
+    /// Editor for the time bins; returns true if a bin changed
+    fn time_bins_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut remove = None;
+        
+        egui::Grid::new("time_bins_grid")
+            .num_columns(3)
+            .spacing([8.0, 4.0])
+            .show(ui, |ui| {
+                for (i, bin) in self.settings.time_bins.iter_mut().enumerate() {
+                    changed |= ui.add(egui::TextEdit::singleline(&mut bin.name).desired_width(120.0)).changed();
+                    ui.horizontal(|ui| {
+                        changed |= ui.add(minute_drag_value(&mut bin.start_minute)).changed();
+                        ui.label("to");
+                        changed |= ui.add(minute_drag_value(&mut bin.end_minute)).changed();
+                    });
+                    if ui.small_button("🗑").on_hover_text("Remove bin").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        
+        if let Some(i) = remove {
+            self.settings.time_bins.remove(i);
+            changed = true;
+        }
+        
+        ui.horizontal(|ui| {
+            if ui.button("Add Bin").clicked() {
+                self.settings.time_bins.push(TimeBin::new("New bin", 0, 24 * 60));
+                changed = true;
+            }
+            if ui.button("Reset to Defaults").clicked() {
+                self.settings.time_bins = default_time_bins();
+                changed = true;
+            }
+        });
+        ui.weak("A bin that starts later than it ends wraps past midnight, e.g. 22:00 to 06:00.");
+        
+        changed
+    }
+    
+    /// Editor for the automatic tagging rules; returns true if a rule changed
+    fn tag_rules_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut remove = None;
+        
+        egui::Grid::new("tag_rules_grid")
+            .num_columns(4)
+            .spacing([8.0, 4.0])
+            .show(ui, |ui| {
+                for (i, rule) in self.settings.tag_rules.iter_mut().enumerate() {
+                    changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                    changed |= ui.add(egui::TextEdit::singleline(&mut rule.tag).desired_width(90.0)).changed();
+                    ui.horizontal(|ui| {
+                        ui.label(rule.condition.label());
+                        match &mut rule.condition {
+                            RuleCondition::FirstInWindow { start_minute, end_minute }
+                            | RuleCondition::InWindow { start_minute, end_minute } => {
+                                changed |= ui.add(minute_drag_value(start_minute)).changed();
+                                ui.label("and");
+                                changed |= ui.add(minute_drag_value(end_minute)).changed();
+                            }
+                            RuleCondition::AfterCarbs { minutes } | RuleCondition::BeforeCarbs { minutes } => {
+                                changed |= ui.add(egui::DragValue::new(minutes).range(5..=360).speed(5.0).suffix(" min")).changed();
+                            }
+                        }
+                    });
+                    if ui.small_button("🗑").on_hover_text("Remove rule").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        
+        if let Some(i) = remove {
+            self.settings.tag_rules.remove(i);
+            changed = true;
+        }
+        
+        ui.horizontal(|ui| {
+            ui.menu_button("Add Rule", |ui| {
+                for condition in RuleCondition::TEMPLATES {
+                    if ui.button(condition.label()).clicked() {
+                        self.settings.tag_rules.push(TagRule::new("", condition));
+                        changed = true;
+                        ui.close();
+                    }
+                }
+            });
+            if ui.button("Apply to All Readings").clicked() {
+                self.rerun_tag_rules();
+            }
+        });
+        ui.weak("The first matching rule tags a reading at import. Tags set by hand are never changed.");
+        
+        changed
+    }
+    
+    /// Re-apply the tagging rules to the whole history
+    fn rerun_tag_rules(&mut self) {
+        let result = Storage::new(&self.db_path)
+            .and_then(|storage| storage.apply_tag_rules(&self.settings.tag_rules, true));
+        match result {
+            Ok(changed) => {
+                self.notifications.push(Notification::new(
+                    format!("Retagged {} readings", changed),
+                    NotificationType::Success
+                ));
+                self.refresh_data();
+            }
+            Err(e) => {
+                self.notifications.push(Notification::new(format!("✗ Tagging failed: {}", e), NotificationType::Error));
+            }
+        }
+    }
+    
     fn show_settings_window(&mut self, ctx: &egui::Context) {
         let mut save_settings = false;
         
@@ -591,6 +1023,71 @@ impl AccuChekApp {
                 ui.separator();
                 ui.add_space(5.0);
                 
+                ui.heading("Testing");
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.label("Target tests per day:");
+                    if ui.add(egui::DragValue::new(&mut self.settings.tests_per_day_target).range(1..=12).speed(0.1)).changed() {
+                        save_settings = true;
+                    }
+                });
+                
+                ui.add_space(10.0);
+                ui.separator();
+                ui.add_space(5.0);
+                
+                ui.heading("Time Bins");
+                ui.add_space(5.0);
+                if self.time_bins_ui(ui) {
+                    save_settings = true;
+                }
+                
+                ui.add_space(10.0);
+                ui.separator();
+                ui.add_space(5.0);
+                
+                ui.heading("Automatic Tags");
+                ui.add_space(5.0);
+                if self.tag_rules_ui(ui) {
+                    save_settings = true;
+                }
+                
+                ui.add_space(10.0);
+                ui.separator();
+                ui.add_space(5.0);
+                
+                ui.heading("Profiles");
+                ui.add_space(5.0);
+                
+                egui::Grid::new("profiles_grid")
+                    .num_columns(2)
+                    .spacing([10.0, 4.0])
+                    .show(ui, |ui| {
+                        for profile in &self.profiles.profiles {
+                            ui.label(&profile.name);
+                            if profile.meter_serials.is_empty() {
+                                ui.weak("no meters assigned");
+                            } else {
+                                ui.label(format!("meters: {}", profile.meter_serials.join(", ")));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.weak("A meter is assigned to the profile it is first synced into.");
+                
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.new_profile_name)
+                        .hint_text("New profile name")
+                        .desired_width(150.0));
+                    if ui.button("Add Profile").clicked() {
+                        self.add_profile();
+                    }
+                });
+                
+                ui.add_space(10.0);
+                ui.separator();
+                ui.add_space(5.0);
+                
                 ui.heading("Data Locations");
                 ui.add_space(5.0);
                 
@@ -605,6 +1102,18 @@ impl AccuChekApp {
                         ui.label("Data folder:");
                         ui.monospace(crate::config::get_data_dir().to_string_lossy().to_string());
                         ui.end_row();
+                        
+                        ui.label("Backups:");
+                        ui.monospace(self.profile.backup_dir().to_string_lossy().to_string());
+                        ui.end_row();
+                        
+                        ui.label("Encryption:");
+                        ui.label(if self.encrypted {
+                            "On (change with 'accuchek db encrypt' / 'db decrypt')"
+                        } else {
+                            "Off (enable with 'accuchek db encrypt')"
+                        });
+                        ui.end_row();
                     });
                 
                 ui.add_space(10.0);
@@ -621,21 +1130,98 @@ impl AccuChekApp {
             });
         
         if save_settings {
-            self.settings.save();
+            self.settings.save(self.profile.settings_path());
             self.refresh_data();
         }
     }
     
-    fn show_export_dialog_window(&mut self, ctx: &egui::Context) {
-        if let Some(ref path) = self.exported_path.clone() {
-            egui::Window::new("Export Successful")
-                .collapsible(false)
-                .resizable(false)
-                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
-                .show(ctx, |ui| {
-                    ui.label("PDF exported successfully!");
-                    ui.add_space(5.0);
-                    ui.label(format!("{}", path.display()));
+    fn show_sync_history_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_sync_history;
+        
+        egui::Window::new("Sync History")
+            .open(&mut open)
+            .default_width(720.0)
+            .show(ctx, |ui| {
+                let sessions = &self.data.sync_sessions;
+                if sessions.is_empty() {
+                    ui.label("No syncs recorded yet.");
+                    return;
+                }
+                
+                ui.label(format!("{} syncs, last on {}", sessions.len(), sessions[0].started_at));
+                ui.add_space(5.0);
+                
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    egui::Grid::new("sync_history_grid")
+                        .num_columns(8)
+                        .striped(true)
+                        .spacing([12.0, 4.0])
+                        .show(ui, |ui| {
+                            for header in ["Started", "Finished", "Meter", "Received", "New", "Duplicates", "Errors", "Clock drift"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+                            
+                            for session in sessions {
+                                ui.monospace(&session.started_at);
+                                ui.monospace(&session.finished_at);
+                                ui.label(session.meter_label());
+                                ui.label(session.received.to_string());
+                                ui.label(session.inserted.to_string());
+                                ui.label(session.duplicates.to_string());
+                                let errors = ui.label(session.error_count().to_string());
+                                if let Some(error) = &session.error {
+                                    errors.on_hover_text(error);
+                                }
+                                ui.label(session.clock_drift_label());
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        
+        self.show_sync_history = open;
+    }
+    
+    /// What to include in the PDF before picking where to save it
+    fn show_export_options_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_export_options;
+        let mut export = false;
+        let tags = distinct_tags(&self.data.readings);
+        
+        egui::Window::new("Export PDF")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Readings:");
+                    tag_selector(ui, "export_tag", &tags, &mut self.export_tag);
+                });
+                ui.checkbox(&mut self.settings.pdf_comparison, "Include period comparison");
+                ui.add_space(10.0);
+                if ui.button("Export...").clicked() {
+                    export = true;
+                }
+            });
+        
+        self.show_export_options = open && !export;
+        if export {
+            self.settings.save(self.profile.settings_path());
+            self.export_pdf();
+        }
+    }
+    
+    fn show_export_dialog_window(&mut self, ctx: &egui::Context) {
+        if let Some(ref path) = self.exported_path.clone() {
+            egui::Window::new("Export Successful")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label("PDF exported successfully!");
+                    ui.add_space(5.0);
+                    ui.label(format!("{}", path.display()));
                     ui.add_space(15.0);
                     
                     ui.horizontal(|ui| {
@@ -683,10 +1269,12 @@ impl AccuChekApp {
                 ui.label(format!("Target: {}", self.settings.thresholds.format_range(self.settings.glucose_unit)));
                 ui.add_space(10.0);
                 
-                if let Some(ref tir) = self.data.time_in_range {
-                    self.render_tir_bars(ui, tir);
-                    ui.add_space(10.0);
-                    ui.label(format!("Total readings: {}", tir.total));
+                let tir = &self.data.analysis.tir;
+                self.render_tir_bars(ui, tir);
+                ui.add_space(10.0);
+                ui.label(format!("Total readings: {}", tir.total));
+                if self.data.excluded_count > 0 {
+                    ui.label(egui::RichText::new(format!("{} excluded readings not counted", self.data.excluded_count)).small());
                 }
             });
             
@@ -695,7 +1283,7 @@ impl AccuChekApp {
                 ui.heading("Summary");
                 ui.add_space(10.0);
                 
-                if let Some(ref stats) = self.data.basic_stats {
+                if let Some(ref stats) = self.data.analysis.basic {
                     ui.horizontal(|ui| {
                         ui.label("Average:");
                         ui.colored_label(
@@ -719,6 +1307,10 @@ impl AccuChekApp {
                             self.settings.glucose_unit.format(stats.mgdl.max, stats.mmol.max)
                         );
                     });
+                    
+                    ui.add_space(10.0);
+                    ui.separator();
+                    self.show_a1c_estimate(ui);
                 }
                 
                 if let Some(latest) = self.data.readings.last() {
@@ -736,6 +1328,22 @@ impl AccuChekApp {
             });
         });
         
+        if let Some(variability) = &self.data.analysis.variability {
+            ui.add_space(10.0);
+            self.show_variability(ui, variability);
+        }
+        
+        ui.add_space(10.0);
+        self.show_insights(ui);
+        
+        if let Some(adherence) = &self.data.analysis.adherence {
+            ui.add_space(10.0);
+            self.show_adherence(ui, adherence);
+        }
+        
+        ui.add_space(10.0);
+        self.show_comparison(ui);
+        
         ui.add_space(20.0);
         
         // Recent readings
@@ -772,6 +1380,248 @@ impl AccuChekApp {
         });
     }
     
+    /// Glycemic variability and risk indices of all shown readings
+    fn show_variability(&self, ui: &mut egui::Ui, variability: &VariabilityStats) {
+        let unit = self.settings.glucose_unit;
+        let good = egui::Color32::from_rgb(60, 160, 60);
+        let warn = egui::Color32::from_rgb(230, 140, 40);
+        
+        ui.group(|ui| {
+            ui.heading("Variability");
+            egui::Grid::new("variability_grid")
+                .num_columns(4)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    ui.label("CV:");
+                    ui.colored_label(
+                        if variability.is_stable() { good } else { warn },
+                        format!("{:.1}% ({} target ≤{:.0}%)",
+                            variability.cv_percent,
+                            if variability.is_stable() { "meets" } else { "above" },
+                            STABLE_CV_PERCENT),
+                    );
+                    ui.label("MAGE:");
+                    ui.label(match variability.mage(unit) {
+                        Some(mage) if unit == GlucoseUnit::MgDl => format!("{:.0} mg/dL", mage),
+                        Some(mage) => format!("{:.1} mmol/L", mage),
+                        None => "-".to_string(),
+                    });
+                    ui.end_row();
+                    
+                    ui.label("LBGI / HBGI:");
+                    ui.label(format!("{:.1} / {:.1}", variability.lbgi, variability.hbgi));
+                    ui.label("ADRR:");
+                    ui.label(format!("{:.1}", variability.adrr));
+                    ui.end_row();
+                    
+                    ui.label("J-index:");
+                    ui.label(format!("{:.1}", variability.j_index));
+                    ui.label("GRI:");
+                    ui.label(format!("{:.0} (zone {})", variability.gri, variability.gri_zone()));
+                    ui.end_row();
+                });
+            
+            let (stable, days) = self.data.analysis.stable_days();
+            if days > 0 {
+                ui.label(egui::RichText::new(format!("{} of {} days with CV ≤{:.0}%", stable, days, STABLE_CV_PERCENT)).small());
+            }
+        });
+    }
+    
+    /// Recurring patterns found in the shown readings, lows first
+    fn show_insights(&self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Insights");
+            let insights = &self.data.insights;
+            if insights.is_empty() {
+                ui.label(egui::RichText::new("No recurring patterns found").weak());
+                return;
+            }
+            for insight in insights {
+                let text = insight.describe(self.settings.glucose_unit, &self.settings.thresholds);
+                match insight.range() {
+                    Some(range) => ui.colored_label(range_color(range), format!("• {}", text)),
+                    None => ui.label(format!("• {}", text)),
+                };
+            }
+        });
+    }
+    
+    /// How often readings were taken, against the tests-per-day target
+    fn show_adherence(&self, ui: &mut egui::Ui, adherence: &Adherence) {
+        let good = egui::Color32::from_rgb(60, 160, 60);
+        let warn = egui::Color32::from_rgb(230, 140, 40);
+        
+        ui.group(|ui| {
+            ui.heading("Testing");
+            egui::Grid::new("adherence_grid")
+                .num_columns(4)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    ui.label("Tests per day:");
+                    ui.colored_label(
+                        if adherence.meets_target() { good } else { warn },
+                        format!("{:.1} (target {})", adherence.tests_per_day(), adherence.target_per_day),
+                    );
+                    ui.label("Days on target:");
+                    ui.label(format!("{} of {} ({:.0}%)", adherence.days_on_target, adherence.days, adherence.days_on_target_percent()));
+                    ui.end_row();
+                    
+                    ui.label("Current streak:");
+                    ui.label(format!("{} d", adherence.current_streak));
+                    ui.label("Longest streak:");
+                    ui.label(format!("{} d", adherence.longest_streak));
+                    ui.end_row();
+                    
+                    ui.label("Days without tests:");
+                    ui.colored_label(
+                        if adherence.zero_test_days.is_empty() { good } else { warn },
+                        adherence.zero_test_days.len().to_string(),
+                    );
+                    ui.label("Longest gap:");
+                    match &adherence.longest_gap {
+                        Some(gap) => ui.colored_label(
+                            if gap.hours >= LONG_GAP_HOURS { warn } else { good },
+                            format!("{:.0} h (from {})", gap.hours, gap.from),
+                        ),
+                        None => ui.label("-"),
+                    };
+                    ui.end_row();
+                });
+            
+            ui.horizontal_wrapped(|ui| {
+                for bin in &adherence.by_time_bin {
+                    ui.label(egui::RichText::new(format!(
+                        "{}: {:.1}/day, {:.0}% of days",
+                        bin.name, bin.per_day(adherence.days), bin.days_percent(adherence.days),
+                    )).small());
+                }
+            });
+            
+            if !adherence.long_gaps.is_empty() {
+                ui.label(egui::RichText::new(format!(
+                    "{} gaps of {:.0} h or more between tests", adherence.long_gaps.len(), LONG_GAP_HOURS,
+                )).small().color(warn));
+            }
+        });
+    }
+    
+    /// Two date ranges side by side with the change in each metric
+    fn show_comparison(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.heading("Compare Periods");
+            ui.horizontal(|ui| {
+                ui.label("Last");
+                for days in [7, 14, 30, 90] {
+                    if ui.selectable_label(self.settings.comparison_days == days, format!("{} d", days)).clicked() {
+                        self.settings.comparison_days = days;
+                        self.settings.save(self.profile.settings_path());
+                        self.compare_last_days(days);
+                    }
+                }
+                ui.label("vs the period before");
+            });
+            
+            ui.horizontal(|ui| {
+                let [prev_start, prev_end, cur_start, cur_end] = &mut self.comparison_inputs;
+                ui.label("Previous:");
+                ui.add(egui::TextEdit::singleline(prev_start).desired_width(80.0));
+                ui.label("to");
+                ui.add(egui::TextEdit::singleline(prev_end).desired_width(80.0));
+                ui.add_space(10.0);
+                ui.label("Current:");
+                ui.add(egui::TextEdit::singleline(cur_start).desired_width(80.0));
+                ui.label("to");
+                ui.add(egui::TextEdit::singleline(cur_end).desired_width(80.0));
+                if ui.button("Compare").clicked() {
+                    self.update_comparison();
+                }
+            });
+            if let Some(error) = &self.comparison_error {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+            }
+            if ui.checkbox(&mut self.settings.pdf_comparison, "Include in PDF export").changed() {
+                self.settings.save(self.profile.settings_path());
+            }
+            
+            let Some(comparison) = &self.comparison else {
+                return;
+            };
+            let deltas = comparison.deltas(self.settings.glucose_unit);
+            if deltas.is_empty() {
+                ui.label("Not enough readings in both periods to compare.");
+                return;
+            }
+            
+            egui::Grid::new("comparison_grid")
+                .num_columns(5)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(egui::RichText::new(format!("Previous ({})", comparison.previous.tir.total)).strong());
+                    ui.label(egui::RichText::new(format!("Current ({})", comparison.current.tir.total)).strong());
+                    ui.label(egui::RichText::new("Change").strong());
+                    ui.label("");
+                    ui.end_row();
+                    
+                    for delta in &deltas {
+                        ui.label(delta.metric);
+                        ui.label(format!("{:.1} {}", delta.previous, delta.unit));
+                        ui.label(format!("{:.1} {}", delta.current, delta.unit));
+                        let arrow = if delta.change > 0.0 { "▲" } else if delta.change < 0.0 { "▼" } else { "=" };
+                        let (verdict, color) = match delta.direction {
+                            Direction::Improved => ("better", egui::Color32::from_rgb(60, 160, 60)),
+                            Direction::Worsened => ("worse", egui::Color32::from_rgb(230, 100, 60)),
+                            Direction::Unchanged => ("same", egui::Color32::GRAY),
+                        };
+                        ui.colored_label(color, format!("{} {:+.1} ({})", arrow, delta.change, verdict));
+                        ui.label(match delta.significant {
+                            Some(true) => "significant",
+                            Some(false) => "not significant",
+                            None => "",
+                        });
+                        ui.end_row();
+                    }
+                });
+            ui.label(egui::RichText::new("Significance: two-sided z-test at p < 0.05 on the readings of each period").small());
+        });
+    }
+    
+    /// GMI and eA1c over the selected window, with a warning when data is sparse
+    fn show_a1c_estimate(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Estimated A1c over");
+            for days in A1C_WINDOWS {
+                if ui.selectable_value(&mut self.settings.a1c_window_days, days, format!("{} d", days)).clicked() {
+                    self.settings.save(self.profile.settings_path());
+                }
+            }
+        });
+        
+        let Some(estimate) = self.data.analysis.a1c_for(self.settings.a1c_window_days) else {
+            return;
+        };
+        egui::Grid::new("a1c_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("GMI:");
+                ui.strong(format!("{:.1}% ({:.0} mmol/mol)", estimate.gmi_percent, estimate.gmi_mmol_mol));
+                ui.end_row();
+                
+                ui.label("eA1c (ADAG):");
+                ui.strong(format!("{:.1}% ({:.0} mmol/mol)", estimate.ea1c_percent, estimate.ea1c_mmol_mol));
+                ui.end_row();
+            });
+        ui.label(egui::RichText::new(format!(
+            "{} readings, {} to {}", estimate.readings, estimate.start_date, estimate.end_date
+        )).small());
+        if let Some(warning) = estimate.density_warning() {
+            ui.colored_label(egui::Color32::from_rgb(230, 140, 40), format!("⚠ {}", warning));
+        }
+    }
+    
     fn render_tir_bars(&self, ui: &mut egui::Ui, tir: &TimeInRange) {
         let total = tir.total as f32;
         if total == 0.0 {
@@ -812,6 +1662,11 @@ impl AccuChekApp {
             if ui.button("X").clicked() {
                 self.search_query.clear();
             }
+            ui.add_space(20.0);
+            if ui.checkbox(&mut self.settings.show_excluded, format!("Show excluded ({})", self.data.excluded_count)).changed() {
+                self.settings.save(self.profile.settings_path());
+                self.selected_reading = None;
+            }
         });
         ui.separator();
         
@@ -831,11 +1686,12 @@ impl AccuChekApp {
             .map(|(idx, r)| (
                 idx,
                 format!(
-                    "{} | {} {}{}",
+                    "{} | {} {}{}{}",
                     r.timestamp,
                     self.settings.glucose_unit.format_value(r.mg_dl, r.mmol_l),
                     if r.note.is_some() { "*" } else { "" },
-                    if r.tags.is_some() { " #" } else { "" }
+                    if r.tags.is_some() { " #" } else { "" },
+                    if r.excluded { " [excluded]" } else { "" }
                 ),
                 self.selected_reading == Some(idx),
                 r.note.clone(),
@@ -843,15 +1699,9 @@ impl AccuChekApp {
             ))
             .collect();
         
-        let selected_details: Option<(i64, String, u16, f64, String)> = 
+        let selected_details: Option<StoredReading> = 
             self.selected_reading.and_then(|idx| {
-                filtered.iter().rev().nth(idx).map(|r| (
-                    r.id,
-                    r.timestamp.clone(),
-                    r.mg_dl,
-                    r.mmol_l,
-                    r.imported_at.clone(),
-                ))
+                filtered.iter().rev().nth(idx).map(|r| (*r).clone())
             });
         
         ui.columns(2, |columns| {
@@ -865,12 +1715,18 @@ impl AccuChekApp {
                             self.selected_reading = Some(*idx);
                             self.note_edit_buffer = note.clone().unwrap_or_default();
                             self.tag_edit_buffer = tags.clone().unwrap_or_default();
+                            self.exclusion_reason_buffer.clear();
                         }
                     }
                 });
             
             columns[1].group(|ui| {
-                if let Some((reading_id, timestamp, mg_dl, mmol_l, imported_at)) = selected_details {
+                if let Some(reading) = selected_details {
+                    let reading_id = reading.id;
+                    if self.reading_history.as_ref().map(|(id, _)| *id) != Some(reading_id) {
+                        self.load_history(reading_id);
+                    }
+                    let (mg_dl, mmol_l) = (reading.mg_dl, reading.mmol_l);
                     ui.heading("Reading Details");
                     ui.separator();
                     
@@ -879,7 +1735,7 @@ impl AccuChekApp {
                         .spacing([10.0, 8.0])
                         .show(ui, |ui| {
                             ui.label("Timestamp:");
-                            ui.label(&timestamp);
+                            ui.label(&reading.timestamp);
                             ui.end_row();
                             
                             ui.label("Glucose:");
@@ -895,8 +1751,17 @@ impl AccuChekApp {
                             ui.end_row();
                             
                             ui.label("Imported:");
-                            ui.label(&imported_at);
+                            ui.label(&reading.imported_at);
                             ui.end_row();
+                            
+                            if reading.excluded {
+                                ui.label("Excluded:");
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(200, 200, 100),
+                                    reading.exclusion_reason.as_deref().unwrap_or("yes")
+                                );
+                                ui.end_row();
+                            }
                         });
                     
                     ui.add_space(15.0);
@@ -923,12 +1788,113 @@ impl AccuChekApp {
                             self.add_tag("fasting");
                         }
                         if ui.small_button("+ before_meal").clicked() {
-                            self.add_tag("before_meal");
+                            self.add_tag(BEFORE_MEAL_TAG);
                         }
                         if ui.small_button("+ after_meal").clicked() {
-                            self.add_tag("after_meal");
+                            self.add_tag(AFTER_MEAL_TAG);
                         }
                     });
+                    
+                    ui.add_space(10.0);
+                    ui.separator();
+                    
+                    if reading.excluded {
+                        ui.label("This reading is excluded from statistics and reports.");
+                        if ui.button("Restore Reading").clicked() {
+                            self.restore_reading(reading_id);
+                        }
+                    } else {
+                        ui.label("Exclusion reason:");
+                        ui.text_edit_singleline(&mut self.exclusion_reason_buffer);
+                        
+                        ui.horizontal(|ui| {
+                            let reason = self.exclusion_reason_buffer.trim().to_string();
+                            if ui.add_enabled(!reason.is_empty(), egui::Button::new("Exclude Reading")).clicked() {
+                                self.exclude_reading(reading_id, &reason);
+                            }
+                            
+                            if ui.small_button("control solution").clicked() {
+                                self.exclusion_reason_buffer = "control solution".to_string();
+                            }
+                            if ui.small_button("unwashed finger").clicked() {
+                                self.exclusion_reason_buffer = "unwashed finger".to_string();
+                            }
+                        });
+                    }
+                    
+                    ui.add_space(10.0);
+                    ui.separator();
+                    
+                    ui.label("Events:");
+                    let linked: Vec<Event> = self.data.events.iter()
+                        .filter(|e| e.reading_id == Some(reading_id))
+                        .cloned()
+                        .collect();
+                    for event in &linked {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{}: {}", event.kind.label(), event.summary()));
+                            if ui.small_button("Remove").clicked() {
+                                self.delete_event(event.id);
+                            }
+                        });
+                    }
+                    
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("event_kind")
+                            .selected_text(self.event_kind.label())
+                            .show_ui(ui, |ui| {
+                                for kind in EventKind::ALL {
+                                    ui.selectable_value(&mut self.event_kind, kind, kind.label());
+                                }
+                            });
+                        if let Some(unit) = self.event_kind.unit() {
+                            ui.add(egui::DragValue::new(&mut self.event_amount).range(0.0..=1000.0).speed(1.0).suffix(format!(" {}", unit)));
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut self.event_detail_buffer)
+                            .hint_text(if self.event_kind == EventKind::Medication { "name" } else { "detail (optional)" })
+                            .desired_width(120.0));
+                        let can_add = self.event_kind != EventKind::Medication || !self.event_detail_buffer.trim().is_empty();
+                        if ui.add_enabled(can_add, egui::Button::new("Add Event")).clicked() {
+                            self.add_event(&reading);
+                        }
+                    });
+                    
+                    ui.add_space(10.0);
+                    
+                    if let Some((_, entries)) = &self.reading_history {
+                        egui::CollapsingHeader::new(format!("History ({} changes)", entries.len()))
+                            .id_salt("reading_history")
+                            .show(ui, |ui| {
+                                if entries.is_empty() {
+                                    ui.label("No changes recorded.");
+                                    return;
+                                }
+                                
+                                egui::Grid::new("reading_history_grid")
+                                    .num_columns(4)
+                                    .spacing([10.0, 4.0])
+                                    .striped(true)
+                                    .show(ui, |ui| {
+                                        ui.label(egui::RichText::new("When").strong());
+                                        ui.label(egui::RichText::new("Field").strong());
+                                        ui.label(egui::RichText::new("Change").strong());
+                                        ui.label(egui::RichText::new("Source").strong());
+                                        ui.end_row();
+                                        
+                                        for entry in entries.iter().rev() {
+                                            ui.label(&entry.changed_at);
+                                            ui.label(&entry.field);
+                                            ui.label(format!(
+                                                "{} → {}",
+                                                entry.old_value.as_deref().unwrap_or("-"),
+                                                entry.new_value.as_deref().unwrap_or("-")
+                                            ));
+                                            ui.label(&entry.source);
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                    }
                 } else {
                     ui.vertical_centered(|ui| {
                         ui.add_space(50.0);
@@ -949,15 +1915,45 @@ impl AccuChekApp {
             ui.selectable_value(&mut self.current_chart_view, ChartView::Histogram, "Distribution");
             ui.selectable_value(&mut self.current_chart_view, ChartView::TimeOfDay, "Time of Day");
             ui.selectable_value(&mut self.current_chart_view, ChartView::DailyTrend, "Daily TIR");
+            ui.selectable_value(&mut self.current_chart_view, ChartView::Trend, "Trend");
             ui.selectable_value(&mut self.current_chart_view, ChartView::TimeBins, "Time Bins");
+            ui.selectable_value(&mut self.current_chart_view, ChartView::Agp, "AGP");
+            ui.selectable_value(&mut self.current_chart_view, ChartView::Episodes, "Episodes");
+            ui.selectable_value(&mut self.current_chart_view, ChartView::Meals, "Meals");
+            ui.selectable_value(&mut self.current_chart_view, ChartView::Weekly, "Weeks");
             ui.selectable_value(&mut self.current_chart_view, ChartView::Calendar, "Calendar");
         });
+        ui.horizontal(|ui| {
+            ui.label("Readings:");
+            let mut tag = self.chart_segment.as_ref().map(|segment| segment.tag.clone());
+            if tag_selector(ui, "chart_tag", &distinct_tags(&self.data.readings), &mut tag) {
+                self.set_chart_tag(tag);
+            }
+            
+            ui.add_space(20.0);
+            match self.settings.chart_range {
+                Some([from, to]) => {
+                    ui.label(format!("Dates: {} to {}", timeaxis::format_epoch(from), timeaxis::format_epoch(to)));
+                    if ui.button("Show all dates").clicked() {
+                        self.settings.chart_range = None;
+                        self.chart_range_unsaved = true;
+                    }
+                }
+                None => {
+                    ui.label(egui::RichText::new("Drag or Ctrl+scroll a trend chart to pick dates").weak());
+                }
+            }
+        });
         ui.separator();
         
         if self.data.readings.is_empty() {
             ui.label("No data to display. Sync your device first.");
             return;
         }
+        if let Some(segment) = self.chart_segment.as_ref().filter(|segment| segment.readings.is_empty()) {
+            ui.label(format!("No readings tagged \"{}\".", segment.tag));
+            return;
+        }
         
         egui::ScrollArea::vertical().show(ui, |ui| {
             match self.current_chart_view {
@@ -965,103 +1961,173 @@ impl AccuChekApp {
                 ChartView::Histogram => self.show_histogram_chart(ui),
                 ChartView::TimeOfDay => self.show_time_of_day_chart(ui),
                 ChartView::DailyTrend => self.show_daily_tir_trend(ui),
+                ChartView::Trend => self.show_trend(ui),
                 ChartView::TimeBins => self.show_time_bins_boxplot(ui),
+                ChartView::Agp => self.show_agp_chart(ui),
+                ChartView::Episodes => self.show_episodes(ui),
+                ChartView::Meals => self.show_meal_pairs(ui),
+                ChartView::Weekly => self.show_weekly(ui),
                 ChartView::Calendar => self.show_calendar_view(ui),
             }
         });
+        
+        if let Some(range) = self.chart_range_change.take() {
+            self.settings.chart_range = range;
+            self.chart_range_unsaved = true;
+        }
+        if self.chart_range_unsaved && !ui.input(|i| i.pointer.any_down()) {
+            self.settings.save(self.profile.settings_path());
+            self.chart_range_unsaved = false;
+        }
+    }
+    
+    /// Plot with epoch seconds on the x axis, zoomed and panned along time only
+    fn time_plot<'a>(&self, id: &'a str) -> Plot<'a> {
+        Plot::new(id)
+            .x_axis_formatter(epoch_axis)
+            .x_grid_spacer(time_grid)
+            .label_formatter(time_label)
+            .allow_zoom([true, false])
+            .allow_drag([true, false])
+            .allow_scroll([true, false])
+            .allow_boxed_zoom(false)
+    }
+    
+    /// Show a time plot over the saved date range and record the range the user zooms or pans to
+    fn show_time_plot<'a, 'b>(&self, ui: &mut egui::Ui, plot: Plot<'a>, build: impl FnOnce(&mut egui_plot::PlotUi<'b>) + 'a) {
+        let range = self.settings.chart_range;
+        let plot = plot.show(ui, move |plot_ui| {
+            match range {
+                Some([from, to]) => plot_ui.set_plot_bounds_x(from as f64..=to as f64),
+                None => plot_ui.set_auto_bounds([true, true]),
+            }
+            build(plot_ui);
+        });
+        
+        let response = &plot.response;
+        let zoomed = response.hovered() && ui.input(|i| i.zoom_delta() != 1.0 || i.smooth_scroll_delta.x != 0.0);
+        if response.double_clicked() {
+            self.chart_range_change.set(Some(None));
+        } else if response.dragged() || zoomed {
+            let x = plot.transform.bounds().range_x();
+            self.chart_range_change.set(Some(Some([*x.start() as i64, *x.end() as i64])));
+        }
     }
     
     fn show_overview_charts(&self, ui: &mut egui::Ui) {
+        let (readings, analysis) = self.chart_data();
+        let (Some(first), Some(last)) = (readings.first(), readings.last()) else {
+            return;
+        };
+        let span = |value: f64| PlotPoints::from(vec![[first.epoch as f64, value], [last.epoch as f64, value]]);
         // Glucose trend chart
         ui.group(|ui| {
-            ui.label(egui::RichText::new("Glucose Trend (All Readings)").heading());
-            ui.label(format!("n = {} readings", self.data.readings.len()));
+            ui.label(egui::RichText::new(match &self.chart_segment {
+                Some(segment) => format!("Glucose Trend (Tagged {})", segment.tag),
+                None => "Glucose Trend (All Readings)".to_string(),
+            }).heading());
+            ui.label(format!("n = {} readings, lines break at gaps over {} h", readings.len(), READING_LINE_BREAK / timeaxis::HOUR));
             
-            let points: PlotPoints = self.data.readings.iter().enumerate()
-                .map(|(i, r)| [i as f64, r.mg_dl as f64])
+            let points: Vec<[f64; 2]> = readings.iter()
+                .map(|r| [r.epoch as f64, r.mg_dl as f64])
+                .collect();
+            let lines: Vec<Line> = gap_lines("Glucose", &points, READING_LINE_BREAK).into_iter()
+                .map(|line| line.color(egui::Color32::from_rgb(100, 150, 255)))
                 .collect();
             
-            let line = Line::new("Glucose", points)
-                .color(egui::Color32::from_rgb(100, 150, 255));
+            let low_line = Line::new(format!("Low ({})", self.settings.thresholds.low_display(self.settings.glucose_unit)), span(self.settings.thresholds.low_mgdl as f64))
+                .color(egui::Color32::from_rgb(255, 100, 100))
+                .style(egui_plot::LineStyle::dashed_dense());
             
-            let low_line = Line::new(format!("Low ({})", self.settings.thresholds.low_display(self.settings.glucose_unit)), PlotPoints::from_iter(
-                (0..self.data.readings.len()).map(|i| [i as f64, self.settings.thresholds.low_mgdl as f64])
-            ))
-            .color(egui::Color32::from_rgb(255, 100, 100))
-            .style(egui_plot::LineStyle::dashed_dense());
+            let high_line = Line::new(format!("High ({})", self.settings.thresholds.high_display(self.settings.glucose_unit)), span(self.settings.thresholds.high_mgdl as f64))
+                .color(egui::Color32::from_rgb(255, 180, 100))
+                .style(egui_plot::LineStyle::dashed_dense());
             
-            let high_line = Line::new(format!("High ({})", self.settings.thresholds.high_display(self.settings.glucose_unit)), PlotPoints::from_iter(
-                (0..self.data.readings.len()).map(|i| [i as f64, self.settings.thresholds.high_mgdl as f64])
-            ))
-            .color(egui::Color32::from_rgb(255, 180, 100))
-            .style(egui_plot::LineStyle::dashed_dense());
+            // Event markers sit on the glucose line at the time they happened
+            let event_markers: Vec<Points> = EventKind::ALL.into_iter()
+                .filter_map(|kind| {
+                    let positions: Vec<[f64; 2]> = self.data.events.iter()
+                        .filter(|e| e.kind == kind)
+                        .filter_map(|e| event_chart_position(readings, e.epoch))
+                        .collect();
+                    if positions.is_empty() {
+                        return None;
+                    }
+                    let (color, shape) = match kind {
+                        EventKind::Carbs => (egui::Color32::from_rgb(120, 200, 120), egui_plot::MarkerShape::Diamond),
+                        EventKind::Insulin => (egui::Color32::from_rgb(200, 120, 255), egui_plot::MarkerShape::Down),
+                        EventKind::Exercise => (egui::Color32::from_rgb(100, 220, 220), egui_plot::MarkerShape::Square),
+                        EventKind::Medication => (egui::Color32::from_rgb(255, 220, 100), egui_plot::MarkerShape::Cross),
+                    };
+                    Some(Points::new(kind.label(), positions).color(color).shape(shape).radius(5.0).filled(true))
+                })
+                .collect();
             
-            Plot::new("glucose_trend")
+            let plot = self.time_plot("glucose_trend")
                 .height(250.0)
                 .show_axes(true)
-                .legend(egui_plot::Legend::default())
-                .show(ui, |plot_ui| {
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                for line in lines {
                     plot_ui.line(line);
-                    plot_ui.line(low_line);
-                    plot_ui.line(high_line);
-                });
+                }
+                plot_ui.line(low_line);
+                plot_ui.line(high_line);
+                for markers in event_markers {
+                    plot_ui.points(markers);
+                }
+            });
         });
         
         ui.add_space(20.0);
         
         // Daily averages
-        if !self.data.daily_stats.is_empty() {
+        if !analysis.daily.is_empty() {
             ui.group(|ui| {
                 ui.label(egui::RichText::new("Daily Averages with Range").heading());
-                ui.label(format!("n = {} days", self.data.daily_stats.len()));
-                
-                let avg_points: PlotPoints = self.data.daily_stats.iter().enumerate()
-                    .map(|(i, d)| [i as f64, d.avg_mgdl])
-                    .collect();
+                ui.label(format!("n = {} days", analysis.daily.len()));
                 
-                let min_points: PlotPoints = self.data.daily_stats.iter().enumerate()
-                    .map(|(i, d)| [i as f64, d.min_mgdl as f64])
+                let daily_points = |value: &dyn Fn(&DailyStats) -> f64| -> Vec<[f64; 2]> {
+                    analysis.daily.iter()
+                        .filter_map(|d| Some([timeaxis::day_midpoint(&d.date)? as f64, value(d)]))
+                        .collect()
+                };
+                let series = [
+                    ("Average", daily_points(&|d| d.avg_mgdl), egui::Color32::from_rgb(100, 200, 100), egui_plot::LineStyle::Solid),
+                    ("Min", daily_points(&|d| d.min_mgdl as f64), egui::Color32::from_rgb(100, 100, 255), egui_plot::LineStyle::dashed_loose()),
+                    ("Max", daily_points(&|d| d.max_mgdl as f64), egui::Color32::from_rgb(255, 100, 100), egui_plot::LineStyle::dashed_loose()),
+                ];
+                let lines: Vec<Line> = series.into_iter()
+                    .flat_map(|(name, points, color, style)| {
+                        gap_lines(name, &points, timeaxis::DAY).into_iter().map(move |line| line.color(color).style(style))
+                    })
                     .collect();
                 
-                let max_points: PlotPoints = self.data.daily_stats.iter().enumerate()
-                    .map(|(i, d)| [i as f64, d.max_mgdl as f64])
-                    .collect();
-                
-                let avg_line = Line::new("Average", avg_points)
-                    .color(egui::Color32::from_rgb(100, 200, 100));
-                
-                let min_line = Line::new("Min", min_points)
-                    .color(egui::Color32::from_rgb(100, 100, 255))
-                    .style(egui_plot::LineStyle::dashed_loose());
-                
-                let max_line = Line::new("Max", max_points)
-                    .color(egui::Color32::from_rgb(255, 100, 100))
-                    .style(egui_plot::LineStyle::dashed_loose());
-                
-                Plot::new("daily_averages")
+                let plot = self.time_plot("daily_averages")
                     .height(200.0)
                     .show_axes(true)
-                    .legend(egui_plot::Legend::default())
-                    .show(ui, |plot_ui| {
-                        plot_ui.line(avg_line);
-                        plot_ui.line(min_line);
-                        plot_ui.line(max_line);
-                    });
+                    .legend(egui_plot::Legend::default());
+                self.show_time_plot(ui, plot, |plot_ui| {
+                    for line in lines {
+                        plot_ui.line(line);
+                    }
+                });
             });
         }
     }
     
     fn show_histogram_chart(&self, ui: &mut egui::Ui) {
+        let (readings, analysis) = self.chart_data();
         ui.group(|ui| {
             ui.label(egui::RichText::new("Glucose Distribution Histogram").heading());
-            ui.label(format!("n = {} readings, bin width = 20 mg/dL", self.data.readings.len()));
+            ui.label(format!("n = {} readings, bin width = 20 mg/dL", readings.len()));
             
-            if self.data.histogram_bins.is_empty() {
+            if analysis.histogram.is_empty() {
                 ui.label("No histogram data available.");
                 return;
             }
             
-            let bars: Vec<Bar> = self.data.histogram_bins.iter()
+            let bars: Vec<Bar> = analysis.histogram.iter()
                 .map(|bin| {
                     let mid = (bin.range_start + bin.range_end) as f64 / 2.0;
                     let color = if bin.range_end <= self.settings.thresholds.low_mgdl {
@@ -1089,7 +2155,7 @@ impl AccuChekApp {
                 });
             
             // Statistics
-            if let Some(ref stats) = self.data.basic_stats {
+            if let Some(ref stats) = analysis.basic {
                 ui.add_space(10.0);
                 let (ci_low, ci_high) = stats.confidence_interval_95(self.settings.glucose_unit);
                 ui.horizontal(|ui| {
@@ -1098,7 +2164,7 @@ impl AccuChekApp {
                         ci_low,
                         ci_high));
                     ui.separator();
-                    ui.label(format!("Median: {}", stats.median(self.settings.glucose_unit)));
+                    ui.label(format!("Median: {}", stats.format_median(self.settings.glucose_unit)));
                     ui.separator();
                     ui.label(format!("SD: {:.1}", stats.std_dev(self.settings.glucose_unit)));
                 });
@@ -1107,19 +2173,20 @@ impl AccuChekApp {
     }
     
     fn show_time_of_day_chart(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
         ui.group(|ui| {
             ui.label(egui::RichText::new("Glucose by Hour of Day (Scatter + Boxplot)").heading());
             
-            let total_readings: usize = self.data.hourly_stats.iter().map(|h| h.count()).sum();
+            let total_readings: usize = analysis.hourly.iter().map(|h| h.count()).sum();
             ui.label(format!("n = {} readings across 24 hours", total_readings));
             
-            if self.data.hourly_stats.is_empty() {
+            if analysis.hourly.is_empty() {
                 ui.label("No hourly data available.");
                 return;
             }
             
             let mut all_points: Vec<[f64; 2]> = Vec::new();
-            for stat in &self.data.hourly_stats {
+            for stat in &analysis.hourly {
                 for &val in &stat.mgdl_readings {
                     let jitter = (val as f64 % 7.0 - 3.5) * 0.1;
                     all_points.push([stat.hour as f64 + jitter, val as f64]);
@@ -1130,15 +2197,15 @@ impl AccuChekApp {
                 .radius(2.0)
                 .color(egui::Color32::from_rgba_unmultiplied(100, 150, 255, 100));
             
-            let boxes: Vec<BoxElem> = self.data.hourly_stats.iter()
+            let boxes: Vec<BoxElem> = analysis.hourly.iter()
                 .filter(|s| s.stats.is_some())
                 .map(|stat| {
                     let s = stat.stats.as_ref().unwrap();
                     BoxElem::new(stat.hour as f64, BoxSpread::new(
                         s.mgdl.min as f64,
-                        s.mgdl.q1 as f64,
-                        s.mgdl.median as f64,
-                        s.mgdl.q3 as f64,
+                        s.mgdl.q1,
+                        s.mgdl.median,
+                        s.mgdl.q3,
                         s.mgdl.max as f64,
                     ))
                     .whisker_width(0.3)
@@ -1150,6 +2217,17 @@ impl AccuChekApp {
             
             let boxplot = BoxPlot::new("Hourly Distribution", boxes);
             
+            // 5th and 95th percentile of each hour with readings
+            let bands: Vec<(f64, PercentileBand)> = analysis.hourly.iter()
+                .filter_map(|h| Some((h.hour as f64, h.band(GlucoseUnit::MgDl)?)))
+                .collect();
+            let p5_line = Line::new("5th percentile", PlotPoints::from_iter(bands.iter().map(|(h, b)| [*h, b.p5])))
+                .color(egui::Color32::from_rgb(120, 120, 200))
+                .style(egui_plot::LineStyle::dotted_dense());
+            let p95_line = Line::new("95th percentile", PlotPoints::from_iter(bands.iter().map(|(h, b)| [*h, b.p95])))
+                .color(egui::Color32::from_rgb(120, 120, 200))
+                .style(egui_plot::LineStyle::dotted_dense());
+            
             let low_line = Line::new(format!("Low ({})", self.settings.thresholds.low_display(self.settings.glucose_unit)), PlotPoints::from_iter(
                 (0..25).map(|h| [h as f64, self.settings.thresholds.low_mgdl as f64])
             ))
@@ -1170,6 +2248,8 @@ impl AccuChekApp {
                 .show(ui, |plot_ui| {
                     plot_ui.points(scatter);
                     plot_ui.box_plot(boxplot);
+                    plot_ui.line(p5_line);
+                    plot_ui.line(p95_line);
                     plot_ui.line(low_line);
                     plot_ui.line(high_line);
                 });
@@ -1177,66 +2257,73 @@ impl AccuChekApp {
     }
     
     fn show_daily_tir_trend(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
         ui.group(|ui| {
             ui.label(egui::RichText::new("Daily Time-in-Range Trend").heading());
             ui.label(format!("Target range: {} | n = {} days", 
                 self.settings.thresholds.format_range(self.settings.glucose_unit),
-                self.data.daily_stats.len()));
+                analysis.daily.len()));
             
-            if self.data.daily_stats.is_empty() {
+            if analysis.daily.is_empty() {
                 ui.label("No daily TIR data available.");
                 return;
             }
             
-            let tir_trend = Line::new("TIR %", PlotPoints::from_iter(
-                self.data.daily_stats.iter().enumerate().map(|(i, d)| [i as f64, d.tir.in_range_percent()])
-            ))
-            .color(egui::Color32::from_rgb(50, 200, 50))
-            .width(2.0);
+            let points: Vec<[f64; 2]> = analysis.daily.iter()
+                .filter_map(|d| Some([timeaxis::day_midpoint(&d.date)? as f64, d.tir.in_range_percent()]))
+                .collect();
+            let tir_trend: Vec<Line> = gap_lines("TIR %", &points, timeaxis::DAY).into_iter()
+                .map(|line| line.color(egui::Color32::from_rgb(50, 200, 50)).width(2.0))
+                .collect();
+            let goal_span = match (points.first(), points.last()) {
+                (Some(first), Some(last)) => vec![[first[0], 70.0], [last[0], 70.0]],
+                _ => Vec::new(),
+            };
             
-            Plot::new("daily_tir_trend")
+            let plot = self.time_plot("daily_tir_trend")
                 .height(250.0)
                 .x_axis_label("Day")
                 .y_axis_label("Percentage")
-                .legend(egui_plot::Legend::default())
-                .show(ui, |plot_ui| {
-                    plot_ui.line(tir_trend);
-                    
-                    let goal_line = Line::new("70% Goal", PlotPoints::from_iter(
-                        (0..self.data.daily_stats.len() + 1).map(|i| [i as f64, 70.0])
-                    ))
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                for line in tir_trend {
+                    plot_ui.line(line);
+                }
+                
+                let goal_line = Line::new("70% Goal", PlotPoints::from(goal_span))
                     .color(egui::Color32::from_rgb(150, 150, 150))
                     .style(egui_plot::LineStyle::dashed_loose());
-                    plot_ui.line(goal_line);
-                });
+                plot_ui.line(goal_line);
+            });
             
             ui.add_space(10.0);
             
-            let avg_tir: f64 = self.data.daily_stats.iter().map(|d| d.tir.in_range_percent()).sum::<f64>() 
-                / self.data.daily_stats.len() as f64;
-            let days_at_goal = self.data.daily_stats.iter().filter(|d| d.tir.in_range_percent() >= 70.0).count();
+            let avg_tir: f64 = analysis.daily.iter().map(|d| d.tir.in_range_percent()).sum::<f64>() 
+                / analysis.daily.len() as f64;
+            let days_at_goal = analysis.daily.iter().filter(|d| d.tir.in_range_percent() >= 70.0).count();
             
             ui.horizontal(|ui| {
                 ui.label(format!("Average TIR: {:.1}%", avg_tir));
                 ui.separator();
                 ui.label(format!("Days at ≥70% goal: {}/{} ({:.1}%)", 
-                    days_at_goal, self.data.daily_stats.len(), 
-                    (days_at_goal as f64 / self.data.daily_stats.len() as f64) * 100.0));
+                    days_at_goal, analysis.daily.len(), 
+                    (days_at_goal as f64 / analysis.daily.len() as f64) * 100.0));
             });
         });
     }
     
     fn show_time_bins_boxplot(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
         ui.group(|ui| {
             ui.label(egui::RichText::new("Glucose by Clinical Time Periods (Boxplots)").heading());
             ui.label("Shows glucose patterns across clinically meaningful time windows");
             
-            if self.data.time_bin_stats.is_empty() {
+            if analysis.time_bins.is_empty() {
                 ui.label("No time bin data available.");
                 return;
             }
             
-            let boxes: Vec<BoxElem> = self.data.time_bin_stats.iter()
+            let boxes: Vec<BoxElem> = analysis.time_bins.iter()
                 .enumerate()
                 .filter(|(_, s)| s.stats.is_some())
                 .map(|(i, stat)| {
@@ -1251,29 +2338,30 @@ impl AccuChekApp {
                     
                     BoxElem::new(i as f64, BoxSpread::new(
                         s.mgdl.min as f64,
-                        s.mgdl.q1 as f64,
-                        s.mgdl.median as f64,
-                        s.mgdl.q3 as f64,
+                        s.mgdl.q1,
+                        s.mgdl.median,
+                        s.mgdl.q3,
                         s.mgdl.max as f64,
                     ))
                     .whisker_width(0.4)
                     .box_width(0.7)
                     .fill(color)
                     .stroke(egui::Stroke::new(1.5, egui::Color32::from_rgb(80, 80, 80)))
-                    .name(&stat.name)
+                    .name(format!("{} ({})", stat.name, stat.description))
                 })
                 .collect();
             
             let boxplot = BoxPlot::new("Time Bin Analysis", boxes);
             
+            let bin_count = analysis.time_bins.len() as i32;
             let low_line = Line::new(format!("Low ({})", self.settings.thresholds.low_display(self.settings.glucose_unit)), PlotPoints::from_iter(
-                (-1..7).map(|x| [x as f64, self.settings.thresholds.low_mgdl as f64])
+                (-1..=bin_count).map(|x| [x as f64, self.settings.thresholds.low_mgdl as f64])
             ))
             .color(egui::Color32::from_rgb(255, 100, 100))
             .style(egui_plot::LineStyle::dashed_dense());
             
             let high_line = Line::new(format!("High ({})", self.settings.thresholds.high_display(self.settings.glucose_unit)), PlotPoints::from_iter(
-                (-1..7).map(|x| [x as f64, self.settings.thresholds.high_mgdl as f64])
+                (-1..=bin_count).map(|x| [x as f64, self.settings.thresholds.high_mgdl as f64])
             ))
             .color(egui::Color32::from_rgb(255, 180, 100))
             .style(egui_plot::LineStyle::dashed_dense());
@@ -1290,19 +2378,686 @@ impl AccuChekApp {
         });
     }
     
+    /// Ambulatory Glucose Profile: consensus metrics above a modal-day band chart
+    fn show_agp_chart(&mut self, ui: &mut egui::Ui) {
+        let unit = self.settings.glucose_unit;
+        
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Ambulatory Glucose Profile").heading());
+                ui.add_space(20.0);
+                ui.label("Bins:");
+                for minutes in AGP_BIN_MINUTES {
+                    if ui.selectable_value(&mut self.settings.agp_bin_minutes, minutes, format!("{} min", minutes)).clicked() {
+                        self.settings.save(self.profile.settings_path());
+                        let (readings, analysis) = self.chart_data_mut();
+                        analysis.agp = AgpReport::new(readings, &analysis.hourly, minutes);
+                    }
+                }
+            });
+            
+            let agp = &self.chart_data().1.agp;
+            let Some(metrics) = &agp.metrics else {
+                ui.label("No AGP data available.");
+                return;
+            };
+            
+            ui.columns(2, |columns| {
+                egui::Grid::new("agp_metrics_grid")
+                    .num_columns(2)
+                    .spacing([20.0, 4.0])
+                    .show(&mut columns[0], |ui| {
+                        ui.label("Period:");
+                        ui.strong(format!("{} to {} ({} days)", metrics.start_date, metrics.end_date, metrics.days));
+                        ui.end_row();
+                        
+                        ui.label("Readings:");
+                        ui.strong(format!("{} ({:.1}/day, {} days with data)",
+                            metrics.readings, metrics.readings_per_day(), metrics.days_with_readings));
+                        ui.end_row();
+                        
+                        ui.label("Mean glucose:");
+                        ui.strong(format!("{:.1} {}", metrics.mean(unit), unit.label()));
+                        ui.end_row();
+                        
+                        ui.label("GMI:");
+                        ui.strong(format!("{:.1}%", metrics.gmi_percent));
+                        ui.end_row();
+                        
+                        ui.label("Glucose variability (CV):");
+                        ui.strong(format!("{:.1}% (target ≤{:.0}%)", metrics.cv_percent, STABLE_CV_PERCENT));
+                        ui.end_row();
+                    });
+                
+                egui::Grid::new("agp_targets_grid")
+                    .num_columns(3)
+                    .spacing([20.0, 4.0])
+                    .show(&mut columns[1], |ui| {
+                        for target in metrics.range_targets() {
+                            ui.colored_label(range_color(target.range), target.label(unit));
+                            ui.strong(format!("{:.1}%", target.percent));
+                            let status = if target.met { "✔" } else { "✘" };
+                            ui.label(format!("{} goal {}", status, target.target));
+                            ui.end_row();
+                        }
+                    });
+            });
+            
+            ui.add_space(10.0);
+            
+            // Each segment joins two neighbouring bins; empty bins leave a gap
+            let hour = |bin: &AgpBin| (bin.start_minute as f64 + agp.bin_minutes as f64 / 2.0) / 60.0;
+            let bands: Vec<Option<(f64, PercentileBand)>> = agp.bins.iter()
+                .map(|bin| Some((hour(bin), bin.band(unit)?)))
+                .collect();
+            let segments: Vec<((f64, PercentileBand), (f64, PercentileBand))> = bands.windows(2)
+                .filter_map(|w| Some((w[0]?, w[1]?)))
+                .collect();
+            let band_polygon = |name: &str, low: fn(&PercentileBand) -> f64, high: fn(&PercentileBand) -> f64, alpha: u8| {
+                segments.iter().map(|((x0, b0), (x1, b1))| {
+                    Polygon::new(name, PlotPoints::from(vec![[*x0, low(b0)], [*x1, low(b1)], [*x1, high(b1)], [*x0, high(b0)]]))
+                        .fill_color(egui::Color32::from_rgba_unmultiplied(70, 120, 200, alpha))
+                        .stroke(egui::Stroke::NONE)
+                }).collect::<Vec<_>>()
+            };
+            let outer = band_polygon("5th-95th percentile", |b| b.p5, |b| b.p95, 50);
+            let inner = band_polygon("25th-75th percentile", |b| b.p25, |b| b.p75, 110);
+            let medians: Vec<Line> = bands.split(|b| b.is_none())
+                .filter(|run| !run.is_empty())
+                .map(|run| Line::new("Median", PlotPoints::from_iter(run.iter().flatten().map(|(x, b)| [*x, b.p50])))
+                    .color(egui::Color32::from_rgb(20, 60, 140))
+                    .width(2.5))
+                .collect();
+            
+            let (low, high) = match unit {
+                GlucoseUnit::MgDl => (self.settings.thresholds.low_mgdl as f64, self.settings.thresholds.high_mgdl as f64),
+                GlucoseUnit::MmolL => (self.settings.thresholds.low_mmol, self.settings.thresholds.high_mmol),
+            };
+            let low_line = Line::new(format!("Low ({})", self.settings.thresholds.low_display(unit)), PlotPoints::from(vec![[0.0, low], [24.0, low]]))
+                .color(egui::Color32::from_rgb(255, 100, 100))
+                .style(egui_plot::LineStyle::dashed_dense());
+            let high_line = Line::new(format!("High ({})", self.settings.thresholds.high_display(unit)), PlotPoints::from(vec![[0.0, high], [24.0, high]]))
+                .color(egui::Color32::from_rgb(255, 180, 100))
+                .style(egui_plot::LineStyle::dashed_dense());
+            
+            Plot::new("agp_chart")
+                .height(350.0)
+                .include_x(0.0)
+                .include_x(24.0)
+                .x_axis_label("Hour of Day")
+                .y_axis_label(format!("Glucose ({})", unit.label()))
+                .x_axis_formatter(|mark, _| format_minute((mark.value * 60.0).round().clamp(0.0, 1440.0) as u16))
+                .legend(egui_plot::Legend::default())
+                .show(ui, |plot_ui| {
+                    for polygon in outer {
+                        plot_ui.polygon(polygon);
+                    }
+                    for polygon in inner {
+                        plot_ui.polygon(polygon);
+                    }
+                    for line in medians {
+                        plot_ui.line(line);
+                    }
+                    plot_ui.line(low_line);
+                    plot_ui.line(high_line);
+                });
+            
+            ui.label(egui::RichText::new(format!(
+                "Readings from every day pooled into {}-minute bins; ranges follow the international consensus (54/70/180/250 mg/dL)",
+                agp.bin_minutes
+            )).small());
+        });
+    }
+    
+    /// Hypo and hyper episodes: counts per week and a list of every episode
+    fn show_episodes(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
+        let unit = self.settings.glucose_unit;
+        let report = &analysis.episodes;
+        let low_color = egui::Color32::from_rgb(255, 100, 100);
+        let high_color = egui::Color32::from_rgb(255, 180, 100);
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Hypo- and Hyperglycemia Episodes").heading());
+            ui.label(format!("Consecutive readings outside {} form one episode", self.settings.thresholds.format_range(unit)));
+            
+            egui::Grid::new("episode_summary_grid")
+                .num_columns(2)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    ui.colored_label(low_color, "Hypo episodes:");
+                    ui.label(format!("{} ({:.1}/week), {} severe",
+                        report.count(EpisodeKind::Hypo), report.count_per_week(EpisodeKind::Hypo), report.severe_count(EpisodeKind::Hypo)));
+                    ui.end_row();
+                    
+                    ui.colored_label(low_color, "Nocturnal hypos (00:00-06:00):");
+                    ui.label(format!("{} ({:.1}/week)", report.nocturnal_hypo_count(), report.nocturnal_hypo_per_week()));
+                    ui.end_row();
+                    
+                    ui.colored_label(high_color, "Hyper episodes:");
+                    ui.label(format!("{} ({:.1}/week), {} severe",
+                        report.count(EpisodeKind::Hyper), report.count_per_week(EpisodeKind::Hyper), report.severe_count(EpisodeKind::Hyper)));
+                    ui.end_row();
+                });
+            
+            if report.weekly.is_empty() {
+                return;
+            }
+            
+            ui.add_space(10.0);
+            // Side by side around the middle of each week
+            let week = timeaxis::WEEK as f64;
+            let week_bars = |offset: f64, count: &dyn Fn(&WeeklyEpisodes) -> usize, color: egui::Color32| -> Vec<Bar> {
+                report.weekly.iter()
+                    .filter_map(|w| {
+                        let middle = (timeaxis::day_midpoint(&w.week_start)? + 3 * timeaxis::DAY) as f64;
+                        Some(Bar::new(middle + offset * week, count(w) as f64).width(0.4 * week).fill(color).name(&w.week_start))
+                    })
+                    .collect()
+            };
+            let hypo_bars = week_bars(-0.2, &|w| w.hypo, low_color);
+            let hyper_bars = week_bars(0.2, &|w| w.hyper, high_color);
+            
+            let plot = self.time_plot("weekly_episodes")
+                .height(200.0)
+                .x_axis_label("Week")
+                .y_axis_label("Episodes")
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new("Hypo", hypo_bars).color(low_color));
+                plot_ui.bar_chart(BarChart::new("Hyper", hyper_bars).color(high_color));
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("All Episodes");
+            if report.episodes.is_empty() {
+                ui.label("No readings outside the target range.");
+                return;
+            }
+            
+            egui::Grid::new("episodes_grid")
+                .num_columns(6)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Type", "Start", "End", "Readings", "Nadir/Peak", "At"] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    
+                    for episode in report.episodes.iter().rev() {
+                        let color = match episode.kind {
+                            EpisodeKind::Hypo => low_color,
+                            EpisodeKind::Hyper => high_color,
+                        };
+                        let label = match (episode.severe, episode.is_nocturnal()) {
+                            (true, true) => format!("{} (severe, nocturnal)", episode.kind.label()),
+                            (true, false) => format!("{} (severe)", episode.kind.label()),
+                            (false, true) => format!("{} (nocturnal)", episode.kind.label()),
+                            (false, false) => episode.kind.label().to_string(),
+                        };
+                        ui.colored_label(color, label);
+                        ui.label(&episode.start);
+                        ui.label(format!("{} ({} min)", episode.end, episode.duration_minutes()));
+                        ui.label(episode.readings.to_string());
+                        ui.colored_label(color, unit.format(episode.extreme_mgdl, episode.extreme_mmol));
+                        ui.label(&episode.extreme_time);
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+    
+    fn show_meal_pairs(&mut self, ui: &mut egui::Ui) {
+        let unit = self.settings.glucose_unit;
+        
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new("Before/After Meal Pairs").heading());
+                ui.add_space(20.0);
+                ui.label("Window:");
+                for minutes in MEAL_PAIR_WINDOWS {
+                    if ui.selectable_value(&mut self.settings.meal_pair_window_minutes, minutes, format!("{} min", minutes)).clicked() {
+                        self.settings.save(self.profile.settings_path());
+                        let time_bins = self.settings.time_bins.clone();
+                        let (readings, analysis) = self.chart_data_mut();
+                        analysis.meal_pairs = MealPairReport::from_readings(readings, &time_bins, minutes);
+                    }
+                }
+            });
+            ui.label(format!(
+                "Each {} reading is paired with the next {} reading up to {} minutes later",
+                BEFORE_MEAL_TAG, AFTER_MEAL_TAG, self.settings.meal_pair_window_minutes
+            ));
+            
+            let report = &self.chart_data().1.meal_pairs;
+            let Some(overall) = &report.overall else {
+                ui.add_space(10.0);
+                ui.label("No pairs yet. Tag readings with the quick-tag buttons in the Readings tab.");
+                return;
+            };
+            
+            egui::Grid::new("meal_pair_summary_grid")
+                .num_columns(2)
+                .spacing([20.0, 4.0])
+                .show(ui, |ui| {
+                    ui.label("Pairs:");
+                    ui.strong(format!("{} ({} before_meal and {} after_meal readings unpaired)",
+                        overall.pairs, report.unpaired_before, report.unpaired_after));
+                    ui.end_row();
+                    
+                    ui.label("Mean before → after:");
+                    ui.strong(format!("{:.1} → {:.1} {}", overall.mean_before(unit), overall.mean_after(unit), unit.label()));
+                    ui.end_row();
+                    
+                    ui.label("Mean excursion:");
+                    ui.strong(format!("{:+.1} {} (largest {:+.1})", overall.mean_excursion(unit), unit.label(), overall.max_excursion(unit)));
+                    ui.end_row();
+                    
+                    ui.label(format!("Rises over {} mg/dL:", LARGE_EXCURSION_MGDL));
+                    ui.strong(format!("{} of {}", overall.large, overall.pairs));
+                    ui.end_row();
+                });
+            
+            ui.add_space(10.0);
+            ui.columns(2, |columns| {
+                for (column, (title, groups)) in columns.iter_mut().zip([
+                    ("By time bin", &report.by_time_bin),
+                    ("By weekday", &report.by_weekday),
+                ]) {
+                    column.label(egui::RichText::new(title).strong());
+                    Self::show_excursion_bars(column, title, groups, unit);
+                }
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.heading("All Pairs");
+            let report = &self.chart_data().1.meal_pairs;
+            egui::Grid::new("meal_pairs_grid")
+                .num_columns(6)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Before meal", "", "After meal", "", "Excursion", "Time bin"] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    
+                    for pair in report.pairs.iter().rev() {
+                        ui.label(&pair.before_time);
+                        ui.colored_label(self.get_reading_color(pair.before_mgdl), unit.format(pair.before_mgdl, pair.before_mmol));
+                        ui.label(format!("{} ({} min)", pair.after_time, pair.minutes_apart()));
+                        ui.colored_label(self.get_reading_color(pair.after_mgdl), unit.format(pair.after_mgdl, pair.after_mmol));
+                        let excursion = format!("{:+.1}", pair.excursion(unit));
+                        if pair.is_large() {
+                            ui.colored_label(egui::Color32::from_rgb(255, 180, 100), excursion);
+                        } else {
+                            ui.label(excursion);
+                        }
+                        ui.label(pair.time_bin.as_deref().unwrap_or("-"));
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+    
+    /// Mean excursion per group as bars, orange where it exceeds the large-rise limit
+    fn show_excursion_bars(ui: &mut egui::Ui, id: &str, groups: &[ExcursionSummary], unit: GlucoseUnit) {
+        let bars: Vec<Bar> = groups.iter().enumerate()
+            .map(|(i, group)| {
+                let color = if group.mean_excursion_mgdl > LARGE_EXCURSION_MGDL as f64 {
+                    egui::Color32::from_rgb(255, 180, 100)
+                } else {
+                    egui::Color32::from_rgb(100, 200, 100)
+                };
+                Bar::new(i as f64, group.mean_excursion(unit))
+                    .width(0.6)
+                    .fill(color)
+                    .name(format!("{} ({} pairs)", group.label, group.pairs))
+            })
+            .collect();
+        let labels: Vec<String> = groups.iter().map(|g| g.label.clone()).collect();
+        
+        Plot::new(id)
+            .height(200.0)
+            .y_axis_label(format!("Mean excursion ({})", unit.label()))
+            .x_axis_formatter(index_labels(labels))
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new("Mean excursion", bars));
+            });
+    }
+    
+    /// Weekly trend of mean and TIR, and the days of the week side by side
+    /// Readings, rolling means and the regression line against time, then rolling TIR and rate of change
+    fn show_trend(&self, ui: &mut egui::Ui) {
+        let (readings, analysis) = self.chart_data();
+        let unit = self.settings.glucose_unit;
+        let trend = &analysis.trend;
+        let (Some(first), Some(last)) = (readings.first(), readings.last()) else {
+            return;
+        };
+        let (low, high) = match unit {
+            GlucoseUnit::MgDl => (self.settings.thresholds.low_mgdl as f64, self.settings.thresholds.high_mgdl as f64),
+            GlucoseUnit::MmolL => (self.settings.thresholds.low_mmol, self.settings.thresholds.high_mmol),
+        };
+        let span = |value: f64| PlotPoints::from(vec![[first.epoch as f64, value], [last.epoch as f64, value]]);
+        let rolling_colors = [
+            egui::Color32::from_rgb(255, 140, 60),
+            egui::Color32::from_rgb(180, 100, 220),
+            egui::Color32::from_rgb(40, 160, 160),
+        ];
+        // Rolling points sit at noon of their day
+        let rolling_lines = |value: &dyn Fn(&RollingPoint) -> f64| -> Vec<Line<'static>> {
+            trend.rolling.iter().zip(rolling_colors)
+                .flat_map(|(series, color)| {
+                    let points: Vec<[f64; 2]> = series.points.iter()
+                        .filter_map(|p| Some([timeaxis::day_midpoint(&p.date)? as f64, value(p)]))
+                        .collect();
+                    gap_lines(&format!("{}-day", series.window_days), &points, timeaxis::DAY).into_iter()
+                        .map(move |line| line.color(color).width(2.0))
+                })
+                .collect()
+        };
+        let format_rate = |rate: f64| match unit {
+            GlucoseUnit::MgDl => format!("{:+.1} mg/dL/min", rate),
+            GlucoseUnit::MmolL => format!("{:+.2} mmol/L/min", rate),
+        };
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Glucose Trend over Time").heading());
+            match &trend.regression {
+                Some(line) => {
+                    let per_week = line.slope_per_week(unit);
+                    ui.label(format!("Trend: {} {} per week (R² {:.2}) | n = {} readings",
+                        if unit == GlucoseUnit::MgDl { format!("{:+.1}", per_week) } else { format!("{:+.2}", per_week) },
+                        unit.label(), line.r_squared, readings.len()));
+                }
+                None => {
+                    ui.label(format!("n = {} readings", readings.len()));
+                }
+            }
+            
+            let reading_points: Vec<[f64; 2]> = readings.iter()
+                .map(|r| [r.epoch as f64, unit.display_value(r.mg_dl, r.mmol_l)])
+                .collect();
+            let plot = self.time_plot("trend_over_time")
+                .height(280.0)
+                .y_axis_label(unit.label())
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                plot_ui.points(Points::new("Readings", PlotPoints::from(reading_points)).radius(1.5).color(egui::Color32::from_rgb(100, 150, 255)));
+                for line in rolling_lines(&|p| p.mean(unit)) {
+                    plot_ui.line(line);
+                }
+                if let Some(line) = &trend.regression {
+                    let ends = [line.start_epoch, line.end_epoch].map(|epoch| [epoch as f64, line.value_at(epoch, unit)]);
+                    plot_ui.line(Line::new("Regression", PlotPoints::from(ends.to_vec()))
+                        .color(egui::Color32::from_rgb(120, 120, 120))
+                        .style(egui_plot::LineStyle::dashed_loose())
+                        .width(2.0));
+                }
+                plot_ui.line(Line::new(format!("Low ({})", self.settings.thresholds.low_display(unit)), span(low))
+                    .color(egui::Color32::from_rgb(255, 100, 100))
+                    .style(egui_plot::LineStyle::dashed_dense()));
+                plot_ui.line(Line::new(format!("High ({})", self.settings.thresholds.high_display(unit)), span(high))
+                    .color(egui::Color32::from_rgb(255, 180, 100))
+                    .style(egui_plot::LineStyle::dashed_dense()));
+            });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Rolling Time in Range").heading());
+            ui.label(format!("Target range: {}", self.settings.thresholds.format_range(unit)));
+            let plot = self.time_plot("rolling_tir")
+                .height(180.0)
+                .y_axis_label("TIR %")
+                .include_y(0.0)
+                .include_y(100.0)
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                for line in rolling_lines(&|p| p.tir_percent) {
+                    plot_ui.line(line);
+                }
+                plot_ui.line(Line::new("70% Goal", span(70.0))
+                    .color(egui::Color32::from_rgb(150, 150, 150))
+                    .style(egui_plot::LineStyle::dashed_loose()));
+            });
+            
+            egui::Grid::new("rolling_grid")
+                .num_columns(4)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new("Window").strong());
+                    ui.label(egui::RichText::new("Readings").strong());
+                    ui.label(egui::RichText::new(format!("Mean ({})", unit.label())).strong());
+                    ui.label(egui::RichText::new("TIR").strong());
+                    ui.end_row();
+                    for series in &trend.rolling {
+                        let Some(latest) = series.points.last() else { continue };
+                        ui.label(format!("Last {} days", series.window_days));
+                        ui.label(latest.count.to_string());
+                        ui.label(if unit == GlucoseUnit::MgDl { format!("{:.0}", latest.mean_mgdl) } else { format!("{:.1}", latest.mean_mmol) });
+                        ui.label(format!("{:.0}%", latest.tir_percent));
+                        ui.end_row();
+                    }
+                });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Rate of Change").heading());
+            ui.label(format!("{} pairs of consecutive readings at most {} min apart, {} rapid (≥{:.0} mg/dL/min)",
+                trend.rates.len(), MAX_RATE_GAP_MINUTES, trend.rapid_count(), RAPID_RATE_MGDL_PER_MIN));
+            if trend.rates.is_empty() {
+                ui.label("Test again within two hours to see how fast glucose is moving.");
+                return;
+            }
+            
+            ui.horizontal(|ui| {
+                if let Some(rise) = trend.fastest_rise() {
+                    ui.label(format!("Fastest rise: {} ({} to {})", format_rate(rise.rate(unit)), rise.from, rise.to));
+                }
+                if let Some(fall) = trend.fastest_fall() {
+                    ui.separator();
+                    ui.label(format!("Fastest fall: {} ({} to {})", format_rate(fall.rate(unit)), fall.from, fall.to));
+                }
+            });
+            
+            let (rapid, steady): (Vec<&RateOfChange>, Vec<&RateOfChange>) = trend.rates.iter().partition(|r| r.is_rapid());
+            let rate_points = |rates: Vec<&RateOfChange>| -> Vec<[f64; 2]> {
+                rates.into_iter().filter_map(|r| Some([timestamp_to_epoch(&r.to)? as f64, r.rate(unit)])).collect()
+            };
+            let rapid_rate = match unit {
+                GlucoseUnit::MgDl => RAPID_RATE_MGDL_PER_MIN,
+                GlucoseUnit::MmolL => RAPID_RATE_MGDL_PER_MIN / 18.0,
+            };
+            let plot = self.time_plot("rate_of_change")
+                .height(160.0)
+                .y_axis_label(format!("{}/min", unit.label()))
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                plot_ui.points(Points::new("Rate", PlotPoints::from(rate_points(steady))).radius(2.5).color(egui::Color32::from_rgb(100, 150, 255)));
+                plot_ui.points(Points::new("Rapid", PlotPoints::from(rate_points(rapid))).radius(3.5).color(egui::Color32::from_rgb(230, 140, 40)));
+                for value in [rapid_rate, -rapid_rate] {
+                    plot_ui.line(Line::new("Rapid", span(value))
+                        .color(egui::Color32::from_rgb(230, 140, 40))
+                        .style(egui_plot::LineStyle::dashed_dense()));
+                }
+            });
+        });
+    }
+    
+    fn show_weekly(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
+        let unit = self.settings.glucose_unit;
+        let (low, high) = match unit {
+            GlucoseUnit::MgDl => (self.settings.thresholds.low_mgdl as f64, self.settings.thresholds.high_mgdl as f64),
+            GlucoseUnit::MmolL => (self.settings.thresholds.low_mmol, self.settings.thresholds.high_mmol),
+        };
+        // Weeks are plotted at their middle, Thursday noon
+        let week_x = |week: &WeeklyStats| timeaxis::day_midpoint(&week.week_start).map(|monday| (monday + 3 * timeaxis::DAY) as f64);
+        let (first_x, last_x) = match (analysis.weekly.first().and_then(week_x), analysis.weekly.last().and_then(week_x)) {
+            (Some(first), Some(last)) => (first - 0.5 * timeaxis::WEEK as f64, last + 0.5 * timeaxis::WEEK as f64),
+            _ => (0.0, 0.0),
+        };
+        let span = |value: f64| PlotPoints::from(vec![[first_x, value], [last_x, value]]);
+        let threshold_lines = |from: f64, to: f64| {
+            let span = |value: f64| PlotPoints::from(vec![[from, value], [to, value]]);
+            [
+                Line::new(format!("Low ({})", self.settings.thresholds.low_display(unit)), span(low))
+                    .color(egui::Color32::from_rgb(255, 100, 100))
+                    .style(egui_plot::LineStyle::dashed_dense()),
+                Line::new(format!("High ({})", self.settings.thresholds.high_display(unit)), span(high))
+                    .color(egui::Color32::from_rgb(255, 180, 100))
+                    .style(egui_plot::LineStyle::dashed_dense()),
+            ]
+        };
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Weekly Trend").heading());
+            ui.label(format!("n = {} ISO weeks", analysis.weekly.len()));
+            if analysis.weekly.is_empty() {
+                ui.label("No weekly data available.");
+                return;
+            }
+            
+            let means: Vec<[f64; 2]> = analysis.weekly.iter()
+                .filter_map(|w| Some([week_x(w)?, w.stats.mean(unit)]))
+                .collect();
+            let mean_lines: Vec<Line> = gap_lines("Mean", &means, timeaxis::WEEK).into_iter()
+                .map(|line| line.color(egui::Color32::from_rgb(100, 150, 255)).width(2.0))
+                .collect();
+            let plot = self.time_plot("weekly_mean")
+                .height(200.0)
+                .y_axis_label(format!("Mean ({})", unit.label()))
+                .legend(egui_plot::Legend::default());
+            self.show_time_plot(ui, plot, |plot_ui| {
+                for line in mean_lines {
+                    plot_ui.line(line);
+                }
+                plot_ui.points(Points::new("Mean", PlotPoints::from(means)).radius(3.0).color(egui::Color32::from_rgb(100, 150, 255)));
+                for line in threshold_lines(first_x, last_x) {
+                    plot_ui.line(line);
+                }
+            });
+            
+            let tir_bars: Vec<Bar> = analysis.weekly.iter()
+                .filter_map(|w| Some(Bar::new(week_x(w)?, w.tir.in_range_percent()).width(0.6 * timeaxis::WEEK as f64).name(w.label())))
+                .collect();
+            let plot = self.time_plot("weekly_tir")
+                .height(150.0)
+                .y_axis_label("TIR %")
+                .include_y(100.0);
+            self.show_time_plot(ui, plot, |plot_ui| {
+                plot_ui.bar_chart(BarChart::new("Time in range", tir_bars).color(egui::Color32::from_rgb(50, 200, 50)));
+                plot_ui.line(Line::new("70% Goal", span(70.0))
+                    .color(egui::Color32::from_rgb(150, 150, 150))
+                    .style(egui_plot::LineStyle::dashed_loose()));
+            });
+            
+            egui::Grid::new("weekly_grid")
+                .num_columns(6)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Week", "Days", "Readings", "Mean", "TIR", "CV"] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    for week in analysis.weekly.iter().rev() {
+                        ui.label(format!("{} ({})", week.label(), week.week_start));
+                        self.show_aggregate_row(ui, week.days_with_readings, &week.stats, &week.tir, week.variability.as_ref());
+                    }
+                });
+        });
+        
+        ui.add_space(10.0);
+        
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Weekday Comparison").heading());
+            if analysis.weekdays.is_empty() {
+                ui.label("No weekday data available.");
+                return;
+            }
+            
+            let boxes: Vec<BoxElem> = analysis.weekdays.iter().enumerate()
+                .map(|(i, day)| {
+                    let s = &day.stats;
+                    let band = s.band(unit);
+                    BoxElem::new(i as f64, BoxSpread::new(s.min(unit), band.p25, band.p50, band.p75, s.max(unit)))
+                        .whisker_width(0.4)
+                        .box_width(0.6)
+                        .fill(range_color(self.settings.thresholds.classify(s.mgdl.mean as u16)).gamma_multiply(0.7))
+                        .stroke(egui::Stroke::new(1.5, egui::Color32::from_rgb(80, 80, 80)))
+                        .name(day.label())
+                })
+                .collect();
+            Plot::new("weekday_boxplot")
+                .height(250.0)
+                .y_axis_label(format!("Glucose ({})", unit.label()))
+                .x_axis_formatter(index_labels(analysis.weekdays.iter().map(|d| d.label().to_string()).collect()))
+                .legend(egui_plot::Legend::default())
+                .show(ui, |plot_ui| {
+                    plot_ui.box_plot(BoxPlot::new("Weekdays", boxes));
+                    for line in threshold_lines(-0.5, analysis.weekdays.len() as f64 - 0.5) {
+                        plot_ui.line(line);
+                    }
+                });
+            
+            egui::Grid::new("weekday_grid")
+                .num_columns(6)
+                .spacing([20.0, 4.0])
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Day", "Days", "Readings", "Mean", "TIR", "CV"] {
+                        ui.label(egui::RichText::new(header).strong());
+                    }
+                    ui.end_row();
+                    for day in &analysis.weekdays {
+                        ui.label(day.label());
+                        self.show_aggregate_row(ui, day.days_with_readings, &day.stats, &day.tir, day.variability.as_ref());
+                    }
+                });
+        });
+    }
+    
+    /// Days, readings, mean, TIR and CV cells of a weekly or weekday grid row
+    fn show_aggregate_row(&self, ui: &mut egui::Ui, days: usize, stats: &BasicStats, tir: &TimeInRange, variability: Option<&VariabilityStats>) {
+        let unit = self.settings.glucose_unit;
+        ui.label(days.to_string());
+        ui.label(stats.count().to_string());
+        ui.colored_label(self.get_reading_color(stats.mgdl.mean as u16), stats.format_mean(unit));
+        ui.label(format!("{:.0}%", tir.in_range_percent()));
+        ui.label(variability.map_or("-".to_string(), |v| format!("{:.1}%", v.cv_percent)));
+        ui.end_row();
+    }
+    
     fn show_calendar_view(&self, ui: &mut egui::Ui) {
+        let analysis = self.chart_data().1;
         ui.group(|ui| {
             ui.label(egui::RichText::new("Calendar View (Daily Small Multiples)").heading());
-            ui.label(format!("Showing {} days with readings", self.data.calendar_data.len()));
+            ui.label(format!("Showing {} days with readings", analysis.calendar.len()));
             
-            if self.data.calendar_data.is_empty() {
+            if analysis.calendar.is_empty() {
                 ui.label("No calendar data available.");
                 return;
             }
             
             use std::collections::BTreeMap;
             let mut weeks: BTreeMap<u32, Vec<&CalendarDay>> = BTreeMap::new();
-            for day in &self.data.calendar_data {
+            for day in &analysis.calendar {
                 weeks.entry(day.week_of_year).or_default().push(day);
             }
             
@@ -1443,6 +3198,105 @@ impl AccuChekApp {
 
 // ============= Helper Functions =============
 
+/// Position of an event on the time-based trend chart: its epoch as x and the
+/// glucose interpolated between the readings around it as y. Events outside
+/// the range of readings are not shown.
+fn event_chart_position(readings: &[StoredReading], epoch: i64) -> Option<[f64; 2]> {
+    let after = readings.partition_point(|r| r.epoch < epoch);
+    if after == readings.len() {
+        return None;
+    }
+    let next = &readings[after];
+    if next.epoch == epoch {
+        return Some([epoch as f64, next.mg_dl as f64]);
+    }
+    let prev = readings.get(after.checked_sub(1)?)?;
+    
+    let t = (epoch - prev.epoch) as f64 / (next.epoch - prev.epoch) as f64;
+    let value = prev.mg_dl as f64 + (next.mg_dl as f64 - prev.mg_dl as f64) * t;
+    Some([epoch as f64, value])
+}
+
+/// Grid lines at whole hours, days, weeks or months of epoch seconds
+fn time_grid(input: egui_plot::GridInput) -> Vec<egui_plot::GridMark> {
+    timeaxis::grid_ticks(input.bounds.0.floor() as i64, input.bounds.1.ceil() as i64, input.base_step_size)
+        .into_iter()
+        .map(|(tick, step)| egui_plot::GridMark { value: tick as f64, step_size: step.approx_secs() })
+        .collect()
+}
+
+/// X axis formatter for [`time_grid`] marks: times of day, dates, months or years
+fn epoch_axis(mark: egui_plot::GridMark, _range: &std::ops::RangeInclusive<f64>) -> String {
+    timeaxis::TickStep::nearest(mark.step_size).format(mark.value.round() as i64)
+}
+
+/// Hover label of a time plot: series name, date and time, and value
+fn time_label(name: &str, point: &egui_plot::PlotPoint) -> String {
+    let time = timeaxis::format_epoch(point.x.round() as i64);
+    if name.is_empty() {
+        format!("{}\n{:.1}", time, point.y)
+    } else {
+        format!("{}\n{}\n{:.1}", name, time, point.y)
+    }
+}
+
+/// One line per run of points without a gap longer than `max_gap` seconds, sharing a legend entry
+fn gap_lines(name: &str, points: &[[f64; 2]], max_gap: i64) -> Vec<Line<'static>> {
+    let times: Vec<i64> = points.iter().map(|p| p[0] as i64).collect();
+    timeaxis::segments(&times, max_gap).into_iter()
+        .map(|segment| Line::new(name, PlotPoints::from(points[segment].to_vec())))
+        .collect()
+}
+
+/// X axis formatter naming whole-number positions after `labels`, e.g. bars or boxes
+fn index_labels(labels: Vec<String>) -> impl Fn(egui_plot::GridMark, &std::ops::RangeInclusive<f64>) -> String {
+    move |mark, _| {
+        let index = mark.value.round();
+        if (mark.value - index).abs() < 0.01 && index >= 0.0 {
+            labels.get(index as usize).cloned().unwrap_or_default()
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Combo box choosing all readings or those with one tag; true when the choice changed
+fn tag_selector(ui: &mut egui::Ui, id: &str, tags: &[String], selected: &mut Option<String>) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected.as_deref().map_or("All readings".to_string(), |tag| format!("Tagged {}", tag)))
+        .show_ui(ui, |ui| {
+            changed |= ui.selectable_value(selected, None, "All readings").changed();
+            for tag in tags {
+                changed |= ui.selectable_value(selected, Some(tag.clone()), format!("Tagged {}", tag)).changed();
+            }
+        });
+    changed
+}
+
+/// Color used for readings in a glucose range
+fn range_color(range: GlucoseRange) -> egui::Color32 {
+    match range {
+        GlucoseRange::VeryLow => egui::Color32::from_rgb(200, 50, 50),
+        GlucoseRange::Low => egui::Color32::from_rgb(255, 100, 100),
+        GlucoseRange::InRange => egui::Color32::from_rgb(100, 255, 100),
+        GlucoseRange::High => egui::Color32::from_rgb(255, 180, 100),
+        GlucoseRange::VeryHigh => egui::Color32::from_rgb(255, 100, 50),
+    }
+}
+
+/// Drag value editing minutes since midnight, shown as "HH:MM"
+fn minute_drag_value(minute: &mut u16) -> egui::DragValue<'_> {
+    egui::DragValue::new(minute)
+        .range(0..=24 * 60)
+        .speed(5.0)
+        .custom_formatter(|n, _| format_minute(n as u16))
+        .custom_parser(|text| {
+            let (hours, minutes) = text.trim().split_once(':')?;
+            Some((hours.parse::<u16>().ok()? * 60 + minutes.parse::<u16>().ok()?) as f64)
+        })
+}
+
 fn open_folder(path: &std::path::Path) {
     #[cfg(target_os = "windows")]
     {
@@ -1476,7 +3330,7 @@ fn open_file(path: &std::path::Path) {
 }
 
 /// Run the GUI application
-pub fn run_gui(db_path: String) -> eframe::Result<()> {
+pub fn run_gui(profiles: Profiles, profile: Profile) -> eframe::Result<()> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1024.0, 768.0])
@@ -1493,7 +3347,7 @@ pub fn run_gui(db_path: String) -> eframe::Result<()> {
         options,
         Box::new(|cc| {
             cc.egui_ctx.set_visuals(egui::Visuals::default());
-            Ok(Box::new(AccuChekApp::new(cc, db_path)))
+            Ok(Box::new(AccuChekApp::new(cc, profiles, profile)))
         }),
     )
 }
@@ -0,0 +1,363 @@
+//! Recurring patterns in the readings, phrased as plain-language findings
+//!
+//! Each finding carries the counts it is based on, so a reader can judge how
+//! much weight to give it. Detection works from a finished `Analysis` and the
+//! readings it was run on, so statistics never depend on this module.
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::stats::{format_minute, in_time_window, minute_of_day, parse_timestamp, split_tags, Analysis, HourlyStats, ReadingData, TimeBinStats};
+use crate::units::{GlucoseRange, GlucoseUnit, Thresholds};
+
+/// Days looked back over for recurring lows and highs
+const RECENT_DAYS: i64 = 7;
+/// Days out of `RECENT_DAYS` a window must be low or high on to be recurring
+const MIN_RECURRING_DAYS: usize = 3;
+/// Width of the windows recurring lows and highs are looked for in
+const WINDOW_HOURS: u8 = 2;
+/// Fewest readings (or days) a share is computed over
+const MIN_SAMPLE: usize = 5;
+/// Share of days or readings above range that is worth pointing out
+const HIGH_SHARE: f64 = 0.5;
+/// Share below range worth pointing out; lower because lows are more dangerous
+const LOW_SHARE: f64 = 0.25;
+/// Difference in mean between weekends and weekdays worth pointing out (mg/dL)
+const WEEKEND_DIFFERENCE_MGDL: f64 = 15.0;
+
+/// What a finding is about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pattern {
+    /// Low in the same window of the day on several recent days
+    RecurringLow { start_hour: u8, end_hour: u8 },
+    RecurringHigh { start_hour: u8, end_hour: u8 },
+    /// An hour of the day whose median is outside the target range
+    HourLow { hour: u8, median_mgdl: f64, median_mmol: f64 },
+    HourHigh { hour: u8, median_mgdl: f64, median_mmol: f64 },
+    /// A time bin often low or high on the days it has readings
+    TimeBinLow { bin: String, description: String },
+    TimeBinHigh { bin: String, description: String },
+    /// A tag whose readings are often low or high
+    TagLow { tag: String },
+    TagHigh { tag: String },
+    /// Weekend mean differs from the weekday mean
+    WeekendDifference { weekend_mgdl: f64, weekday_mgdl: f64, weekend_mmol: f64, weekday_mmol: f64 },
+}
+
+/// A pattern with the counts it rests on: `matching` of `total` days or readings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Insight {
+    pub pattern: Pattern,
+    pub matching: usize,
+    pub total: usize,
+}
+
+impl Insight {
+    /// The side of the target range the finding is about; `None` if neither
+    pub fn range(&self) -> Option<GlucoseRange> {
+        match self.pattern {
+            Pattern::RecurringLow { .. } | Pattern::HourLow { .. } | Pattern::TimeBinLow { .. } | Pattern::TagLow { .. } => {
+                Some(GlucoseRange::Low)
+            }
+            Pattern::RecurringHigh { .. } | Pattern::HourHigh { .. } | Pattern::TimeBinHigh { .. } | Pattern::TagHigh { .. } => {
+                Some(GlucoseRange::High)
+            }
+            Pattern::WeekendDifference { .. } => None,
+        }
+    }
+
+    fn percent(&self) -> f64 {
+        self.matching as f64 * 100.0 / self.total.max(1) as f64
+    }
+
+    /// The finding as a sentence, e.g. "Low between 02:00 and 04:00 on 3 of the last 7 days"
+    pub fn describe(&self, unit: GlucoseUnit, thresholds: &Thresholds) -> String {
+        let value = |mgdl: f64, mmol: f64| match unit {
+            GlucoseUnit::MgDl => format!("{:.0} mg/dL", mgdl),
+            GlucoseUnit::MmolL => format!("{:.1} mmol/L", mmol),
+        };
+        let below = format!("below {} {}", thresholds.low_display(unit), unit.label());
+        let above = format!("above {} {}", thresholds.high_display(unit), unit.label());
+        let hours = |start: u8, end: u8| format!("{} and {}", format_minute(start as u16 * 60), format_minute(end as u16 * 60));
+
+        match &self.pattern {
+            Pattern::RecurringLow { start_hour, end_hour } => format!(
+                "Low between {} on {} of the last {} days", hours(*start_hour, *end_hour), self.matching, self.total
+            ),
+            Pattern::RecurringHigh { start_hour, end_hour } => format!(
+                "High between {} on {} of the last {} days", hours(*start_hour, *end_hour), self.matching, self.total
+            ),
+            Pattern::HourLow { hour, median_mgdl, median_mmol } | Pattern::HourHigh { hour, median_mgdl, median_mmol } => format!(
+                "Readings between {} have a median of {} ({} of {} readings {})",
+                hours(*hour, hour + 1), value(*median_mgdl, *median_mmol), self.matching, self.total,
+                if matches!(self.pattern, Pattern::HourLow { .. }) { &below } else { &above }
+            ),
+            Pattern::TimeBinLow { bin, description } => format!(
+                "{} ({}) readings {} on {:.0}% of days ({} of {})", bin, description, below, self.percent(), self.matching, self.total
+            ),
+            Pattern::TimeBinHigh { bin, description } => format!(
+                "{} ({}) readings {} on {:.0}% of days ({} of {})", bin, description, above, self.percent(), self.matching, self.total
+            ),
+            Pattern::TagLow { tag } => format!(
+                "{} readings {} {:.0}% of the time ({} of {})", tag, below, self.percent(), self.matching, self.total
+            ),
+            Pattern::TagHigh { tag } => format!(
+                "{} readings {} {:.0}% of the time ({} of {})", tag, above, self.percent(), self.matching, self.total
+            ),
+            Pattern::WeekendDifference { weekend_mgdl, weekday_mgdl, weekend_mmol, weekday_mmol } => format!(
+                "Weekend mean {} {} than on weekdays ({} vs {}; {} weekend and {} weekday readings)",
+                value((weekend_mgdl - weekday_mgdl).abs(), (weekend_mmol - weekday_mmol).abs()),
+                if weekend_mgdl > weekday_mgdl { "higher" } else { "lower" },
+                value(*weekend_mgdl, *weekend_mmol), value(*weekday_mgdl, *weekday_mmol),
+                self.matching, self.total - self.matching
+            ),
+        }
+    }
+}
+
+/// Find recurring patterns in the readings `analysis` was run on (sorted by time), lows first
+pub fn detect<R: ReadingData>(analysis: &Analysis, readings: &[R], thresholds: Thresholds) -> Vec<Insight> {
+    let mut insights = recurring_windows(readings, thresholds);
+    insights.extend(hourly_medians(&analysis.hourly, thresholds));
+    insights.extend(time_bin_shares(readings, &analysis.time_bins, thresholds));
+    insights.extend(tag_shares(readings, thresholds));
+    insights.extend(weekend_difference(readings));
+    insights.sort_by_key(|insight| match insight.range() {
+        Some(GlucoseRange::Low) => 0,
+        Some(_) => 1,
+        None => 2,
+    });
+    insights
+}
+
+fn is_low(mg_dl: u16, thresholds: &Thresholds) -> bool {
+    matches!(thresholds.classify(mg_dl), GlucoseRange::VeryLow | GlucoseRange::Low)
+}
+
+fn is_high(mg_dl: u16, thresholds: &Thresholds) -> bool {
+    matches!(thresholds.classify(mg_dl), GlucoseRange::High | GlucoseRange::VeryHigh)
+}
+
+fn parse_date(timestamp: &str) -> Option<chrono::NaiveDate> {
+    parse_timestamp(timestamp).map(|t| t.date())
+}
+
+/// Windows of the day that were low or high on several of the last few days
+fn recurring_windows<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<Insight> {
+    let Some(last_day) = readings.last().and_then(|r| parse_date(r.timestamp())) else {
+        return Vec::new();
+    };
+    let first_day = last_day - chrono::Duration::days(RECENT_DAYS - 1);
+
+    // Days each window had a low or high reading on
+    let windows = (24 / WINDOW_HOURS) as usize;
+    let mut low_days: Vec<BTreeSet<chrono::NaiveDate>> = vec![BTreeSet::new(); windows];
+    let mut high_days: Vec<BTreeSet<chrono::NaiveDate>> = vec![BTreeSet::new(); windows];
+    for reading in readings {
+        let (Some(day), Some(minute)) = (parse_date(reading.timestamp()), minute_of_day(reading.timestamp())) else {
+            continue;
+        };
+        if day < first_day {
+            continue;
+        }
+        let window = (minute / 60) as usize / WINDOW_HOURS as usize;
+        if is_low(reading.mg_dl(), &thresholds) {
+            low_days[window].insert(day);
+        } else if is_high(reading.mg_dl(), &thresholds) {
+            high_days[window].insert(day);
+        }
+    }
+
+    let mut insights = Vec::new();
+    for (days, low) in [(&low_days, true), (&high_days, false)] {
+        for (window, days) in days.iter().enumerate().filter(|(_, days)| days.len() >= MIN_RECURRING_DAYS) {
+            let start_hour = window as u8 * WINDOW_HOURS;
+            let end_hour = start_hour + WINDOW_HOURS;
+            insights.push(Insight {
+                pattern: if low {
+                    Pattern::RecurringLow { start_hour, end_hour }
+                } else {
+                    Pattern::RecurringHigh { start_hour, end_hour }
+                },
+                matching: days.len(),
+                total: RECENT_DAYS as usize,
+            });
+        }
+    }
+    insights
+}
+
+/// Hours of the day whose median reading is outside the target range
+fn hourly_medians(hourly: &[HourlyStats], thresholds: Thresholds) -> Vec<Insight> {
+    hourly.iter()
+        .filter(|h| h.count() >= MIN_SAMPLE)
+        .filter_map(|h| {
+            let stats = h.stats.as_ref()?;
+            let (median_mgdl, median_mmol) = (stats.mgdl.median, stats.mmol.median);
+            let (pattern, matching) = if median_mgdl < thresholds.low_mgdl as f64 {
+                (Pattern::HourLow { hour: h.hour, median_mgdl, median_mmol },
+                 h.mgdl_readings.iter().filter(|&&v| is_low(v, &thresholds)).count())
+            } else if median_mgdl > thresholds.high_mgdl as f64 {
+                (Pattern::HourHigh { hour: h.hour, median_mgdl, median_mmol },
+                 h.mgdl_readings.iter().filter(|&&v| is_high(v, &thresholds)).count())
+            } else {
+                return None;
+            };
+            Some(Insight { pattern, matching, total: h.count() })
+        })
+        .collect()
+}
+
+/// Time bins that are often low or high on the days they have readings
+fn time_bin_shares<R: ReadingData>(readings: &[R], time_bins: &[TimeBinStats], thresholds: Thresholds) -> Vec<Insight> {
+    let mut insights = Vec::new();
+    for bin in time_bins {
+        // Per day with readings in the bin: (any low, any high)
+        let mut days: BTreeMap<&str, (bool, bool)> = BTreeMap::new();
+        let in_bin = |r: &&R| minute_of_day(r.timestamp()).is_some_and(|m| in_time_window(m, bin.start_minute, bin.end_minute));
+        for reading in readings.iter().filter(in_bin) {
+            let day = days.entry(reading.timestamp().get(0..10).unwrap_or_default()).or_default();
+            day.0 |= is_low(reading.mg_dl(), &thresholds);
+            day.1 |= is_high(reading.mg_dl(), &thresholds);
+        }
+        if days.len() < MIN_SAMPLE {
+            continue;
+        }
+
+        let total = days.len();
+        let low_days = days.values().filter(|d| d.0).count();
+        let high_days = days.values().filter(|d| d.1).count();
+        let (bin_name, description) = (bin.name.clone(), bin.description.clone());
+        if low_days as f64 >= LOW_SHARE * total as f64 {
+            insights.push(Insight { pattern: Pattern::TimeBinLow { bin: bin_name.clone(), description: description.clone() }, matching: low_days, total });
+        }
+        if high_days as f64 >= HIGH_SHARE * total as f64 {
+            insights.push(Insight { pattern: Pattern::TimeBinHigh { bin: bin_name, description }, matching: high_days, total });
+        }
+    }
+    insights
+}
+
+/// Tags whose readings are often low or high
+fn tag_shares<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<Insight> {
+    // Per tag: (readings, low, high)
+    let mut tags: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+    for reading in readings {
+        for tag in split_tags(reading.tags().unwrap_or_default()) {
+            let counts = tags.entry(tag.to_string()).or_default();
+            counts.0 += 1;
+            counts.1 += is_low(reading.mg_dl(), &thresholds) as usize;
+            counts.2 += is_high(reading.mg_dl(), &thresholds) as usize;
+        }
+    }
+
+    let mut insights = Vec::new();
+    for (tag, (total, low, high)) in tags.into_iter().filter(|(_, counts)| counts.0 >= MIN_SAMPLE) {
+        if low as f64 >= LOW_SHARE * total as f64 {
+            insights.push(Insight { pattern: Pattern::TagLow { tag: tag.clone() }, matching: low, total });
+        }
+        if high as f64 >= HIGH_SHARE * total as f64 {
+            insights.push(Insight { pattern: Pattern::TagHigh { tag }, matching: high, total });
+        }
+    }
+    insights
+}
+
+/// Whether weekends run noticeably higher or lower than weekdays
+fn weekend_difference<R: ReadingData>(readings: &[R]) -> Option<Insight> {
+    let (weekend, weekday): (Vec<&R>, Vec<&R>) = readings.iter()
+        .filter(|r| parse_date(r.timestamp()).is_some())
+        .partition(|r| parse_date(r.timestamp()).is_some_and(|d| d.weekday().number_from_monday() >= 6));
+    if weekend.len() < MIN_SAMPLE || weekday.len() < MIN_SAMPLE {
+        return None;
+    }
+
+    let mean = |values: &[&R], f: fn(&R) -> f64| values.iter().map(|r| f(r)).sum::<f64>() / values.len() as f64;
+    let (weekend_mgdl, weekday_mgdl) = (mean(&weekend, |r| r.mg_dl() as f64), mean(&weekday, |r| r.mg_dl() as f64));
+    if (weekend_mgdl - weekday_mgdl).abs() < WEEKEND_DIFFERENCE_MGDL {
+        return None;
+    }
+    Some(Insight {
+        pattern: Pattern::WeekendDifference {
+            weekend_mgdl,
+            weekday_mgdl,
+            weekend_mmol: mean(&weekend, |r| r.mmol_l()),
+            weekday_mmol: mean(&weekday, |r| r.mmol_l()),
+        },
+        matching: weekend.len(),
+        total: weekend.len() + weekday.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::fixtures::{TaggedReading, TestReading};
+    use crate::stats::{AnalysisOptions, TimeBin};
+
+    fn run_detect<R: ReadingData>(readings: &[R], bins: &[TimeBin]) -> Vec<Insight> {
+        let analysis = Analysis::run(readings, &AnalysisOptions::new(Thresholds::default(), bins));
+        detect(&analysis, readings, Thresholds::default())
+    }
+
+    #[test]
+    fn test_recurring_night_lows() {
+        let readings = [
+            TestReading("2024/01/01 02:30", 60),
+            TestReading("2024/01/03 03:10", 65),
+            TestReading("2024/01/04 03:59", 55),
+            TestReading("2024/01/05 04:30", 60),
+            TestReading("2024/01/06 12:00", 120),
+        ];
+        let insights = run_detect(&readings, &[]);
+        assert_eq!(insights, vec![Insight {
+            pattern: Pattern::RecurringLow { start_hour: 2, end_hour: 4 },
+            matching: 3,
+            total: 7,
+        }]);
+        assert_eq!(
+            insights[0].describe(GlucoseUnit::MgDl, &Thresholds::default()),
+            "Low between 02:00 and 04:00 on 3 of the last 7 days"
+        );
+    }
+
+    #[test]
+    fn test_time_bin_tag_and_weekend_patterns() {
+        // Mon 2024/01/01 to Sun 2024/01/14: breakfast spikes on weekdays only
+        let times: [&'static str; 14] = [
+            "2024/01/01 08:30", "2024/01/02 08:30", "2024/01/03 08:30", "2024/01/04 08:30", "2024/01/05 08:30",
+            "2024/01/06 08:30", "2024/01/07 08:30", "2024/01/08 08:30", "2024/01/09 08:30", "2024/01/10 08:30",
+            "2024/01/11 08:30", "2024/01/12 08:30", "2024/01/13 08:30", "2024/01/14 08:30",
+        ];
+        let mut readings: Vec<TaggedReading> = times.iter()
+            .map(|&time| {
+                let weekend = ["06", "07", "13", "14"].contains(&&time[8..10]);
+                TaggedReading(time, if weekend { 120 } else { 200 }, "after_meal")
+            })
+            .collect();
+        readings.insert(13, TaggedReading("2024/01/13 20:00", 120, ""));
+        let bins = [TimeBin::new("Breakfast", 8 * 60, 10 * 60)];
+        let insights = run_detect(&readings, &bins);
+        let patterns: Vec<(&Pattern, usize, usize)> = insights.iter().map(|i| (&i.pattern, i.matching, i.total)).collect();
+
+        assert_eq!(patterns[..4], [
+            (&Pattern::RecurringHigh { start_hour: 8, end_hour: 10 }, 5, 7),
+            (&Pattern::HourHigh { hour: 8, median_mgdl: 200.0, median_mmol: 200.0 / 18.0 }, 10, 14),
+            (&Pattern::TimeBinHigh { bin: "Breakfast".into(), description: "08:00-10:00".into() }, 10, 14),
+            (&Pattern::TagHigh { tag: "after_meal".into() }, 10, 14),
+        ]);
+        match &insights[4].pattern {
+            Pattern::WeekendDifference { weekend_mgdl, weekday_mgdl, .. } => {
+                assert_eq!((*weekend_mgdl, *weekday_mgdl), (120.0, 200.0));
+                assert_eq!((insights[4].matching, insights[4].total), (5, 15));
+            }
+            other => panic!("expected weekend difference, got {other:?}"),
+        }
+        assert_eq!(
+            insights[2].describe(GlucoseUnit::MgDl, &Thresholds::default()),
+            "Breakfast (08:00-10:00) readings above 180 mg/dL on 71% of days (10 of 14)"
+        );
+    }
+}
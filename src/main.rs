@@ -8,6 +8,9 @@
 //! Usage:
 //!   accuchek              - Launch GUI
 //!   accuchek sync         - Download from device (CLI mode)
+//!   accuchek list         - List stored readings
+//!   accuchek exclude <id> - Exclude a reading from statistics
+//!   accuchek --profile <name> <command> - Use another patient profile
 //!   accuchek --help       - Show help
 //!   ACCUCHEK_DBG=1 accuchek sync - Enable debug output
 //!
@@ -23,15 +26,22 @@ mod error;
 mod units;
 mod stats;
 mod storage;
+mod profile;
+mod tagging;
+mod insights;
+mod timeaxis;
 mod gui;
 mod export;
 
 use std::env;
 use log::{info, warn};
 use crate::device::find_and_operate_accuchek;
-use crate::config::{Config, default_database_path, ensure_data_dir, config_file_path};
+use crate::config::{AppSettings, Config, default_database_path, ensure_data_dir, config_file_path};
 use crate::error::AccuChekError;
-use crate::storage::Storage;
+use crate::storage::{ChangeSource, Event, EventKind, Storage, SyncSession};
+use crate::profile::{Profile, Profiles, DEFAULT_PROFILE};
+use crate::units::GlucoseUnit;
+use crate::stats::{format_minute, has_tag, percentiles, AgpReport, Analysis, DateRange, Direction, EpisodeKind, PeriodComparison, AGP_BIN_MINUTES, LARGE_EXCURSION_MGDL, MAX_RATE_GAP_MINUTES, STABLE_CV_PERCENT, STANDARD_PERCENTILES};
 
 /// Attach to parent console on Windows (needed for CLI output with windows_subsystem = "windows")
 /// This redirects stdout/stderr to the parent console when running from a terminal.
@@ -69,7 +79,10 @@ fn attach_console() {
 }
 
 fn main() -> Result<(), AccuChekError> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    
+    // "--profile <name>" may appear anywhere on the command line
+    let profile_name = take_option(&mut args, "--profile")?;
     
     // Check if we're in CLI mode (any arguments passed)
     let cli_mode = args.len() > 1;
@@ -114,16 +127,33 @@ fn main() -> Result<(), AccuChekError> {
             Config::default()
         });
 
-    // Use configured path or default OS-specific path
-    let db_path = config.database_path
-        .clone()
-        .unwrap_or_else(|| default_database_path().to_string_lossy().to_string());
+    // The GUI reopens the last used profile; CLI commands use the default one
+    let command = args.get(1).map(|s| s.as_str());
+    let profiles = Profiles::load()?;
+    let profile = match &profile_name {
+        Some(name) => profiles.find(name)?.clone(),
+        None if command.is_none() => profiles.last_used().clone(),
+        None => profiles.find(DEFAULT_PROFILE)?.clone(),
+    };
+    profile.ensure_dir()?;
+    let db_path = profile.database_path(&config);
+
+    // Commands that open the database need the passphrase if it is encrypted;
+    // the GUI, which also starts for unknown commands, asks for it itself
+    let uses_database = matches!(
+        command,
+        Some("sync" | "download" | "db" | "list" | "history" | "exclude" | "include" | "note" | "tag" | "tags"
+            | "event" | "events" | "stats" | "compare" | "agp" | "autotag")
+    );
+    if uses_database {
+        unlock_database(&db_path)?;
+    }
 
     // Parse command
-    match args.get(1).map(|s| s.as_str()) {
+    match command {
         Some("sync") | Some("download") => {
             // CLI sync mode
-            cmd_sync(&config, &db_path, args.get(2))?;
+            cmd_sync(&config, &profile, args.get(2))?;
         }
         Some("--help") | Some("-h") | Some("help") => {
             print_help();
@@ -132,11 +162,53 @@ fn main() -> Result<(), AccuChekError> {
             println!("accuchek {}", env!("CARGO_PKG_VERSION"));
         }
         Some("path") | Some("paths") => {
-            cmd_show_paths();
+            cmd_show_paths(&profile, &db_path);
+        }
+        Some("profile") | Some("profiles") => {
+            cmd_profile(profiles, &args[2..])?;
+        }
+        Some("db") => {
+            cmd_db(&config, &profile, &db_path, &args[2..])?;
+        }
+        Some("list") => {
+            cmd_list(&db_path)?;
+        }
+        Some("history") => {
+            cmd_sync_history(&db_path)?;
+        }
+        Some("exclude") => {
+            cmd_exclude(&db_path, &args[2..])?;
+        }
+        Some("include") => {
+            cmd_include(&db_path, args.get(2))?;
+        }
+        Some("note") => {
+            cmd_annotate(&db_path, "note", &args[2..])?;
+        }
+        Some("tag") | Some("tags") => {
+            cmd_annotate(&db_path, "tags", &args[2..])?;
+        }
+        Some("event") => {
+            cmd_event(&db_path, &args[2..])?;
+        }
+        Some("events") => {
+            cmd_list_events(&db_path)?;
+        }
+        Some("stats") => {
+            cmd_stats(&profile, &db_path, &args[2..])?;
+        }
+        Some("compare") => {
+            cmd_compare(&profile, &db_path, &args[2..])?;
+        }
+        Some("agp") => {
+            cmd_agp(&profile, &db_path, &args[2..])?;
+        }
+        Some("autotag") => {
+            cmd_autotag(&profile, &db_path)?;
         }
         _ => {
             // Default: launch GUI
-            gui::run_gui(db_path).map_err(|e| {
+            gui::run_gui(profiles, profile).map_err(|e| {
                 AccuChekError::Communication(format!("GUI error: {}", e))
             })?;
         }
@@ -145,19 +217,70 @@ fn main() -> Result<(), AccuChekError> {
     Ok(())
 }
 
+/// Remove `<flag> <value>` from the arguments, returning the value
+///
+/// A flag given without a value is an error rather than being ignored.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, AccuChekError> {
+    let Some(pos) = args.iter().position(|a| a == flag) else {
+        return Ok(None);
+    };
+    args.remove(pos);
+    if pos < args.len() && !args[pos].starts_with("--") {
+        Ok(Some(args.remove(pos)))
+    } else {
+        Err(AccuChekError::Usage(format!("{} needs a value", flag)))
+    }
+}
+
 /// Show data paths
-fn cmd_show_paths() {
+fn cmd_show_paths(profile: &Profile, db_path: &str) {
     use crate::config::{get_data_dir, default_export_dir};
     
     println!("Accu-Chek Data Paths:");
+    println!("  Profile:         {}", profile.name);
     println!("  Data directory:  {}", get_data_dir().display());
-    println!("  Database:        {}", default_database_path().display());
+    println!("  Database:        {}", db_path);
+    println!("  Settings:        {}", profile.settings_path().display());
     println!("  Config file:     {}", config_file_path().display());
+    println!("  Backups:         {}", profile.backup_dir().display());
     println!("  Export default:  {}", default_export_dir().display());
 }
 
+/// Manage patient profiles (CLI mode)
+fn cmd_profile(mut profiles: Profiles, args: &[String]) -> Result<(), AccuChekError> {
+    match (args.first().map(|s| s.as_str()), args.get(1), args.get(2)) {
+        (Some("list") | None, _, _) => {
+            for profile in &profiles.profiles {
+                let meters = if profile.meter_serials.is_empty() {
+                    "no meters".to_string()
+                } else {
+                    format!("meters: {}", profile.meter_serials.join(", "))
+                };
+                println!("{:<16}  {}", profile.name, meters);
+            }
+        }
+        (Some("add"), Some(name), _) => {
+            let profile = profiles.add(name)?;
+            eprintln!("Created profile '{}' in {}", profile.name, profile.dir().display());
+        }
+        (Some("assign"), Some(name), Some(serial)) => {
+            profiles.assign_meter(name, serial)?;
+            eprintln!("Readings from meter {} will be imported into profile '{}'", serial, name);
+        }
+        _ => {
+            return Err(AccuChekError::Usage(
+                "Usage: accuchek profile list | add <name> | assign <name> <meter serial>".to_string()
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Sync from device (CLI mode)
-fn cmd_sync(config: &Config, db_path: &str, device_index: Option<&String>) -> Result<(), AccuChekError> {
+///
+/// Readings are imported into the profile the meter is assigned to, which may
+/// differ from the selected one.
+fn cmd_sync(config: &Config, profile: &Profile, device_index: Option<&String>) -> Result<(), AccuChekError> {
     // On Unix, check for root privileges (not needed on Windows with proper driver)
     #[cfg(unix)]
     check_root_privileges()?;
@@ -166,36 +289,616 @@ fn cmd_sync(config: &Config, db_path: &str, device_index: Option<&String>) -> Re
 
     let device_index: Option<usize> = device_index.and_then(|s| s.parse().ok());
 
-    // Initialize libusb context
-    let context = rusb::Context::new()?;
-    
-    // Find and operate the device
-    let readings = find_and_operate_accuchek(&context, config, device_index)?;
+    let mut session = SyncSession::begin();
+
+    // Initialize libusb context and find and operate the device
+    let download = match rusb::Context::new()
+        .map_err(AccuChekError::from)
+        .and_then(|context| find_and_operate_accuchek(&context, config, device_index))
+    {
+        Ok(download) => download,
+        Err(e) => {
+            record_failed_sync(&profile.database_path(config), session, &e);
+            return Err(e);
+        }
+    };
+    let readings = &download.readings;
+    session.set_download(&download);
 
-    // Save to database
+    // Failures from here on are recorded in the meter's profile once it is known
+    let mut target = profile.clone();
+    let prepared = Profiles::load()
+        .and_then(|mut profiles| profiles.route_meter(download.meter.serial.as_deref(), &profile.name))
+        .and_then(|routed| {
+            target = routed;
+            target.ensure_dir()?;
+            unlock_database(&target.database_path(config))
+        });
+    if let Err(e) = prepared {
+        record_failed_sync(&target.database_path(config), session, &e);
+        return Err(e);
+    }
+    let profile = &target;
+    let db_path = &profile.database_path(config);
+
+    // Save to database, keeping a backup of the previous state
     let storage = Storage::new(db_path)?;
-    let new_count = storage.import_readings(&readings)?;
+    auto_backup(&storage, config, profile);
+    let report = match storage.import_readings(readings) {
+        Ok(report) => report,
+        Err(e) => {
+            session.fail(&e);
+            let _ = storage.record_sync_session(&session);
+            return Err(e.into());
+        }
+    };
+    session.complete(&report);
+    storage.record_sync_session(&session)?;
+    let tagged = storage.apply_tag_rules(&AppSettings::load(profile.settings_path()).tag_rules, false)?;
     let total_count = storage.count()?;
-    let skipped_count = readings.len() - new_count;
     
     info!("Imported {} new readings ({} from device, {} total in database)", 
-          new_count, readings.len(), total_count);
+          report.inserted, readings.len(), total_count);
 
     // Always print summary (not just in debug mode)
-    eprintln!("Downloaded {} readings from device", readings.len());
-    eprintln!("  New entries:     {}", new_count);
-    eprintln!("  Duplicates:      {} (skipped)", skipped_count);
+    eprintln!("Downloaded {} readings from {}", readings.len(), session.meter_label());
+    if session.clock_drift_secs.is_some() {
+        eprintln!("  Clock drift:     {}", session.clock_drift_label());
+    }
+    eprintln!("  New entries:     {}", report.inserted);
+    eprintln!("  Duplicates:      {} (skipped)", report.duplicates);
+    if report.conflicts > 0 {
+        eprintln!("  Conflicts:       {} (stored value kept)", report.conflicts);
+    }
+    if report.rejected > 0 {
+        eprintln!("  Rejected:        {} (invalid time or value)", report.rejected);
+    }
+    if tagged > 0 {
+        eprintln!("  Auto-tagged:     {}", tagged);
+    }
     eprintln!("  Total in DB:     {}", total_count);
-    eprintln!("Saved to: {}", db_path);
+    eprintln!("Saved to: {} (profile '{}')", db_path, profile.name);
 
     // Output readings as JSON
-    let json = serde_json::to_string_pretty(&readings)?;
+    let json = serde_json::to_string_pretty(readings)?;
     println!("{}", json);
 
     eprintln!("Export complete!");
     Ok(())
 }
 
+/// Ask for the passphrase of an encrypted database (or take it from ACCUCHEK_PASSPHRASE)
+fn unlock_database(db_path: &str) -> Result<(), AccuChekError> {
+    if !storage::is_encrypted(db_path) || storage::has_passphrase(db_path) {
+        return Ok(());
+    }
+
+    let passphrase = match env::var("ACCUCHEK_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password("Database passphrase: ")?,
+    };
+    storage::set_passphrase(db_path, Some(passphrase));
+
+    match Storage::new(db_path) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+            Err(AccuChekError::WrongPassphrase)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Ask twice for a new passphrase
+fn prompt_new_passphrase() -> Result<String, AccuChekError> {
+    let passphrase = rpassword::prompt_password("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(AccuChekError::Usage("Passphrase must not be empty".to_string()));
+    }
+    if rpassword::prompt_password("Repeat passphrase: ")? != passphrase {
+        return Err(AccuChekError::Usage("Passphrases do not match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+/// Record a sync that failed before anything was imported
+fn record_failed_sync(db_path: &str, mut session: SyncSession, error: &AccuChekError) {
+    session.fail(error);
+    if let Err(e) = Storage::new(db_path).and_then(|storage| storage.record_sync_session(&session)) {
+        warn!("Could not record sync session: {}", e);
+    }
+}
+
+/// Show past sync sessions, newest first
+fn cmd_sync_history(db_path: &str) -> Result<(), AccuChekError> {
+    let storage = Storage::new(db_path)?;
+    let sessions = storage.get_sync_sessions()?;
+    if sessions.is_empty() {
+        eprintln!("No syncs recorded yet");
+        return Ok(());
+    }
+
+    println!("{:<19}  {:<28}  {:>8}  {:>4}  {:>5}  {:>6}  {:>8}",
+             "Started", "Meter", "Received", "New", "Dupes", "Errors", "Drift");
+    for session in &sessions {
+        println!("{:<19}  {:<28}  {:>8}  {:>4}  {:>5}  {:>6}  {:>8}",
+                 session.started_at, session.meter_label(), session.received, session.inserted,
+                 session.duplicates, session.error_count(), session.clock_drift_label());
+        if let Some(error) = &session.error {
+            println!("{:<19}  failed: {}", "", error);
+        }
+    }
+    Ok(())
+}
+
+/// Take a rotating backup before modifying the database
+fn auto_backup(storage: &Storage, config: &Config, profile: &Profile) {
+    match storage.auto_backup(&profile.backup_dir(), config.backup_keep()) {
+        Ok(Some(path)) => info!("Backup written to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("Warning: Could not back up database: {}", e),
+    }
+}
+
+/// Database maintenance commands (CLI mode)
+fn cmd_db(config: &Config, profile: &Profile, db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("backup"), Some(file)) => {
+            let storage = Storage::new(db_path)?;
+            storage.backup_to(file)?;
+            eprintln!("Backed up {} to {}", db_path, file);
+        }
+        (Some("restore"), Some(file)) => {
+            // Refuse to restore a missing backup over a working database;
+            // restore_from refuses damaged ones
+            if !std::path::Path::new(file).is_file() {
+                return Err(AccuChekError::Usage(format!("Backup file not found: {}", file)));
+            }
+
+            let mut storage = Storage::new(db_path)?;
+            auto_backup(&storage, config, profile);
+            storage.restore_from(file)?;
+            eprintln!("Restored {} from {} ({} readings)", db_path, file, storage.count()?);
+        }
+        (Some("merge"), Some(file)) => {
+            if !std::path::Path::new(file).is_file() {
+                return Err(AccuChekError::Usage(format!("Database not found: {}", file)));
+            }
+
+            let storage = Storage::new(db_path)?;
+            auto_backup(&storage, config, profile);
+            let report = storage.merge_from(file)?;
+
+            eprintln!("Merged {} into {}", file, db_path);
+            eprintln!("  New readings:        {}", report.inserted);
+            eprintln!("  Duplicates:          {}", report.duplicates);
+            eprintln!("  Annotations adopted: {}", report.annotations_adopted);
+            eprintln!("  Total in DB:         {}", storage.count()?);
+
+            if !report.value_conflicts.is_empty() || !report.annotation_conflicts.is_empty() {
+                eprintln!();
+                eprintln!("Conflicts (kept the value in {}):", db_path);
+                for conflict in report.value_conflicts.iter().chain(&report.annotation_conflicts) {
+                    eprintln!("  {}  {:<5}  ours: {:?}  theirs: {:?}",
+                              conflict.timestamp, conflict.field, conflict.ours, conflict.theirs);
+                }
+            }
+        }
+        (Some("check"), _) => {
+            let storage = Storage::new(db_path)?;
+            let problems = storage.integrity_check()?;
+            if problems.is_empty() {
+                eprintln!("Integrity check passed: {} ({} readings)", db_path, storage.count()?);
+            } else {
+                eprintln!("Integrity check FAILED for {}:", db_path);
+                for problem in &problems {
+                    eprintln!("  {}", problem);
+                }
+                return Err(AccuChekError::DamagedDatabase(db_path.to_string()));
+            }
+        }
+        (Some("encrypt"), _) => {
+            let passphrase = prompt_new_passphrase()?;
+            auto_backup(&Storage::new(db_path)?, config, profile);
+            Storage::change_encryption(db_path, Some(&passphrase))?;
+            eprintln!("Encrypted {}", db_path);
+            eprintln!("Backups made before now in {} are not encrypted", profile.backup_dir().display());
+        }
+        (Some("decrypt"), _) => {
+            if !storage::is_encrypted(db_path) {
+                eprintln!("{} is not encrypted", db_path);
+                return Ok(());
+            }
+            auto_backup(&Storage::new(db_path)?, config, profile);
+            Storage::change_encryption(db_path, None)?;
+            eprintln!("Decrypted {}", db_path);
+        }
+        _ => {
+            return Err(AccuChekError::Usage(
+                "Usage: accuchek db backup <file> | restore <file> | merge <other.db> | check | encrypt | decrypt".to_string()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// List all stored readings with their database IDs
+fn cmd_list(db_path: &str) -> Result<(), AccuChekError> {
+    let storage = Storage::new(db_path)?;
+    for reading in storage.get_all_readings()? {
+        let excluded = match (reading.excluded, reading.exclusion_reason.as_deref()) {
+            (true, Some(reason)) => format!("  [excluded: {}]", reason),
+            (true, None) => "  [excluded]".to_string(),
+            (false, _) => String::new(),
+        };
+        println!("{:>6}  {}  {:>3} mg/dL  {:>5.1} mmol/L{}",
+                 reading.id, reading.timestamp, reading.mg_dl, reading.mmol_l, excluded);
+    }
+    Ok(())
+}
+
+/// Parse a reading ID argument
+fn parse_reading_id(arg: Option<&String>) -> Result<i64, AccuChekError> {
+    arg.and_then(|s| s.parse().ok())
+        .ok_or_else(|| AccuChekError::Usage("Expected a reading ID (see 'accuchek list')".to_string()))
+}
+
+/// Exclude a reading from statistics (CLI mode)
+fn cmd_exclude(db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let id = parse_reading_id(args.first())?;
+    let reason = args[1..].join(" ");
+    let reason = if reason.is_empty() { "excluded via CLI".to_string() } else { reason };
+
+    let storage = Storage::new(db_path)?;
+    if storage.exclude_reading(id, &reason, ChangeSource::Cli)? == 0 {
+        eprintln!("No reading with ID {}", id);
+    } else {
+        eprintln!("Excluded reading {} ({})", id, reason);
+    }
+    Ok(())
+}
+
+/// Restore an excluded reading (CLI mode)
+fn cmd_include(db_path: &str, id: Option<&String>) -> Result<(), AccuChekError> {
+    let id = parse_reading_id(id)?;
+
+    let storage = Storage::new(db_path)?;
+    if storage.restore_reading(id, ChangeSource::Cli)? == 0 {
+        eprintln!("No reading with ID {}", id);
+    } else {
+        eprintln!("Reading {} is included in statistics again", id);
+    }
+    Ok(())
+}
+
+/// Set the note or tags of a reading (CLI mode)
+fn cmd_annotate(db_path: &str, field: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let id = parse_reading_id(args.first())?;
+    let value = args.get(1..).map(|rest| rest.join(" ")).unwrap_or_default();
+
+    let storage = Storage::new(db_path)?;
+    let updated = if field == "note" {
+        storage.update_note(id, &value, ChangeSource::Cli)?
+    } else {
+        storage.update_tags(id, &value, ChangeSource::Cli)?
+    };
+
+    if updated == 0 {
+        eprintln!("No reading with ID {}", id);
+    } else {
+        eprintln!("Updated {} of reading {}", field, id);
+    }
+    Ok(())
+}
+
+/// Readings within this many seconds of an event are linked to it automatically
+const EVENT_LINK_WINDOW_SECS: i64 = 60 * 60;
+
+/// Log or delete a carb, insulin, exercise or medication event (CLI mode)
+fn cmd_event(db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let usage = || AccuChekError::Usage(
+        "Usage: accuchek event <carbs|insulin|exercise|medication> <amount|name> [detail] [--at \"YYYY/MM/DD HH:MM\"] [--link <id>] | event delete <id>".to_string()
+    );
+
+    let mut args = args.to_vec();
+    let at = take_option(&mut args, "--at")?;
+    let link = take_option(&mut args, "--link")?;
+    let storage = Storage::new(db_path)?;
+
+    if args.first().map(|s| s.as_str()) == Some("delete") {
+        let id = parse_reading_id(args.get(1))?;
+        if storage.delete_event(id)? == 0 {
+            eprintln!("No event with ID {}", id);
+        } else {
+            eprintln!("Deleted event {}", id);
+        }
+        return Ok(());
+    }
+
+    let kind = args.first().and_then(|s| EventKind::parse(s)).ok_or_else(usage)?;
+    let (amount, detail) = if kind == EventKind::Medication {
+        (None, args[1..].join(" "))
+    } else {
+        let amount: f64 = args.get(1).and_then(|s| s.parse().ok()).ok_or_else(usage)?;
+        (Some(amount), args.get(2..).map(|rest| rest.join(" ")).unwrap_or_default())
+    };
+
+    let timestamp = at.unwrap_or_else(|| chrono::Local::now().format("%Y/%m/%d %H:%M").to_string());
+    let mut event = Event::new(kind, &timestamp, amount, Some(detail)).ok_or_else(|| {
+        AccuChekError::Usage(format!("Invalid time '{}', expected YYYY/MM/DD HH:MM", timestamp))
+    })?;
+    if event.kind == EventKind::Medication && event.detail.is_none() {
+        return Err(usage());
+    }
+
+    event.reading_id = match link {
+        Some(id) => Some(parse_reading_id(Some(&id))?),
+        None => storage.nearest_reading(event.epoch, EVENT_LINK_WINDOW_SECS)?,
+    };
+    let id = storage.add_event(&event)?;
+
+    match event.reading_id {
+        Some(reading_id) => eprintln!("Logged event {}: {} {} (linked to reading {})", id, kind.label(), event.summary(), reading_id),
+        None => eprintln!("Logged event {}: {} {}", id, kind.label(), event.summary()),
+    }
+    Ok(())
+}
+
+/// List logged events (CLI mode)
+fn cmd_list_events(db_path: &str) -> Result<(), AccuChekError> {
+    let storage = Storage::new(db_path)?;
+    for event in storage.get_events()? {
+        let linked = event.reading_id.map(|id| format!("  [reading {}]", id)).unwrap_or_default();
+        println!("{:>6}  {}  {:<10}  {}{}", event.id, event.timestamp, event.kind.label(), event.summary(), linked);
+    }
+    Ok(())
+}
+
+/// Print statistics of the stored readings, or the full analysis as JSON with --json
+fn cmd_stats(profile: &Profile, db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let mut args = args.to_vec();
+    let tag = take_option(&mut args, "--tag")?;
+    let settings = AppSettings::load(profile.settings_path());
+    let storage = Storage::new(db_path)?;
+    let mut readings = storage.get_readings()?;
+    if let Some(tag) = &tag {
+        readings.retain(|r| has_tag(r, tag));
+    }
+    let analysis = Analysis::run(&readings, &settings.analysis_options());
+    let insights = insights::detect(&analysis, &readings, settings.thresholds);
+
+    if args.iter().any(|a| a == "--json") {
+        let mut json = serde_json::to_value(&analysis)?;
+        json["insights"] = serde_json::to_value(&insights)?;
+        // The tag filter the analysis was restricted to, null for all readings
+        json["tag"] = serde_json::to_value(&tag)?;
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    let Some(stats) = &analysis.basic else {
+        match &tag {
+            Some(tag) => println!("No readings tagged \"{}\"", tag),
+            None => println!("No readings"),
+        }
+        return Ok(());
+    };
+    if let Some(tag) = &tag {
+        println!("Tag:           {}", tag);
+    }
+    let unit = settings.glucose_unit;
+    let tir = &analysis.tir;
+    println!("Readings:      {} ({} to {})", stats.count(),
+             readings.first().map(|r| r.timestamp.as_str()).unwrap_or_default(),
+             readings.last().map(|r| r.timestamp.as_str()).unwrap_or_default());
+    println!("Average:       {}", stats.format_mean(unit));
+    println!("Median:        {}", stats.format_median(unit));
+    println!("Std deviation: {} {}", stats.format_value(unit, stats.std_dev(unit)), unit.label());
+    println!("Range:         {} - {}", stats.format_min(unit), stats.format_max(unit));
+    let values: Vec<f64> = readings.iter().map(|r| unit.display_value(r.mg_dl, r.mmol_l)).collect();
+    let percentile_list: Vec<String> = STANDARD_PERCENTILES.iter()
+        .zip(percentiles(&values, &STANDARD_PERCENTILES))
+        .map(|(pct, value)| format!("P{:.0} {}", pct, stats.format_value(unit, value)))
+        .collect();
+    println!("Percentiles:   {}", percentile_list.join("  "));
+    println!("In range:      {:.1}% ({})", tir.in_range_percent(), settings.thresholds.format_range(unit));
+    println!("Below range:   {:.1}%", tir.low_percent());
+    println!("Above range:   {:.1}%", tir.high_percent());
+    if let Some(v) = &analysis.variability {
+        let mage = v.mage(unit).map(|m| format!("{:.1} {}", m, unit.label())).unwrap_or_else(|| "-".to_string());
+        println!("CV:            {:.1}% ({} the {:.0}% target)", v.cv_percent,
+                 if v.is_stable() { "within" } else { "above" }, STABLE_CV_PERCENT);
+        println!("MAGE:          {}", mage);
+        println!("LBGI / HBGI:   {:.1} / {:.1}", v.lbgi, v.hbgi);
+        println!("ADRR:          {:.1}", v.adrr);
+        println!("J-index:       {:.1}", v.j_index);
+        println!("GRI:           {:.0} (zone {})", v.gri, v.gri_zone());
+    }
+    for estimate in &analysis.a1c {
+        println!("A1c ({:>2} days): GMI {:.1}% ({:.0} mmol/mol), eA1c {:.1}% ({:.0} mmol/mol)",
+                 estimate.window_days, estimate.gmi_percent, estimate.gmi_mmol_mol,
+                 estimate.ea1c_percent, estimate.ea1c_mmol_mol);
+        if let Some(warning) = estimate.density_warning() {
+            println!("               {}", warning);
+        }
+    }
+    let episodes = &analysis.episodes;
+    println!("Hypo episodes:  {} ({:.1}/week), {} severe, {} nocturnal ({:.1}/week)",
+             episodes.count(EpisodeKind::Hypo), episodes.count_per_week(EpisodeKind::Hypo),
+             episodes.severe_count(EpisodeKind::Hypo), episodes.nocturnal_hypo_count(), episodes.nocturnal_hypo_per_week());
+    println!("Hyper episodes: {} ({:.1}/week), {} severe",
+             episodes.count(EpisodeKind::Hyper), episodes.count_per_week(EpisodeKind::Hyper), episodes.severe_count(EpisodeKind::Hyper));
+    if let Some(meals) = &analysis.meal_pairs.overall {
+        println!("Meal pairs:     {} (before {:.1} → after {:.1} {}, mean excursion {:+.1}, {} over {} mg/dL)",
+                 meals.pairs, meals.mean_before(unit), meals.mean_after(unit), unit.label(),
+                 meals.mean_excursion(unit), meals.large, LARGE_EXCURSION_MGDL);
+    }
+    if let Some(line) = &analysis.trend.regression {
+        println!("Trend:          {:+.2} {} per week (R² {:.2})", line.slope_per_week(unit), unit.label(), line.r_squared);
+    }
+    let rolling: Vec<String> = analysis.trend.rolling.iter()
+        .filter_map(|series| {
+            let latest = series.points.last()?;
+            Some(format!("{} d {:.1} {} / {:.0}% TIR", series.window_days, latest.mean(unit), unit.label(), latest.tir_percent))
+        })
+        .collect();
+    if !rolling.is_empty() {
+        println!("Rolling:        {}", rolling.join(", "));
+    }
+    let trend = &analysis.trend;
+    if !trend.rates.is_empty() {
+        println!("Rate of change: {} pairs ≤{} min apart, {} rapid, fastest rise {:+.2}, fastest fall {:+.2} {}/min",
+                 trend.rates.len(), MAX_RATE_GAP_MINUTES, trend.rapid_count(),
+                 trend.fastest_rise().map_or(0.0, |r| r.rate(unit)), trend.fastest_fall().map_or(0.0, |r| r.rate(unit)), unit.label());
+    }
+    if let Some(adherence) = &analysis.adherence {
+        println!("Testing:        {:.1}/day (target {}), {} days without tests, streak {} d (longest {} d)",
+                 adherence.tests_per_day(), adherence.target_per_day, adherence.zero_test_days.len(),
+                 adherence.current_streak, adherence.longest_streak);
+    }
+    println!();
+    for (bin, bin_stats) in analysis.time_bins.iter().filter_map(|b| Some((b, b.stats.as_ref()?))) {
+        println!("  {:<18} {:<12} n={:<4} avg {}", bin.name, bin.description, bin_stats.count(), bin_stats.format_mean(unit));
+    }
+    if !insights.is_empty() {
+        println!();
+        println!("Insights:");
+        for insight in &insights {
+            println!("  - {}", insight.describe(unit, &settings.thresholds));
+        }
+    }
+    Ok(())
+}
+
+/// Compare two date ranges: the last `--days` days against the ones before, or
+/// `<start> <end> <start> <end>` for the previous and the current period
+fn cmd_compare(profile: &Profile, db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let mut args = args.to_vec();
+    let settings = AppSettings::load(profile.settings_path());
+    let days = take_option(&mut args, "--days")?
+        .map(|value| value.parse::<u32>().ok().filter(|&d| d > 0)
+            .ok_or_else(|| AccuChekError::Usage(format!("Invalid number of days '{}'", value))))
+        .transpose()?
+        .unwrap_or(settings.comparison_days);
+    let json = args.iter().any(|a| a == "--json");
+    args.retain(|a| a != "--json");
+
+    let storage = Storage::new(db_path)?;
+    let readings = storage.get_readings()?;
+
+    let comparison = match args.as_slice() {
+        [] => match PeriodComparison::last_days(&readings, days, settings.thresholds) {
+            Some(comparison) => comparison,
+            None => {
+                println!("No readings");
+                return Ok(());
+            }
+        },
+        [prev_start, prev_end, cur_start, cur_end] => {
+            let range = |start: &str, end: &str| DateRange::new(start, end).ok_or_else(|| AccuChekError::Usage(
+                format!("Invalid date range '{} {}', expected YYYY/MM/DD YYYY/MM/DD", start, end)
+            ));
+            PeriodComparison::new(&readings, range(prev_start, prev_end)?, range(cur_start, cur_end)?, settings.thresholds)
+        }
+        _ => return Err(AccuChekError::Usage(
+            "Usage: accuchek compare [--days N] | <start> <end> <start> <end>".to_string()
+        )),
+    };
+
+    let unit = settings.glucose_unit;
+    let deltas = comparison.deltas(unit);
+    if json {
+        let output = serde_json::json!({ "comparison": comparison, "deltas": deltas });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!("Previous: {} ({} readings)", comparison.previous.range.description(), comparison.previous.tir.total);
+    println!("Current:  {} ({} readings)", comparison.current.range.description(), comparison.current.tir.total);
+    if deltas.is_empty() {
+        println!("Not enough readings in both periods to compare");
+        return Ok(());
+    }
+    println!();
+    println!("  {:<20} {:>9} {:>9} {:>9}", "", "Previous", "Current", "Change");
+    for delta in &deltas {
+        let direction = match delta.direction {
+            Direction::Improved => "better",
+            Direction::Worsened => "worse",
+            Direction::Unchanged => "same",
+        };
+        let significance = match delta.significant {
+            Some(true) => ", significant",
+            Some(false) => ", not significant",
+            None => "",
+        };
+        println!("  {:<20} {:>9.1} {:>9.1} {:>+9.1} {} ({}{})", delta.metric, delta.previous, delta.current,
+                 delta.change, delta.unit, direction, significance);
+    }
+    Ok(())
+}
+
+/// Print the Ambulatory Glucose Profile: consensus metrics and the modal day
+fn cmd_agp(profile: &Profile, db_path: &str, args: &[String]) -> Result<(), AccuChekError> {
+    let mut args = args.to_vec();
+    let settings = AppSettings::load(profile.settings_path());
+    let bin_minutes = match take_option(&mut args, "--bins")? {
+        Some(value) => value.parse::<u16>().ok()
+            .filter(|m| AGP_BIN_MINUTES.contains(m))
+            .ok_or_else(|| AccuChekError::Usage(format!("--bins must be 15 or 60, got '{}'", value)))?,
+        None => settings.agp_bin_minutes,
+    };
+    let storage = Storage::new(db_path)?;
+    let agp = AgpReport::from_readings(&storage.get_readings()?, bin_minutes);
+
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&agp)?);
+        return Ok(());
+    }
+
+    let Some(metrics) = &agp.metrics else {
+        println!("No readings");
+        return Ok(());
+    };
+    let unit = settings.glucose_unit;
+    let format = |value: f64| match unit {
+        GlucoseUnit::MgDl => format!("{:.0}", value),
+        GlucoseUnit::MmolL => format!("{:.1}", value),
+    };
+    println!("Period:        {} to {} ({} days, {} with readings)",
+             metrics.start_date, metrics.end_date, metrics.days, metrics.days_with_readings);
+    println!("Readings:      {} ({:.1}/day)", metrics.readings, metrics.readings_per_day());
+    println!("Mean glucose:  {} {}", format(metrics.mean(unit)), unit.label());
+    println!("GMI:           {:.1}%", metrics.gmi_percent);
+    println!("CV:            {:.1}% (goal <= {:.0}%)", metrics.cv_percent, STABLE_CV_PERCENT);
+    for target in metrics.range_targets() {
+        println!("  {:<28} {:>5.1}%  goal {:<5} {}", target.label(unit), target.percent, target.target,
+                 if target.met { "met" } else { "not met" });
+    }
+    println!();
+    println!("  {:<6} {:>5} {:>6} {:>6} {:>6} {:>6} {:>6}", "Time", "n", "P5", "P25", "P50", "P75", "P95");
+    for bin in &agp.bins {
+        match bin.band(unit) {
+            Some(b) => println!("  {:<6} {:>5} {:>6} {:>6} {:>6} {:>6} {:>6}", format_minute(bin.start_minute), bin.count,
+                                format(b.p5), format(b.p25), format(b.p50), format(b.p75), format(b.p95)),
+            None => println!("  {:<6} {:>5}", format_minute(bin.start_minute), 0),
+        }
+    }
+    Ok(())
+}
+
+/// Re-run the automatic tagging rules over all readings
+fn cmd_autotag(profile: &Profile, db_path: &str) -> Result<(), AccuChekError> {
+    let rules = AppSettings::load(profile.settings_path()).tag_rules;
+    let storage = Storage::new(db_path)?;
+    let changed = storage.apply_tag_rules(&rules, true)?;
+
+    for rule in rules.iter().filter(|r| r.enabled) {
+        eprintln!("  {:<12} {}", rule.tag, rule.condition.describe());
+    }
+    eprintln!("Retagged {} readings (tags set by hand are left alone)", changed);
+    Ok(())
+}
+
 fn print_help() {
     eprintln!("Accu-Chek USB Data Downloader v{}", env!("CARGO_PKG_VERSION"));
     eprintln!();
@@ -203,10 +906,39 @@ fn print_help() {
     eprintln!("  accuchek                    Launch GUI application");
     eprintln!("  accuchek sync [device_idx]  Download from device (CLI mode)");
     eprintln!("  accuchek path               Show data file locations");
+    eprintln!("  accuchek db backup <file>   Copy the database to a file");
+    eprintln!("  accuchek db restore <file>  Replace the database with a backup");
+    eprintln!("  accuchek db merge <file>    Import readings and annotations from another database");
+    eprintln!("  accuchek db check           Verify database integrity");
+    eprintln!("  accuchek db encrypt         Encrypt the database (or change its passphrase)");
+    eprintln!("  accuchek db decrypt         Store the database unencrypted again");
+    eprintln!("  accuchek history            Show past syncs with the meter");
+    eprintln!("  accuchek list               List stored readings with IDs");
+    eprintln!("  accuchek exclude <id> [reason]  Exclude a reading from statistics");
+    eprintln!("  accuchek include <id>       Restore an excluded reading");
+    eprintln!("  accuchek note <id> <text>   Set the note of a reading");
+    eprintln!("  accuchek tag <id> <tags>    Set the comma-separated tags of a reading");
+    eprintln!("  accuchek event carbs 45 [lunch] [--at \"YYYY/MM/DD HH:MM\"] [--link <id>]");
+    eprintln!("                              Log carbs (g), insulin (units, type), exercise (min)");
+    eprintln!("                              or medication (name); links the nearest reading");
+    eprintln!("  accuchek events             List logged events");
+    eprintln!("  accuchek event delete <id>  Delete an event");
+    eprintln!("  accuchek stats [--tag <tag>] [--json]  Show statistics, optionally of one tag's readings");
+    eprintln!("  accuchek compare [--days N] [--json]  Compare the last N days (default 14) with the N before");
+    eprintln!("  accuchek compare <start> <end> <start> <end>  Compare two date ranges (YYYY/MM/DD)");
+    eprintln!("  accuchek agp [--bins 15|60] [--json]  Show the Ambulatory Glucose Profile");
+    eprintln!("  accuchek autotag            Re-apply the automatic tagging rules to all readings");
+    eprintln!("  accuchek profile list       List profiles and their meters");
+    eprintln!("  accuchek profile add <name> Create a profile with its own database and settings");
+    eprintln!("  accuchek profile assign <name> <serial>  Import a meter's readings into a profile");
     eprintln!("  accuchek help               Show this help");
     eprintln!();
+    eprintln!("OPTIONS:");
+    eprintln!("  --profile <name>            Use a profile other than 'default' (any command)");
+    eprintln!();
     eprintln!("ENVIRONMENT:");
     eprintln!("  ACCUCHEK_DBG=1              Enable debug output");
+    eprintln!("  ACCUCHEK_PASSPHRASE=...     Passphrase of an encrypted database (instead of prompting)");
     eprintln!();
     eprintln!("DATA LOCATIONS:");
     eprintln!("  Database:  {}", default_database_path().display());
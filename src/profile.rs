@@ -0,0 +1,251 @@
+//! Named patient profiles, each with its own database, settings and meters
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::error::AccuChekError;
+
+/// Profile used when none is chosen; it keeps the original file locations
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A person whose readings are kept in their own database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Serial numbers of meters whose readings belong to this profile
+    #[serde(default)]
+    pub meter_serials: Vec<String>,
+}
+
+impl Profile {
+    fn new(name: &str) -> Self {
+        Self { name: name.to_string(), meter_serials: Vec::new() }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_PROFILE
+    }
+
+    /// Directory holding the profile's files (the data directory for the default profile)
+    pub fn dir(&self) -> PathBuf {
+        if self.is_default() {
+            config::get_data_dir()
+        } else {
+            config::get_data_dir().join("profiles").join(&self.name)
+        }
+    }
+
+    /// Make sure the profile's directory exists before its database is opened
+    pub fn ensure_dir(&self) -> io::Result<()> {
+        fs::create_dir_all(self.dir())
+    }
+
+    /// Database path; the default profile honours `database_path` from config.txt
+    pub fn database_path(&self, config: &Config) -> String {
+        match &config.database_path {
+            Some(path) if self.is_default() => path.clone(),
+            _ => self.dir().join("accuchek.db").to_string_lossy().to_string(),
+        }
+    }
+
+    /// Settings file holding the profile's thresholds and unit
+    pub fn settings_path(&self) -> PathBuf {
+        if self.is_default() {
+            config::settings_file_path()
+        } else {
+            self.dir().join("settings.json")
+        }
+    }
+
+    /// Directory holding the profile's automatic backups
+    pub fn backup_dir(&self) -> PathBuf {
+        if self.is_default() {
+            config::backup_dir()
+        } else {
+            self.dir().join("backups")
+        }
+    }
+}
+
+/// All profiles, stored in profiles.json in the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    /// Profile the GUI opens with
+    #[serde(default)]
+    pub last_used: Option<String>,
+    /// File the list was loaded from and is saved to
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+            last_used: None,
+            path: config::profiles_file_path(),
+        }
+    }
+}
+
+impl Profiles {
+    /// Load profiles.json from the data directory
+    pub fn load() -> Result<Self, AccuChekError> {
+        Self::load_from(config::profiles_file_path())
+    }
+
+    /// Load a profile list, always including the default profile
+    ///
+    /// A missing file gives just the default profile. A file that cannot be read
+    /// or parsed is an error, so that saving does not overwrite the profiles in it.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, AccuChekError> {
+        let path = path.as_ref();
+        let mut profiles: Self = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| AccuChekError::DamagedProfiles(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        profiles.path = path.to_path_buf();
+
+        if profiles.get(DEFAULT_PROFILE).is_none() {
+            profiles.profiles.insert(0, Profile::new(DEFAULT_PROFILE));
+        }
+        Ok(profiles)
+    }
+
+    pub fn save(&self) -> Result<(), AccuChekError> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, json)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Look up a profile by name, failing with a helpful error if it does not exist
+    pub fn find(&self, name: &str) -> Result<&Profile, AccuChekError> {
+        self.get(name).ok_or_else(|| AccuChekError::UnknownProfile(name.to_string()))
+    }
+
+    /// Profile the GUI should open: the last one used, or the default
+    pub fn last_used(&self) -> &Profile {
+        self.last_used
+            .as_deref()
+            .and_then(|name| self.get(name))
+            .or_else(|| self.get(DEFAULT_PROFILE))
+            .unwrap_or(&self.profiles[0])
+    }
+
+    /// Create a new profile and its directory
+    pub fn add(&mut self, name: &str) -> Result<&Profile, AccuChekError> {
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AccuChekError::Usage(
+                "Profile names may only contain letters, digits, '-' and '_'".to_string()
+            ));
+        }
+        if self.get(name).is_some() {
+            return Err(AccuChekError::Usage(format!("Profile '{}' already exists", name)));
+        }
+
+        let profile = Profile::new(name);
+        profile.ensure_dir()?;
+        self.profiles.push(profile);
+        self.save()?;
+        Ok(&self.profiles[self.profiles.len() - 1])
+    }
+
+    /// Assign a meter to a profile, removing it from any other profile
+    pub fn assign_meter(&mut self, name: &str, serial: &str) -> Result<(), AccuChekError> {
+        self.find(name)?;
+        for profile in &mut self.profiles {
+            profile.meter_serials.retain(|s| s != serial);
+            if profile.name == name {
+                profile.meter_serials.push(serial.to_string());
+            }
+        }
+        self.save()
+    }
+
+    /// Profile that readings from a meter belong to
+    ///
+    /// Meters without a known serial go to `fallback`. A meter seen for the
+    /// first time is assigned to `fallback`, so later syncs route it there.
+    pub fn route_meter(&mut self, serial: Option<&str>, fallback: &str) -> Result<Profile, AccuChekError> {
+        let Some(serial) = serial else {
+            return self.find(fallback).cloned();
+        };
+
+        if let Some(owner) = self.profiles.iter().find(|p| p.meter_serials.iter().any(|s| s == serial)) {
+            return Ok(owner.clone());
+        }
+
+        self.assign_meter(fallback, serial)?;
+        self.find(fallback).cloned()
+    }
+
+    /// Remember the profile the GUI should open next time
+    pub fn set_last_used(&mut self, name: &str) {
+        self.last_used = Some(name.to_string());
+        if let Err(e) = self.save() {
+            log::warn!("Could not save profiles: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("accuchek-test-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_load_missing_and_damaged() {
+        let path = temp_file("profiles-damaged");
+        let profiles = Profiles::load_from(&path).unwrap();
+        assert_eq!(profiles.profiles.len(), 1);
+        assert!(profiles.last_used().is_default());
+
+        // A damaged file is reported and left as it is
+        fs::write(&path, r#"{"profiles": [{"name": "anna""#).unwrap();
+        assert!(matches!(Profiles::load_from(&path), Err(AccuChekError::DamagedProfiles(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"profiles": [{"name": "anna""#);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_route_meter() {
+        let path = temp_file("profiles-route");
+        fs::write(&path, r#"{"profiles": [{"name": "anna", "meter_serials": ["111"]}], "last_used": "anna"}"#).unwrap();
+        let mut profiles = Profiles::load_from(&path).unwrap();
+        assert_eq!(profiles.profiles[0].name, DEFAULT_PROFILE);
+        assert_eq!(profiles.last_used().name, "anna");
+
+        assert_eq!(profiles.route_meter(Some("111"), DEFAULT_PROFILE).unwrap().name, "anna");
+        assert_eq!(profiles.route_meter(None, "anna").unwrap().name, "anna");
+        // A new meter is assigned to the fallback and saved
+        assert_eq!(profiles.route_meter(Some("222"), DEFAULT_PROFILE).unwrap().name, DEFAULT_PROFILE);
+        let saved = Profiles::load_from(&path).unwrap();
+        assert_eq!(saved.find(DEFAULT_PROFILE).unwrap().meter_serials, vec!["222"]);
+
+        // Reassigning moves the meter
+        profiles.assign_meter("anna", "222").unwrap();
+        assert!(profiles.find(DEFAULT_PROFILE).unwrap().meter_serials.is_empty());
+        assert_eq!(profiles.find("anna").unwrap().meter_serials, vec!["111", "222"]);
+        assert!(profiles.assign_meter("nobody", "333").is_err());
+        assert!(matches!(profiles.add("bad name"), Err(AccuChekError::Usage(_))));
+        assert!(matches!(profiles.add("anna"), Err(AccuChekError::Usage(_))));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -14,9 +14,11 @@ pub struct MgDlStats {
     pub std_dev: f64,
     pub min: u16,
     pub max: u16,
-    pub median: u16,
-    pub q1: u16,  // 25th percentile
-    pub q3: u16,  // 75th percentile
+    pub median: f64,
+    pub q1: f64,  // 25th percentile
+    pub q3: f64,  // 75th percentile
+    pub p5: f64,
+    pub p95: f64,
 }
 
 /// Statistical measures for mmol/L values (float)
@@ -30,6 +32,18 @@ pub struct MmolLStats {
     pub median: f64,
     pub q1: f64,  // 25th percentile
     pub q3: f64,  // 75th percentile
+    pub p5: f64,
+    pub p95: f64,
+}
+
+/// The 5/25/50/75/95 percentiles in one unit, as drawn by AGP-style charts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PercentileBand {
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
 }
 
 /// Basic statistical measures in both units
@@ -52,6 +66,7 @@ impl MgDlStats {
         
         let mut sorted = values.to_vec();
         sorted.sort_unstable();
+        let sorted_f64: Vec<f64> = sorted.iter().map(|&v| v as f64).collect();
         
         Some(Self {
             count,
@@ -59,9 +74,11 @@ impl MgDlStats {
             std_dev,
             min: sorted[0],
             max: sorted[count - 1],
-            median: percentile_u16(&sorted, 50.0),
-            q1: percentile_u16(&sorted, 25.0),
-            q3: percentile_u16(&sorted, 75.0),
+            median: percentile(&sorted_f64, 50.0),
+            q1: percentile(&sorted_f64, 25.0),
+            q3: percentile(&sorted_f64, 75.0),
+            p5: percentile(&sorted_f64, 5.0),
+            p95: percentile(&sorted_f64, 95.0),
         })
     }
 
@@ -87,7 +104,7 @@ impl MmolLStats {
         let std_dev = calculate_std_dev_f64(values, mean);
         
         let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        sorted.sort_by(f64::total_cmp);
         
         Some(Self {
             count,
@@ -95,9 +112,11 @@ impl MmolLStats {
             std_dev,
             min: sorted[0],
             max: sorted[count - 1],
-            median: percentile_f64(&sorted, 50.0),
-            q1: percentile_f64(&sorted, 25.0),
-            q3: percentile_f64(&sorted, 75.0),
+            median: percentile(&sorted, 50.0),
+            q1: percentile(&sorted, 25.0),
+            q3: percentile(&sorted, 75.0),
+            p5: percentile(&sorted, 5.0),
+            p95: percentile(&sorted, 95.0),
         })
     }
 
@@ -152,11 +171,23 @@ impl BasicStats {
     /// Get median in user's preferred unit
     pub fn median(&self, unit: GlucoseUnit) -> f64 {
         match unit {
-            GlucoseUnit::MgDl => self.mgdl.median as f64,
+            GlucoseUnit::MgDl => self.mgdl.median,
             GlucoseUnit::MmolL => self.mmol.median,
         }
     }
 
+    /// 5th to 95th percentiles in user's preferred unit
+    pub fn band(&self, unit: GlucoseUnit) -> PercentileBand {
+        match unit {
+            GlucoseUnit::MgDl => PercentileBand {
+                p5: self.mgdl.p5, p25: self.mgdl.q1, p50: self.mgdl.median, p75: self.mgdl.q3, p95: self.mgdl.p95,
+            },
+            GlucoseUnit::MmolL => PercentileBand {
+                p5: self.mmol.p5, p25: self.mmol.q1, p50: self.mmol.median, p75: self.mmol.q3, p95: self.mmol.p95,
+            },
+        }
+    }
+
     /// Get std_dev in user's preferred unit
     pub fn std_dev(&self, unit: GlucoseUnit) -> f64 {
         match unit {
@@ -200,7 +231,7 @@ impl BasicStats {
     /// Format median with unit
     pub fn format_median(&self, unit: GlucoseUnit) -> String {
         match unit {
-            GlucoseUnit::MgDl => format!("{} mg/dL", self.mgdl.median),
+            GlucoseUnit::MgDl => format!("{:.0} mg/dL", self.mgdl.median),
             GlucoseUnit::MmolL => format!("{:.1} mmol/L", self.mmol.median),
         }
     }
@@ -307,6 +338,7 @@ pub struct DailyStats {
     pub max_mgdl: u16,
     pub max_mmol: f64,
     pub tir: TimeInRange,
+    pub variability: Option<VariabilityStats>,
 }
 
 #[allow(dead_code)]
@@ -337,6 +369,7 @@ impl DailyStats {
             max_mgdl: mgdl_values.iter().copied().max().unwrap_or(0),
             max_mmol: mmol_values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
             tir: TimeInRange::from_values(mgdl_values, thresholds),
+            variability: VariabilityStats::from_days(&[(mgdl_values.to_vec(), mmol_values.to_vec())]),
         }
     }
 
@@ -368,6 +401,51 @@ impl HourlyStats {
     pub fn count(&self) -> usize {
         self.mgdl_readings.len()
     }
+
+    /// 5th to 95th percentile band of the hour in user's preferred unit
+    pub fn band(&self, unit: GlucoseUnit) -> Option<PercentileBand> {
+        self.stats.as_ref().map(|s| s.band(unit))
+    }
+}
+
+/// A named window of the day, e.g. "Breakfast 06:00-09:00"
+///
+/// A bin whose start is later than its end wraps past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeBin {
+    pub name: String,
+    /// Minutes since midnight
+    pub start_minute: u16,
+    /// Minutes since midnight (exclusive); 1440 is the end of the day
+    pub end_minute: u16,
+}
+
+impl TimeBin {
+    pub fn new(name: &str, start_minute: u16, end_minute: u16) -> Self {
+        Self { name: name.to_string(), start_minute, end_minute }
+    }
+
+    /// Whether a reading taken at the given "YYYY/MM/DD HH:MM" timestamp falls in the bin
+    pub fn contains(&self, timestamp: &str) -> bool {
+        minute_of_day(timestamp).is_some_and(|m| in_time_window(m, self.start_minute, self.end_minute))
+    }
+
+    /// Time range, e.g. "21:00-06:00"
+    pub fn description(&self) -> String {
+        format!("{}-{}", format_minute(self.start_minute), format_minute(self.end_minute))
+    }
+}
+
+/// The clinical time bins used until the user defines their own
+pub fn default_time_bins() -> Vec<TimeBin> {
+    vec![
+        TimeBin::new("Overnight", 0, 6 * 60),
+        TimeBin::new("Fasting/Morning", 6 * 60, 9 * 60),
+        TimeBin::new("Mid-Morning", 9 * 60, 12 * 60),
+        TimeBin::new("Afternoon", 12 * 60, 18 * 60),
+        TimeBin::new("Evening", 18 * 60, 21 * 60),
+        TimeBin::new("Night", 21 * 60, 24 * 60),
+    ]
 }
 
 /// Clinical time bin statistics for boxplots (both units)
@@ -375,8 +453,8 @@ impl HourlyStats {
 pub struct TimeBinStats {
     pub name: String,
     pub description: String,
-    pub hour_start: u8,
-    pub hour_end: u8,
+    pub start_minute: u16,
+    pub end_minute: u16,
     pub mgdl_readings: Vec<u16>,
     pub mmol_readings: Vec<f64>,
     pub stats: Option<BasicStats>,
@@ -384,13 +462,13 @@ pub struct TimeBinStats {
 
 impl TimeBinStats {
     /// Create time bin stats
-    pub fn new(name: &str, description: &str, hour_start: u8, hour_end: u8, mgdl_readings: Vec<u16>, mmol_readings: Vec<f64>) -> Self {
+    pub fn new(bin: &TimeBin, mgdl_readings: Vec<u16>, mmol_readings: Vec<f64>) -> Self {
         let stats = BasicStats::from_values(&mgdl_readings, &mmol_readings);
         Self {
-            name: name.to_string(),
-            description: description.to_string(),
-            hour_start,
-            hour_end,
+            name: bin.name.clone(),
+            description: bin.description(),
+            start_minute: bin.start_minute,
+            end_minute: bin.end_minute,
             mgdl_readings,
             mmol_readings,
             stats,
@@ -449,24 +527,124 @@ impl CalendarDay {
     }
 }
 
+/// Short weekday names, Monday first
+pub const WEEKDAY_LABELS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Statistics of one ISO week (both units)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyStats {
+    pub iso_year: i32,
+    pub week: u32,
+    /// Monday of the week ("YYYY/MM/DD")
+    pub week_start: String,
+    pub days_with_readings: usize,
+    pub stats: BasicStats,
+    pub tir: TimeInRange,
+    pub variability: Option<VariabilityStats>,
+}
+
+impl WeeklyStats {
+    /// ISO week label, e.g. "2024-W05"
+    pub fn label(&self) -> String {
+        format!("{}-W{:02}", self.iso_year, self.week)
+    }
+}
+
+/// Statistics of all readings taken on one day of the week (both units)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeekdayStats {
+    pub day_of_week: u8,  // 0=Monday, 6=Sunday
+    pub days_with_readings: usize,
+    pub stats: BasicStats,
+    pub tir: TimeInRange,
+    pub variability: Option<VariabilityStats>,
+}
+
+impl WeekdayStats {
+    pub fn label(&self) -> &'static str {
+        WEEKDAY_LABELS[self.day_of_week as usize % 7]
+    }
+}
+
 // ============= Helper Functions =============
 
-/// Calculate percentile from sorted u16 values
-fn percentile_u16(sorted_values: &[u16], pct: f64) -> u16 {
-    if sorted_values.is_empty() {
-        return 0;
+/// Minutes since midnight of a "YYYY/MM/DD HH:MM" timestamp
+pub fn minute_of_day(timestamp: &str) -> Option<u16> {
+    let hour: u16 = timestamp.get(11..13)?.parse().ok()?;
+    let minute: u16 = timestamp.get(14..16)?.parse().ok()?;
+    Some(hour * 60 + minute)
+}
+
+/// Values of the readings in consecutive `bin_minutes` slots of the day
+fn bin_by_minute<R: ReadingData>(readings: &[R], bin_minutes: u16) -> Vec<(Vec<u16>, Vec<f64>)> {
+    let bin_count = (24 * 60usize).div_ceil(bin_minutes as usize);
+    let mut values: Vec<(Vec<u16>, Vec<f64>)> = vec![(Vec::new(), Vec::new()); bin_count];
+    for reading in readings {
+        if let Some(minute) = minute_of_day(reading.timestamp()) {
+            let slot = &mut values[(minute / bin_minutes) as usize % bin_count];
+            slot.0.push(reading.mg_dl());
+            slot.1.push(reading.mmol_l());
+        }
     }
-    let idx = ((sorted_values.len() as f64 - 1.0) * pct / 100.0).round() as usize;
-    sorted_values[idx.min(sorted_values.len() - 1)]
+    values
 }
 
-/// Calculate percentile from sorted f64 values
-fn percentile_f64(sorted_values: &[f64], pct: f64) -> f64 {
-    if sorted_values.is_empty() {
-        return 0.0;
+/// Whether `minute` falls in [start, end), wrapping past midnight when start > end
+pub fn in_time_window(minute: u16, start: u16, end: u16) -> bool {
+    if start <= end {
+        minute >= start && minute < end
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+/// Format minutes since midnight as "HH:MM"
+pub fn format_minute(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+/// Tags of a reading's comma-separated tag list
+pub fn split_tags(tags: &str) -> impl Iterator<Item = &str> {
+    tags.split(',').map(str::trim).filter(|t| !t.is_empty())
+}
+
+/// Whether a reading carries `tag` in its comma-separated tag list
+pub fn has_tag<R: ReadingData>(reading: &R, tag: &str) -> bool {
+    reading.tags().is_some_and(|tags| split_tags(tags).any(|t| t == tag))
+}
+
+/// Every tag used by the readings, sorted
+pub fn distinct_tags<R: ReadingData>(readings: &[R]) -> Vec<String> {
+    let tags: std::collections::BTreeSet<&str> = readings.iter()
+        .filter_map(|r| r.tags())
+        .flat_map(split_tags)
+        .collect();
+    tags.into_iter().map(str::to_string).collect()
+}
+
+/// Percentiles reported by default, e.g. for AGP-style charts
+pub const STANDARD_PERCENTILES: [f64; 7] = [5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0];
+
+/// Linearly interpolated percentile of sorted values (Hyndman & Fan type 7,
+/// as used by spreadsheets and R's default)
+pub fn percentile(sorted_values: &[f64], pct: f64) -> f64 {
+    match sorted_values.len() {
+        0 => 0.0,
+        1 => sorted_values[0],
+        n => {
+            let h = (n - 1) as f64 * (pct / 100.0).clamp(0.0, 1.0);
+            let lower = h.floor() as usize;
+            let upper = (lower + 1).min(n - 1);
+            sorted_values[lower] + (h - lower as f64) * (sorted_values[upper] - sorted_values[lower])
+        }
     }
-    let idx = ((sorted_values.len() as f64 - 1.0) * pct / 100.0).round() as usize;
-    sorted_values[idx.min(sorted_values.len() - 1)]
+}
+
+/// Percentiles of unsorted values, one per entry of `pcts`
+pub fn percentiles<T: Copy + Into<f64>>(values: &[T], pcts: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().map(|&v| v.into()).collect();
+    sorted.sort_by(f64::total_cmp);
+    pcts.iter().map(|&pct| percentile(&sorted, pct)).collect()
 }
 
 /// Calculate standard deviation for u16 values
@@ -491,168 +669,2124 @@ fn calculate_std_dev_f64(values: &[f64], mean: f64) -> f64 {
     variance.sqrt()
 }
 
-// ============= Export Statistics (separate from in-app) =============
+// ============= Glycemic Variability =============
 
-/// Statistics specifically formatted for PDF export
-#[derive(Debug, Clone)]
-pub struct ExportStatistics {
-    pub basic: BasicStats,
-    pub tir: TimeInRange,
-    pub daily: Vec<DailyStats>,
-    pub hourly: Vec<HourlyStats>,
-    pub time_bins: Vec<TimeBinStats>,
-    pub histogram: Vec<HistogramBin>,
+/// Coefficient of variation at or below which glucose is considered stable (%)
+pub const STABLE_CV_PERCENT: f64 = 36.0;
+
+/// Kovatchev's symmetrised blood glucose risk for one mg/dL value,
+/// split into its low and high parts
+fn bg_risk(mg_dl: u16) -> (f64, f64) {
+    let f = 1.509 * ((mg_dl.max(1) as f64).ln().powf(1.084) - 5.381);
+    let risk = 10.0 * f * f;
+    if f < 0.0 { (risk, 0.0) } else { (0.0, risk) }
 }
 
-impl ExportStatistics {
-    /// Generate all export statistics from stored readings
-    pub fn generate<R>(readings: &[R], thresholds: Thresholds) -> Self 
-    where
-        R: ReadingData,
+/// Mean amplitude of glycemic excursions: the average rise or fall between
+/// successive peaks and nadirs that exceeds one standard deviation
+///
+/// Each day is a separate series, so no excursion spans a day boundary or a
+/// gap of several days.
+fn mage<'a, T: Copy + Into<f64> + 'a>(days: impl IntoIterator<Item = &'a [T]>, std_dev: f64) -> Option<f64> {
+    let excursions: Vec<f64> = days.into_iter()
+        .flat_map(|values| {
+            let turning_points = significant_turning_points(values, std_dev);
+            turning_points.windows(2).map(|w| (w[1] - w[0]).abs()).collect::<Vec<_>>()
+        })
+        .collect();
+    if excursions.is_empty() {
+        return None;
+    }
+    Some(excursions.iter().sum::<f64>() / excursions.len() as f64)
+}
+
+/// Alternating peaks and nadirs of a series, after smoothing out every swing
+/// of at most `std_dev`, smallest first
+fn significant_turning_points<T: Copy + Into<f64>>(values: &[T], std_dev: f64) -> Vec<f64> {
+    let mut series: Vec<f64> = values.iter().map(|&v| v.into()).collect();
+    series.dedup();
+
+    // Turning points: the ends plus every local peak and nadir
+    let mut points: Vec<f64> = Vec::new();
+    for (i, &v) in series.iter().enumerate() {
+        let is_end = i == 0 || i == series.len() - 1;
+        let is_extremum = !is_end && ((v > series[i - 1]) == (v > series[i + 1]));
+        if is_end || is_extremum {
+            points.push(v);
+        }
+    }
+
+    while let Some(i) = (0..points.len().saturating_sub(1))
+        .filter(|&i| (points[i + 1] - points[i]).abs() <= std_dev)
+        .min_by(|&a, &b| (points[a + 1] - points[a]).abs().total_cmp(&(points[b + 1] - points[b]).abs()))
     {
-        let mgdl_values: Vec<u16> = readings.iter().map(|r| r.mg_dl()).collect();
-        let mmol_values: Vec<f64> = readings.iter().map(|r| r.mmol_l()).collect();
-        
-        let basic = BasicStats::from_values(&mgdl_values, &mmol_values).unwrap_or_else(|| {
-            BasicStats {
-                mgdl: MgDlStats { count: 0, mean: 0.0, std_dev: 0.0, min: 0, max: 0, median: 0, q1: 0, q3: 0 },
-                mmol: MmolLStats { count: 0, mean: 0.0, std_dev: 0.0, min: 0.0, max: 0.0, median: 0.0, q1: 0.0, q3: 0.0 },
-            }
-        });
-        let tir = TimeInRange::from_values(&mgdl_values, thresholds);
-        
-        let daily = Self::calculate_daily(readings, thresholds);
-        let hourly = Self::calculate_hourly(readings);
-        let time_bins = Self::calculate_time_bins(readings);
-        let histogram = Self::calculate_histogram(&mgdl_values);
-        
-        Self { basic, tir, daily, hourly, time_bins, histogram }
+        if i == 0 {
+            points.remove(0);
+        } else if i + 2 == points.len() {
+            points.pop();
+        } else {
+            // Drop the swing and keep the more extreme of the points around it,
+            // e.g. peak, nadir, peak, nadir becomes the higher peak, lower nadir
+            let rising = points[i + 1] > points[i];
+            let (before, after) = (points[i - 1], points[i + 2]);
+            points[i - 1] = if rising { before.max(points[i + 1]) } else { before.min(points[i + 1]) };
+            points[i + 2] = if rising { after.min(points[i]) } else { after.max(points[i]) };
+            points.drain(i..i + 2);
+        }
     }
+    points
+}
 
-    fn calculate_daily<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<DailyStats> {
-        use std::collections::BTreeMap;
-        let mut daily_readings: BTreeMap<String, (Vec<u16>, Vec<f64>)> = BTreeMap::new();
+/// Glycemic variability and risk indices, over a period or a single day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariabilityStats {
+    pub count: usize,
+    /// Coefficient of variation (%), SD / mean
+    pub cv_percent: f64,
+    /// Mean amplitude of glycemic excursions, `None` if no excursion exceeds one SD
+    pub mage_mgdl: Option<f64>,
+    pub mage_mmol: Option<f64>,
+    /// Low and high blood glucose indices (Kovatchev)
+    pub lbgi: f64,
+    pub hbgi: f64,
+    /// Average daily risk range: mean over days of the day's highest low and high risk
+    pub adrr: f64,
+    /// J-index, 0.001 x (mean + SD)^2 in mg/dL
+    pub j_index: f64,
+    /// Glycemia Risk Index (Klonoff 2022), 0-100
+    pub gri: f64,
+}
 
-        for reading in readings {
-            if let Some(date) = reading.timestamp().get(0..10) {
-                let entry = daily_readings.entry(date.to_string()).or_default();
-                entry.0.push(reading.mg_dl());
-                entry.1.push(reading.mmol_l());
-            }
+impl VariabilityStats {
+    /// Compute variability from readings grouped by day, each day in time order
+    ///
+    /// Returns `None` with fewer than two readings.
+    pub fn from_days(days: &[(Vec<u16>, Vec<f64>)]) -> Option<Self> {
+        let mgdl: Vec<u16> = days.iter().flat_map(|(v, _)| v.iter().copied()).collect();
+        let mmol: Vec<f64> = days.iter().flat_map(|(_, v)| v.iter().copied()).collect();
+        let count = mgdl.len();
+        if count < 2 {
+            return None;
         }
 
-        daily_readings.into_iter()
-            .map(|(date, (mgdl, mmol))| DailyStats::new(date, &mgdl, &mmol, thresholds))
-            .collect()
+        let mean_mgdl = mgdl.iter().map(|&v| v as f64).sum::<f64>() / count as f64;
+        let sd_mgdl = calculate_std_dev_u16(&mgdl, mean_mgdl);
+        let mean_mmol = mmol.iter().sum::<f64>() / mmol.len().max(1) as f64;
+        let sd_mmol = calculate_std_dev_f64(&mmol, mean_mmol);
+
+        let risks: Vec<(f64, f64)> = mgdl.iter().map(|&v| bg_risk(v)).collect();
+        let lbgi = risks.iter().map(|r| r.0).sum::<f64>() / count as f64;
+        let hbgi = risks.iter().map(|r| r.1).sum::<f64>() / count as f64;
+
+        let daily_ranges: Vec<f64> = days.iter()
+            .filter(|(values, _)| !values.is_empty())
+            .map(|(values, _)| {
+                let day_risks: Vec<(f64, f64)> = values.iter().map(|&v| bg_risk(v)).collect();
+                let max_low = day_risks.iter().map(|r| r.0).fold(0.0, f64::max);
+                let max_high = day_risks.iter().map(|r| r.1).fold(0.0, f64::max);
+                max_low + max_high
+            })
+            .collect();
+        let adrr = daily_ranges.iter().sum::<f64>() / daily_ranges.len().max(1) as f64;
+
+        // GRI uses fixed consensus ranges, not the user's thresholds
+        let pct = |pred: &dyn Fn(u16) -> bool| mgdl.iter().filter(|&&v| pred(v)).count() as f64 * 100.0 / count as f64;
+        let very_low = pct(&|v| v < 54);
+        let low = pct(&|v| (54..70).contains(&v));
+        let high = pct(&|v| (181..=250).contains(&v));
+        let very_high = pct(&|v| v > 250);
+        let gri = (3.0 * (very_low + 0.8 * low) + 1.6 * (very_high + 0.5 * high)).min(100.0);
+
+        Some(Self {
+            count,
+            cv_percent: if mean_mgdl > 0.0 { sd_mgdl / mean_mgdl * 100.0 } else { 0.0 },
+            mage_mgdl: mage(days.iter().map(|(v, _)| v.as_slice()), sd_mgdl),
+            mage_mmol: mage(days.iter().map(|(_, v)| v.as_slice()), sd_mmol),
+            lbgi,
+            hbgi,
+            adrr,
+            j_index: 0.001 * (mean_mgdl + sd_mgdl).powi(2),
+            gri,
+        })
     }
 
-    fn calculate_hourly<R: ReadingData>(readings: &[R]) -> Vec<HourlyStats> {
-        let mut hourly_data: Vec<(Vec<u16>, Vec<f64>)> = vec![(Vec::new(), Vec::new()); 24];
+    /// Whether the CV is within the 36% stability target
+    pub fn is_stable(&self) -> bool {
+        self.cv_percent <= STABLE_CV_PERCENT
+    }
 
-        for reading in readings {
-            if let Some(hour_str) = reading.timestamp().get(11..13) {
-                if let Ok(hour) = hour_str.parse::<usize>() {
-                    if hour < 24 {
-                        hourly_data[hour].0.push(reading.mg_dl());
-                        hourly_data[hour].1.push(reading.mmol_l());
-                    }
-                }
-            }
+    /// MAGE in the user's preferred unit
+    pub fn mage(&self, unit: GlucoseUnit) -> Option<f64> {
+        match unit {
+            GlucoseUnit::MgDl => self.mage_mgdl,
+            GlucoseUnit::MmolL => self.mage_mmol,
         }
+    }
 
-        hourly_data.into_iter()
-            .enumerate()
-            .map(|(hour, (mgdl, mmol))| HourlyStats::new(hour as u8, mgdl, mmol))
-            .collect()
+    /// GRI zone from A (lowest risk) to E
+    pub fn gri_zone(&self) -> char {
+        match self.gri {
+            g if g < 20.0 => 'A',
+            g if g < 40.0 => 'B',
+            g if g < 60.0 => 'C',
+            g if g < 80.0 => 'D',
+            _ => 'E',
+        }
     }
+}
 
-    fn calculate_time_bins<R: ReadingData>(readings: &[R]) -> Vec<TimeBinStats> {
-        let bins = [
-            ("Overnight", "12AM-6AM", 0u8, 6u8),
-            ("Fasting/Morning", "6AM-9AM", 6, 9),
-            ("Mid-Morning", "9AM-12PM", 9, 12),
-            ("Afternoon", "12PM-6PM", 12, 18),
-            ("Evening", "6PM-9PM", 18, 21),
-            ("Night", "9PM-12AM", 21, 24),
-        ];
+// ============= Estimated A1c =============
 
-        bins.iter().map(|(name, desc, start, end)| {
-            let filtered: Vec<_> = readings.iter()
-                .filter(|r| {
-                    if let Some(hour_str) = r.timestamp().get(11..13) {
-                        if let Ok(hour) = hour_str.parse::<u8>() {
-                            return hour >= *start && hour < *end;
-                        }
-                    }
-                    false
+/// Windows (in days) over which eA1c and GMI are estimated
+pub const A1C_WINDOWS: [u32; 3] = [14, 30, 90];
+
+/// Fewer readings per day than this make an A1c estimate unreliable
+const MIN_A1C_READINGS_PER_DAY: f64 = 3.0;
+/// Share of the window's days that should have at least one reading
+const MIN_A1C_DAY_COVERAGE: f64 = 0.7;
+
+/// Glucose Management Indicator (%) from mean glucose (Bergenstal 2018)
+pub fn gmi_percent(mean_mgdl: f64) -> f64 {
+    3.31 + 0.02392 * mean_mgdl
+}
+
+/// ADAG estimated A1c (%) from mean glucose (Nathan 2008)
+pub fn adag_ea1c_percent(mean_mgdl: f64) -> f64 {
+    (mean_mgdl + 46.7) / 28.7
+}
+
+/// Convert an NGSP A1c percentage to IFCC mmol/mol
+pub fn a1c_percent_to_mmol_mol(percent: f64) -> f64 {
+    (percent - 2.15) * 10.929
+}
+
+/// GMI and eA1c over the last `window_days` days of readings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct A1cEstimate {
+    pub window_days: u32,
+    /// First and last date of the window ("YYYY/MM/DD")
+    pub start_date: String,
+    pub end_date: String,
+    pub readings: usize,
+    pub days_with_readings: usize,
+    pub mean_mgdl: f64,
+    pub gmi_percent: f64,
+    pub gmi_mmol_mol: f64,
+    pub ea1c_percent: f64,
+    pub ea1c_mmol_mol: f64,
+}
+
+impl A1cEstimate {
+    /// Estimate over the `window_days` days ending on the date of the latest reading
+    ///
+    /// `readings` must be sorted by time. Returns `None` if there are no readings.
+    pub fn from_readings<R: ReadingData>(readings: &[R], window_days: u32) -> Option<Self> {
+        let last = readings.last()?.timestamp().get(0..10)?;
+        let end = chrono::NaiveDate::parse_from_str(last, "%Y/%m/%d").ok()?;
+        let start = end - chrono::Duration::days(window_days.saturating_sub(1) as i64);
+        let start_date = start.format("%Y/%m/%d").to_string();
+
+        let in_window: Vec<&R> = readings.iter()
+            .filter(|r| r.timestamp().get(0..10).is_some_and(|d| d >= start_date.as_str()))
+            .collect();
+        let mut days: Vec<&str> = in_window.iter().filter_map(|r| r.timestamp().get(0..10)).collect();
+        days.dedup();
+
+        let mean_mgdl = in_window.iter().map(|r| r.mg_dl() as f64).sum::<f64>() / in_window.len() as f64;
+        let gmi = gmi_percent(mean_mgdl);
+        let ea1c = adag_ea1c_percent(mean_mgdl);
+
+        Some(Self {
+            window_days,
+            start_date,
+            end_date: last.to_string(),
+            readings: in_window.len(),
+            days_with_readings: days.len(),
+            mean_mgdl,
+            gmi_percent: gmi,
+            gmi_mmol_mol: a1c_percent_to_mmol_mol(gmi),
+            ea1c_percent: ea1c,
+            ea1c_mmol_mol: a1c_percent_to_mmol_mol(ea1c),
+        })
+    }
+
+    pub fn readings_per_day(&self) -> f64 {
+        self.readings as f64 / self.window_days.max(1) as f64
+    }
+
+    fn day_coverage(&self) -> f64 {
+        self.days_with_readings as f64 / self.window_days.max(1) as f64
+    }
+
+    /// Whether there are too few readings for the estimate to mean much
+    pub fn low_density(&self) -> bool {
+        self.readings_per_day() < MIN_A1C_READINGS_PER_DAY || self.day_coverage() < MIN_A1C_DAY_COVERAGE
+    }
+
+    /// Why the estimate should be read with caution, if it should
+    pub fn density_warning(&self) -> Option<String> {
+        if self.readings_per_day() < MIN_A1C_READINGS_PER_DAY {
+            Some(format!(
+                "Only {:.1} readings/day (at least {:.0} recommended); estimate may be unreliable",
+                self.readings_per_day(), MIN_A1C_READINGS_PER_DAY
+            ))
+        } else if self.day_coverage() < MIN_A1C_DAY_COVERAGE {
+            Some(format!(
+                "Readings on only {} of {} days; estimate may be unreliable",
+                self.days_with_readings, self.window_days
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// ============= Ambulatory Glucose Profile =============
+
+/// Bin widths (minutes) the AGP modal day can be drawn with
+pub const AGP_BIN_MINUTES: [u16; 2] = [15, 60];
+
+/// One slot of the AGP modal day, pooling readings from every day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgpBin {
+    /// Minutes after midnight the bin starts at
+    pub start_minute: u16,
+    pub count: usize,
+    pub stats: Option<BasicStats>,
+}
+
+impl AgpBin {
+    /// Percentile band of the bin in user's preferred unit
+    pub fn band(&self, unit: GlucoseUnit) -> Option<PercentileBand> {
+        self.stats.as_ref().map(|s| s.band(unit))
+    }
+}
+
+/// A consensus target for the share of readings in one range
+#[derive(Debug, Clone)]
+pub struct AgpRangeTarget {
+    pub range: GlucoseRange,
+    /// Share of readings this target applies to (%)
+    pub percent: f64,
+    /// e.g. "<4%" or ">70%"
+    pub target: &'static str,
+    pub met: bool,
+}
+
+impl AgpRangeTarget {
+    /// Range name with its consensus bounds, e.g. "Low (<70 mg/dL)"
+    pub fn label(&self, unit: GlucoseUnit) -> String {
+        let (mgdl, mmol) = match self.range {
+            GlucoseRange::VeryHigh => (">250", ">13.9"),
+            GlucoseRange::High => (">180", ">10.0"),
+            GlucoseRange::InRange => ("70-180", "3.9-10.0"),
+            GlucoseRange::Low => ("<70", "<3.9"),
+            GlucoseRange::VeryLow => ("<54", "<3.0"),
+        };
+        let bounds = match unit {
+            GlucoseUnit::MgDl => mgdl,
+            GlucoseUnit::MmolL => mmol,
+        };
+        format!("{} ({} {})", self.range.label(), bounds, unit.label())
+    }
+}
+
+/// Headline metrics in the international consensus layout (Battelino 2019)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgpMetrics {
+    /// First and last date of the report ("YYYY/MM/DD")
+    pub start_date: String,
+    pub end_date: String,
+    pub days: usize,
+    pub days_with_readings: usize,
+    pub readings: usize,
+    pub mean_mgdl: f64,
+    pub mean_mmol: f64,
+    pub gmi_percent: f64,
+    pub cv_percent: f64,
+    /// Time in the consensus ranges (54/70/180/250 mg/dL), not the user's thresholds
+    pub tir: TimeInRange,
+}
+
+impl AgpMetrics {
+    pub fn readings_per_day(&self) -> f64 {
+        self.readings as f64 / self.days.max(1) as f64
+    }
+
+    pub fn mean(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mean_mgdl,
+            GlucoseUnit::MmolL => self.mean_mmol,
+        }
+    }
+
+    /// Each consensus range target, from very high to very low
+    ///
+    /// The high and low targets include the very high and very low readings.
+    pub fn range_targets(&self) -> Vec<AgpRangeTarget> {
+        let pct = |range| self.tir.percentage(range);
+        let high = pct(GlucoseRange::High) + pct(GlucoseRange::VeryHigh);
+        let low = pct(GlucoseRange::Low) + pct(GlucoseRange::VeryLow);
+        let target = |range, percent: f64, target, met| AgpRangeTarget { range, percent, target, met };
+        vec![
+            target(GlucoseRange::VeryHigh, pct(GlucoseRange::VeryHigh), "<5%", pct(GlucoseRange::VeryHigh) < 5.0),
+            target(GlucoseRange::High, high, "<25%", high < 25.0),
+            target(GlucoseRange::InRange, pct(GlucoseRange::InRange), ">70%", pct(GlucoseRange::InRange) > 70.0),
+            target(GlucoseRange::Low, low, "<4%", low < 4.0),
+            target(GlucoseRange::VeryLow, pct(GlucoseRange::VeryLow), "<1%", pct(GlucoseRange::VeryLow) < 1.0),
+        ]
+    }
+}
+
+/// AGP-style modal day: percentile bands over a 24h clock plus headline metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgpReport {
+    pub bin_minutes: u16,
+    pub bins: Vec<AgpBin>,
+    /// `None` when there are no readings
+    pub metrics: Option<AgpMetrics>,
+}
+
+impl AgpReport {
+    /// Build the profile from readings sorted by time and their [`HourlyStats`]
+    ///
+    /// `bin_minutes` is clamped to 15..=60. 60-minute bins are the hourly stats
+    /// themselves; shorter bins split the hours, so the readings are binned again.
+    pub fn new<R: ReadingData>(readings: &[R], hourly: &[HourlyStats], bin_minutes: u16) -> Self {
+        let bin_minutes = bin_minutes.clamp(15, 60);
+        let bins = if bin_minutes == 60 {
+            hourly.iter()
+                .map(|h| AgpBin { start_minute: h.hour as u16 * 60, count: h.count(), stats: h.stats.clone() })
+                .collect()
+        } else {
+            bin_by_minute(readings, bin_minutes).into_iter()
+                .enumerate()
+                .map(|(i, (mgdl, mmol))| AgpBin {
+                    start_minute: i as u16 * bin_minutes,
+                    count: mgdl.len(),
+                    stats: BasicStats::from_values(&mgdl, &mmol),
                 })
-                .collect();
-            let mgdl: Vec<u16> = filtered.iter().map(|r| r.mg_dl()).collect();
-            let mmol: Vec<f64> = filtered.iter().map(|r| r.mmol_l()).collect();
-            TimeBinStats::new(name, desc, *start, *end, mgdl, mmol)
-        }).collect()
+                .collect()
+        };
+
+        Self { bin_minutes, bins, metrics: Self::calculate_metrics(readings) }
     }
 
-    fn calculate_histogram(values: &[u16]) -> Vec<HistogramBin> {
-        let bin_width = 20u16;
-        let mut bins: Vec<HistogramBin> = Vec::new();
-        let mut start = 40u16;
-        let total = values.len();
+    /// Build the profile from readings sorted by time
+    pub fn from_readings<R: ReadingData>(readings: &[R], bin_minutes: u16) -> Self {
+        Self::new(readings, &Analysis::calculate_hourly(readings), bin_minutes)
+    }
 
-        while start < 400 {
-            let end = start + bin_width;
-            let count = values.iter().filter(|&&v| v >= start && v < end).count();
-            bins.push(HistogramBin {
-                range_start: start,
-                range_end: end,
-                count,
-                percentage: if total > 0 { (count as f64 / total as f64) * 100.0 } else { 0.0 },
-            });
-            start = end;
+    fn calculate_metrics<R: ReadingData>(readings: &[R]) -> Option<AgpMetrics> {
+        let start_date = readings.first()?.timestamp().get(0..10)?.to_string();
+        let end_date = readings.last()?.timestamp().get(0..10)?.to_string();
+        let parse = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y/%m/%d").ok();
+        let days = (parse(&end_date)? - parse(&start_date)?).num_days() as usize + 1;
+
+        let mgdl: Vec<u16> = readings.iter().map(|r| r.mg_dl()).collect();
+        let mmol: Vec<f64> = readings.iter().map(|r| r.mmol_l()).collect();
+        let stats = BasicStats::from_values(&mgdl, &mmol)?;
+        let mut dates: Vec<&str> = readings.iter().filter_map(|r| r.timestamp().get(0..10)).collect();
+        dates.dedup();
+
+        Some(AgpMetrics {
+            start_date,
+            end_date,
+            days,
+            days_with_readings: dates.len(),
+            readings: readings.len(),
+            mean_mgdl: stats.mgdl.mean,
+            mean_mmol: stats.mmol.mean,
+            gmi_percent: gmi_percent(stats.mgdl.mean),
+            cv_percent: if stats.mgdl.mean > 0.0 { stats.mgdl.std_dev / stats.mgdl.mean * 100.0 } else { 0.0 },
+            tir: TimeInRange::from_values(&mgdl, Thresholds::default()),
+        })
+    }
+}
+
+// ============= Episodes =============
+
+/// Readings further apart than this belong to separate episodes
+const MAX_EPISODE_GAP_MINUTES: i64 = 180;
+/// Episodes starting before this minute of the day are nocturnal (00:00-06:00)
+const NOCTURNAL_END_MINUTE: u16 = 6 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EpisodeKind {
+    Hypo,
+    Hyper,
+}
+
+impl EpisodeKind {
+    fn of(range: GlucoseRange) -> Option<Self> {
+        match range {
+            GlucoseRange::VeryLow | GlucoseRange::Low => Some(EpisodeKind::Hypo),
+            GlucoseRange::High | GlucoseRange::VeryHigh => Some(EpisodeKind::Hyper),
+            GlucoseRange::InRange => None,
         }
+    }
 
-        bins
+    pub fn label(self) -> &'static str {
+        match self {
+            EpisodeKind::Hypo => "Hypo",
+            EpisodeKind::Hyper => "Hyper",
+        }
     }
 }
 
-/// Trait for reading data access (both units)
-pub trait ReadingData {
-    fn mg_dl(&self) -> u16;
-    fn mmol_l(&self) -> f64;
-    fn timestamp(&self) -> &str;
+/// Consecutive readings below or above the target range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Episode {
+    pub kind: EpisodeKind,
+    /// Whether any reading was very low (<54 mg/dL) or very high (>250 mg/dL)
+    pub severe: bool,
+    /// Timestamps of the first and last reading
+    pub start: String,
+    pub end: String,
+    pub readings: usize,
+    /// Nadir of a hypo, peak of a hyper
+    pub extreme_mgdl: u16,
+    pub extreme_mmol: f64,
+    pub extreme_time: String,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Episode {
+    /// Minutes from the first to the last reading
+    pub fn duration_minutes(&self) -> i64 {
+        match (parse_timestamp(&self.start), parse_timestamp(&self.end)) {
+            (Some(start), Some(end)) => (end - start).num_minutes(),
+            _ => 0,
+        }
+    }
 
-    #[test]
-    fn test_basic_stats() {
-        let mgdl_values = vec![100, 120, 140, 160, 180];
-        let mmol_values = vec![5.6, 6.7, 7.8, 8.9, 10.0];
-        let stats = BasicStats::from_values(&mgdl_values, &mmol_values).unwrap();
-        
-        // mg/dL stats
-        assert_eq!(stats.mgdl.count, 5);
-        assert!((stats.mgdl.mean - 140.0).abs() < 0.01);
-        assert_eq!(stats.mgdl.min, 100);
-        assert_eq!(stats.mgdl.max, 180);
-        
-        // mmol/L stats
-        assert_eq!(stats.mmol.count, 5);
-        assert!((stats.mmol.mean - 7.8).abs() < 0.01);
-        assert!((stats.mmol.min - 5.6).abs() < 0.01);
-        assert!((stats.mmol.max - 10.0).abs() < 0.01);
+    /// Whether the episode started between midnight and 06:00
+    pub fn is_nocturnal(&self) -> bool {
+        minute_of_day(&self.start).is_some_and(|m| m < NOCTURNAL_END_MINUTE)
     }
+}
 
-    #[test]
-    fn test_time_in_range() {
-        let values = vec![50, 65, 100, 150, 200, 300];
-        let tir = TimeInRange::from_values(&values, Thresholds::default());
-        assert_eq!(tir.very_low, 1);
-        assert_eq!(tir.low, 1);
-        assert_eq!(tir.in_range, 2);
-        assert_eq!(tir.high, 1);
-        assert_eq!(tir.very_high, 1);
+pub fn parse_timestamp(timestamp: &str) -> Option<chrono::NaiveDateTime> {
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y/%m/%d %H:%M").ok()
+}
+
+/// Episode counts in one ISO week
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyEpisodes {
+    /// Monday of the week ("YYYY/MM/DD")
+    pub week_start: String,
+    pub hypo: usize,
+    pub hyper: usize,
+}
+
+/// Every episode in the readings with counts per week
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeReport {
+    pub episodes: Vec<Episode>,
+    /// Weeks from the first to the last reading, at least one day's worth
+    pub weeks: f64,
+    /// Weeks with readings, in date order
+    pub weekly: Vec<WeeklyEpisodes>,
+}
+
+impl EpisodeReport {
+    /// Detect episodes in readings sorted by time
+    pub fn from_readings<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Self {
+        let mut episodes: Vec<Episode> = Vec::new();
+        let mut previous: Option<(EpisodeKind, &str)> = None;
+
+        for reading in readings {
+            let Some(kind) = EpisodeKind::of(thresholds.classify(reading.mg_dl())) else {
+                previous = None;
+                continue;
+            };
+            let timestamp = reading.timestamp();
+            let continues = previous.is_some_and(|(previous_kind, previous_time)| {
+                previous_kind == kind && match (parse_timestamp(previous_time), parse_timestamp(timestamp)) {
+                    (Some(a), Some(b)) => (b - a).num_minutes() <= MAX_EPISODE_GAP_MINUTES,
+                    _ => false,
+                }
+            });
+            let severe = matches!(thresholds.classify(reading.mg_dl()), GlucoseRange::VeryLow | GlucoseRange::VeryHigh);
+
+            match episodes.last_mut().filter(|_| continues) {
+                Some(episode) => {
+                    episode.end = timestamp.to_string();
+                    episode.readings += 1;
+                    episode.severe |= severe;
+                    let more_extreme = match kind {
+                        EpisodeKind::Hypo => reading.mg_dl() < episode.extreme_mgdl,
+                        EpisodeKind::Hyper => reading.mg_dl() > episode.extreme_mgdl,
+                    };
+                    if more_extreme {
+                        episode.extreme_mgdl = reading.mg_dl();
+                        episode.extreme_mmol = reading.mmol_l();
+                        episode.extreme_time = timestamp.to_string();
+                    }
+                }
+                None => episodes.push(Episode {
+                    kind,
+                    severe,
+                    start: timestamp.to_string(),
+                    end: timestamp.to_string(),
+                    readings: 1,
+                    extreme_mgdl: reading.mg_dl(),
+                    extreme_mmol: reading.mmol_l(),
+                    extreme_time: timestamp.to_string(),
+                }),
+            }
+            previous = Some((kind, timestamp));
+        }
+
+        let weeks = readings.first().zip(readings.last())
+            .and_then(|(first, last)| {
+                let days = (parse_timestamp(last.timestamp())?.date() - parse_timestamp(first.timestamp())?.date()).num_days() + 1;
+                Some(days as f64 / 7.0)
+            })
+            .unwrap_or(0.0);
+
+        Self { weekly: Self::count_weekly(readings, &episodes), episodes, weeks }
+    }
+
+    fn count_weekly<R: ReadingData>(readings: &[R], episodes: &[Episode]) -> Vec<WeeklyEpisodes> {
+        use chrono::Datelike;
+        let week_start = |timestamp: &str| {
+            parse_timestamp(timestamp).map(|t| {
+                let monday = t.date() - chrono::Duration::days(t.weekday().num_days_from_monday() as i64);
+                monday.format("%Y/%m/%d").to_string()
+            })
+        };
+
+        let mut weeks: std::collections::BTreeMap<String, WeeklyEpisodes> = std::collections::BTreeMap::new();
+        for week in readings.iter().filter_map(|r| week_start(r.timestamp())) {
+            weeks.entry(week.clone()).or_insert(WeeklyEpisodes { week_start: week, hypo: 0, hyper: 0 });
+        }
+        for episode in episodes {
+            if let Some(week) = week_start(&episode.start).and_then(|w| weeks.get_mut(&w)) {
+                match episode.kind {
+                    EpisodeKind::Hypo => week.hypo += 1,
+                    EpisodeKind::Hyper => week.hyper += 1,
+                }
+            }
+        }
+        weeks.into_values().collect()
+    }
+
+    pub fn count(&self, kind: EpisodeKind) -> usize {
+        self.episodes.iter().filter(|e| e.kind == kind).count()
+    }
+
+    pub fn severe_count(&self, kind: EpisodeKind) -> usize {
+        self.episodes.iter().filter(|e| e.kind == kind && e.severe).count()
+    }
+
+    pub fn nocturnal_hypo_count(&self) -> usize {
+        self.episodes.iter().filter(|e| e.kind == EpisodeKind::Hypo && e.is_nocturnal()).count()
+    }
+
+    fn per_week(&self, count: usize) -> f64 {
+        if self.weeks > 0.0 { count as f64 / self.weeks } else { 0.0 }
+    }
+
+    pub fn count_per_week(&self, kind: EpisodeKind) -> f64 {
+        self.per_week(self.count(kind))
+    }
+
+    pub fn nocturnal_hypo_per_week(&self) -> f64 {
+        self.per_week(self.nocturnal_hypo_count())
+    }
+}
+
+// ============= Meal Pairs =============
+
+/// Tags the quick-tag buttons and the default tag rules put on readings around a meal
+pub const BEFORE_MEAL_TAG: &str = "before_meal";
+pub const AFTER_MEAL_TAG: &str = "after_meal";
+/// Pairing windows offered in the GUI, in minutes
+pub const MEAL_PAIR_WINDOWS: [u16; 4] = [60, 120, 180, 240];
+/// Rise after a meal above which the excursion counts as large (mg/dL)
+pub const LARGE_EXCURSION_MGDL: i32 = 60;
+
+/// A before_meal reading and the after_meal reading that followed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MealPair {
+    pub before_time: String,
+    pub after_time: String,
+    pub before_mgdl: u16,
+    pub before_mmol: f64,
+    pub after_mgdl: u16,
+    pub after_mmol: f64,
+    /// First time bin the before_meal reading falls in
+    pub time_bin: Option<String>,
+}
+
+impl MealPair {
+    /// Minutes from the before_meal to the after_meal reading
+    pub fn minutes_apart(&self) -> i64 {
+        match (parse_timestamp(&self.before_time), parse_timestamp(&self.after_time)) {
+            (Some(before), Some(after)) => (after - before).num_minutes(),
+            _ => 0,
+        }
+    }
+
+    /// Postprandial excursion: after minus before, negative when glucose fell
+    pub fn excursion_mgdl(&self) -> i32 {
+        self.after_mgdl as i32 - self.before_mgdl as i32
+    }
+
+    pub fn excursion_mmol(&self) -> f64 {
+        self.after_mmol - self.before_mmol
+    }
+
+    pub fn excursion(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.excursion_mgdl() as f64,
+            GlucoseUnit::MmolL => self.excursion_mmol(),
+        }
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.excursion_mgdl() > LARGE_EXCURSION_MGDL
+    }
+
+    fn weekday(&self) -> Option<chrono::Weekday> {
+        use chrono::Datelike;
+        parse_timestamp(&self.before_time).map(|t| t.weekday())
+    }
+}
+
+/// Excursions of a group of meal pairs (one time bin, one weekday, or all)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExcursionSummary {
+    pub label: String,
+    pub pairs: usize,
+    pub mean_before_mgdl: f64,
+    pub mean_before_mmol: f64,
+    pub mean_after_mgdl: f64,
+    pub mean_after_mmol: f64,
+    pub mean_excursion_mgdl: f64,
+    pub mean_excursion_mmol: f64,
+    pub max_excursion_mgdl: i32,
+    pub max_excursion_mmol: f64,
+    /// Pairs rising more than [`LARGE_EXCURSION_MGDL`]
+    pub large: usize,
+}
+
+impl ExcursionSummary {
+    fn from_pairs(label: &str, pairs: &[&MealPair]) -> Option<Self> {
+        if pairs.is_empty() {
+            return None;
+        }
+        let mean = |value: &dyn Fn(&MealPair) -> f64| pairs.iter().map(|p| value(p)).sum::<f64>() / pairs.len() as f64;
+        let max = pairs.iter().max_by_key(|p| p.excursion_mgdl())?;
+        Some(Self {
+            label: label.to_string(),
+            pairs: pairs.len(),
+            mean_before_mgdl: mean(&|p| p.before_mgdl as f64),
+            mean_before_mmol: mean(&|p| p.before_mmol),
+            mean_after_mgdl: mean(&|p| p.after_mgdl as f64),
+            mean_after_mmol: mean(&|p| p.after_mmol),
+            mean_excursion_mgdl: mean(&|p| p.excursion_mgdl() as f64),
+            mean_excursion_mmol: mean(&|p| p.excursion_mmol()),
+            max_excursion_mgdl: max.excursion_mgdl(),
+            max_excursion_mmol: max.excursion_mmol(),
+            large: pairs.iter().filter(|p| p.is_large()).count(),
+        })
+    }
+
+    pub fn mean_before(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mean_before_mgdl,
+            GlucoseUnit::MmolL => self.mean_before_mmol,
+        }
+    }
+
+    pub fn mean_after(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mean_after_mgdl,
+            GlucoseUnit::MmolL => self.mean_after_mmol,
+        }
+    }
+
+    pub fn mean_excursion(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mean_excursion_mgdl,
+            GlucoseUnit::MmolL => self.mean_excursion_mmol,
+        }
+    }
+
+    pub fn max_excursion(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.max_excursion_mgdl as f64,
+            GlucoseUnit::MmolL => self.max_excursion_mmol,
+        }
+    }
+}
+
+/// Before/after meal pairs with their excursions per time bin and weekday
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MealPairReport {
+    /// Longest time from a before_meal to its after_meal reading
+    pub window_minutes: u16,
+    pub pairs: Vec<MealPair>,
+    pub overall: Option<ExcursionSummary>,
+    /// Time bins with pairs, in bin order
+    pub by_time_bin: Vec<ExcursionSummary>,
+    /// Weekdays with pairs, Monday first
+    pub by_weekday: Vec<ExcursionSummary>,
+    /// Tagged readings left without a partner
+    pub unpaired_before: usize,
+    pub unpaired_after: usize,
+}
+
+impl MealPairReport {
+    /// Pair each before_meal reading with the next after_meal reading up to
+    /// `window_minutes` later; a newer before_meal reading replaces a waiting one.
+    /// `readings` must be sorted by time.
+    pub fn from_readings<R: ReadingData>(readings: &[R], time_bins: &[TimeBin], window_minutes: u16) -> Self {
+        let mut pairs = Vec::new();
+        let mut waiting: Option<&R> = None;
+        let (mut unpaired_before, mut unpaired_after) = (0, 0);
+
+        for reading in readings {
+            let before = has_tag(reading, BEFORE_MEAL_TAG);
+            let after = has_tag(reading, AFTER_MEAL_TAG);
+            if before == after {
+                continue;
+            }
+            if before {
+                unpaired_before += waiting.is_some() as usize;
+                waiting = Some(reading);
+                continue;
+            }
+
+            let Some(start) = waiting.take() else {
+                unpaired_after += 1;
+                continue;
+            };
+            let minutes = match (parse_timestamp(start.timestamp()), parse_timestamp(reading.timestamp())) {
+                (Some(a), Some(b)) => (b - a).num_minutes(),
+                _ => -1,
+            };
+            if !(0..=window_minutes as i64).contains(&minutes) {
+                unpaired_before += 1;
+                unpaired_after += 1;
+                continue;
+            }
+            pairs.push(MealPair {
+                before_time: start.timestamp().to_string(),
+                after_time: reading.timestamp().to_string(),
+                before_mgdl: start.mg_dl(),
+                before_mmol: start.mmol_l(),
+                after_mgdl: reading.mg_dl(),
+                after_mmol: reading.mmol_l(),
+                time_bin: time_bins.iter().find(|b| b.contains(start.timestamp())).map(|b| b.name.clone()),
+            });
+        }
+        unpaired_before += waiting.is_some() as usize;
+
+        let all: Vec<&MealPair> = pairs.iter().collect();
+        let by_time_bin = time_bins.iter()
+            .filter_map(|bin| {
+                let in_bin: Vec<&MealPair> = pairs.iter().filter(|p| p.time_bin.as_deref() == Some(bin.name.as_str())).collect();
+                ExcursionSummary::from_pairs(&bin.name, &in_bin)
+            })
+            .collect();
+        let by_weekday = (0..7)
+            .map(chrono::Weekday::try_from)
+            .filter_map(|day| {
+                let day = day.ok()?;
+                let on_day: Vec<&MealPair> = pairs.iter().filter(|p| p.weekday() == Some(day)).collect();
+                ExcursionSummary::from_pairs(&day.to_string(), &on_day)
+            })
+            .collect();
+
+        Self {
+            window_minutes,
+            overall: ExcursionSummary::from_pairs("All meals", &all),
+            by_time_bin,
+            by_weekday,
+            pairs,
+            unpaired_before,
+            unpaired_after,
+        }
+    }
+}
+
+// ============= Trend =============
+
+/// Longest gap between two readings for their rate of change to be meaningful
+pub const MAX_RATE_GAP_MINUTES: i64 = 120;
+/// Rates at or above this many mg/dL per minute count as rapid rises or falls
+pub const RAPID_RATE_MGDL_PER_MIN: f64 = 2.0;
+/// Trailing windows of the rolling mean and TIR, in days
+pub const ROLLING_WINDOWS: [u32; 3] = [7, 14, 30];
+
+/// How fast glucose moved between two consecutive readings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateOfChange {
+    pub from: String,
+    pub to: String,
+    pub minutes: i64,
+    pub mgdl_per_min: f64,
+    pub mmol_per_min: f64,
+}
+
+impl RateOfChange {
+    pub fn rate(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mgdl_per_min,
+            GlucoseUnit::MmolL => self.mmol_per_min,
+        }
+    }
+
+    pub fn is_rapid(&self) -> bool {
+        self.mgdl_per_min.abs() >= RAPID_RATE_MGDL_PER_MIN
+    }
+}
+
+/// Mean and TIR of the readings in the days up to and including `date`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingPoint {
+    /// "YYYY/MM/DD"
+    pub date: String,
+    pub count: usize,
+    pub mean_mgdl: f64,
+    pub mean_mmol: f64,
+    pub tir_percent: f64,
+}
+
+impl RollingPoint {
+    pub fn mean(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.mean_mgdl,
+            GlucoseUnit::MmolL => self.mean_mmol,
+        }
+    }
+}
+
+/// One rolling window over every day from the first to the last reading
+///
+/// The first points average fewer days than the window, as there are no earlier readings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollingSeries {
+    pub window_days: u32,
+    pub points: Vec<RollingPoint>,
+}
+
+/// Least-squares line through all readings against time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendLine {
+    pub start_epoch: i64,
+    pub end_epoch: i64,
+    /// Fitted value at `start_epoch`
+    pub start_mgdl: f64,
+    pub start_mmol: f64,
+    pub slope_mgdl_per_day: f64,
+    pub slope_mmol_per_day: f64,
+    /// Share of the variance explained by the line (0-1)
+    pub r_squared: f64,
+}
+
+impl TrendLine {
+    /// Least-squares fit of (epoch, mg/dL, mmol/L) points; `None` with fewer than two distinct times
+    fn fit(points: &[(i64, f64, f64)]) -> Option<Self> {
+        let (start_epoch, end_epoch) = (points.first()?.0, points.last()?.0);
+        if end_epoch == start_epoch {
+            return None;
+        }
+        let n = points.len() as f64;
+        let days: Vec<f64> = points.iter().map(|p| (p.0 - start_epoch) as f64 / 86400.0).collect();
+        let mean_x = days.iter().sum::<f64>() / n;
+        let sxx: f64 = days.iter().map(|x| (x - mean_x).powi(2)).sum();
+
+        // Slope and intercept of one unit's values
+        let line = |values: &[f64]| {
+            let mean_y = values.iter().sum::<f64>() / n;
+            let sxy: f64 = days.iter().zip(values).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+            let syy: f64 = values.iter().map(|y| (y - mean_y).powi(2)).sum();
+            let slope = sxy / sxx;
+            let r_squared = if syy == 0.0 { 0.0 } else { sxy * sxy / (sxx * syy) };
+            (mean_y - slope * mean_x, slope, r_squared)
+        };
+        let mgdl: Vec<f64> = points.iter().map(|p| p.1).collect();
+        let mmol: Vec<f64> = points.iter().map(|p| p.2).collect();
+        let (start_mgdl, slope_mgdl_per_day, r_squared) = line(&mgdl);
+        let (start_mmol, slope_mmol_per_day, _) = line(&mmol);
+
+        Some(Self { start_epoch, end_epoch, start_mgdl, start_mmol, slope_mgdl_per_day, slope_mmol_per_day, r_squared })
+    }
+
+    pub fn slope_per_week(&self, unit: GlucoseUnit) -> f64 {
+        match unit {
+            GlucoseUnit::MgDl => self.slope_mgdl_per_day * 7.0,
+            GlucoseUnit::MmolL => self.slope_mmol_per_day * 7.0,
+        }
+    }
+
+    /// Fitted value at an epoch
+    pub fn value_at(&self, epoch: i64, unit: GlucoseUnit) -> f64 {
+        let days = (epoch - self.start_epoch) as f64 / 86400.0;
+        match unit {
+            GlucoseUnit::MgDl => self.start_mgdl + self.slope_mgdl_per_day * days,
+            GlucoseUnit::MmolL => self.start_mmol + self.slope_mmol_per_day * days,
+        }
+    }
+}
+
+/// Rate of change, rolling averages and the overall regression trend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendReport {
+    /// Consecutive readings at most [`MAX_RATE_GAP_MINUTES`] apart, in time order
+    pub rates: Vec<RateOfChange>,
+    /// One series per window in [`ROLLING_WINDOWS`]
+    pub rolling: Vec<RollingSeries>,
+    pub regression: Option<TrendLine>,
+}
+
+impl TrendReport {
+    /// Build the report from readings sorted by time
+    pub fn from_readings<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Self {
+        let timed: Vec<(chrono::NaiveDateTime, &R)> = readings.iter()
+            .filter_map(|r| Some((parse_timestamp(r.timestamp())?, r)))
+            .collect();
+
+        let rates = timed.windows(2)
+            .filter_map(|pair| {
+                let ((from_time, from), (to_time, to)) = (pair[0], pair[1]);
+                let minutes = (to_time - from_time).num_minutes();
+                if minutes <= 0 || minutes > MAX_RATE_GAP_MINUTES {
+                    return None;
+                }
+                Some(RateOfChange {
+                    from: from.timestamp().to_string(),
+                    to: to.timestamp().to_string(),
+                    minutes,
+                    mgdl_per_min: (to.mg_dl() as f64 - from.mg_dl() as f64) / minutes as f64,
+                    mmol_per_min: (to.mmol_l() - from.mmol_l()) / minutes as f64,
+                })
+            })
+            .collect();
+
+        let rolling = ROLLING_WINDOWS.iter()
+            .map(|&window_days| RollingSeries { window_days, points: Self::rolling(&timed, window_days, thresholds) })
+            .collect();
+
+        let points: Vec<(i64, f64, f64)> = timed.iter()
+            .map(|(time, r)| (time.and_utc().timestamp(), r.mg_dl() as f64, r.mmol_l()))
+            .collect();
+
+        Self { rates, rolling, regression: TrendLine::fit(&points) }
+    }
+
+    /// Trailing mean and TIR for every day from the first to the last reading
+    fn rolling<R: ReadingData>(timed: &[(chrono::NaiveDateTime, &R)], window_days: u32, thresholds: Thresholds) -> Vec<RollingPoint> {
+        let (Some(first), Some(last)) = (timed.first(), timed.last()) else {
+            return Vec::new();
+        };
+        let mut points = Vec::new();
+        let (mut start, mut end) = (0, 0);
+        for date in first.0.date().iter_days().take_while(|d| *d <= last.0.date()) {
+            let window_start = date - chrono::Duration::days(window_days as i64 - 1);
+            while end < timed.len() && timed[end].0.date() <= date {
+                end += 1;
+            }
+            while start < end && timed[start].0.date() < window_start {
+                start += 1;
+            }
+            let window = &timed[start..end];
+            if window.is_empty() {
+                continue;
+            }
+            let mgdl: Vec<u16> = window.iter().map(|(_, r)| r.mg_dl()).collect();
+            points.push(RollingPoint {
+                date: date.format("%Y/%m/%d").to_string(),
+                count: window.len(),
+                mean_mgdl: mgdl.iter().map(|&v| v as f64).sum::<f64>() / window.len() as f64,
+                mean_mmol: window.iter().map(|(_, r)| r.mmol_l()).sum::<f64>() / window.len() as f64,
+                tir_percent: TimeInRange::from_values(&mgdl, thresholds).in_range_percent(),
+            });
+        }
+        points
+    }
+
+    pub fn fastest_rise(&self) -> Option<&RateOfChange> {
+        self.rates.iter().filter(|r| r.mgdl_per_min > 0.0).max_by(|a, b| a.mgdl_per_min.total_cmp(&b.mgdl_per_min))
+    }
+
+    pub fn fastest_fall(&self) -> Option<&RateOfChange> {
+        self.rates.iter().filter(|r| r.mgdl_per_min < 0.0).min_by(|a, b| a.mgdl_per_min.total_cmp(&b.mgdl_per_min))
+    }
+
+    pub fn rapid_count(&self) -> usize {
+        self.rates.iter().filter(|r| r.is_rapid()).count()
+    }
+}
+
+// ============= Testing Adherence =============
+
+/// Tests per day aimed for until the user sets their own target
+pub const DEFAULT_TESTS_PER_DAY: u32 = 4;
+/// Gaps between tests at least this long are flagged
+pub const LONG_GAP_HOURS: f64 = 24.0;
+
+/// Time between two consecutive tests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestGap {
+    /// Timestamps of the tests before and after the gap
+    pub from: String,
+    pub to: String,
+    pub hours: f64,
+}
+
+/// How often one time bin was tested
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinAdherence {
+    pub name: String,
+    pub description: String,
+    pub tests: usize,
+    /// Days with at least one test in the bin
+    pub days_tested: usize,
+}
+
+/// Testing frequency from reading timestamps, measured against a tests-per-day target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Adherence {
+    pub target_per_day: u32,
+    /// Calendar days from the first to the last test
+    pub days: usize,
+    pub tests: usize,
+    /// Dates ("YYYY/MM/DD") without a single test
+    pub zero_test_days: Vec<String>,
+    /// Days with at least `target_per_day` tests
+    pub days_on_target: usize,
+    /// Consecutive days on target up to the last day with tests
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    pub by_time_bin: Vec<BinAdherence>,
+    pub longest_gap: Option<TestGap>,
+    /// Gaps of at least [`LONG_GAP_HOURS`], in date order
+    pub long_gaps: Vec<TestGap>,
+}
+
+impl Adherence {
+    /// Measure testing frequency of readings sorted by time; `None` without readings
+    pub fn from_readings<R: ReadingData>(readings: &[R], time_bins: &[TimeBin], target_per_day: u32) -> Option<Self> {
+        let times: Vec<chrono::NaiveDateTime> = readings.iter().filter_map(|r| parse_timestamp(r.timestamp())).collect();
+        let (first, last) = (times.first()?.date(), times.last()?.date());
+
+        let mut per_day: std::collections::BTreeMap<chrono::NaiveDate, usize> = std::collections::BTreeMap::new();
+        for time in &times {
+            *per_day.entry(time.date()).or_default() += 1;
+        }
+
+        let (mut zero_test_days, mut days_on_target) = (Vec::new(), 0);
+        let (mut streak, mut longest_streak) = (0, 0);
+        for date in first.iter_days().take_while(|d| *d <= last) {
+            let tests = per_day.get(&date).copied().unwrap_or(0);
+            if tests == 0 {
+                zero_test_days.push(date.format("%Y/%m/%d").to_string());
+            }
+            if tests >= target_per_day as usize {
+                days_on_target += 1;
+                streak += 1;
+                longest_streak = longest_streak.max(streak);
+            } else {
+                streak = 0;
+            }
+        }
+
+        let gaps: Vec<TestGap> = readings.windows(2)
+            .filter_map(|pair| {
+                let (from, to) = (parse_timestamp(pair[0].timestamp())?, parse_timestamp(pair[1].timestamp())?);
+                Some(TestGap {
+                    from: pair[0].timestamp().to_string(),
+                    to: pair[1].timestamp().to_string(),
+                    hours: (to - from).num_minutes() as f64 / 60.0,
+                })
+            })
+            .collect();
+
+        let by_time_bin = time_bins.iter()
+            .map(|bin| {
+                let in_bin: Vec<&R> = readings.iter().filter(|r| bin.contains(r.timestamp())).collect();
+                let dates: std::collections::HashSet<&str> = in_bin.iter().filter_map(|r| r.timestamp().get(0..10)).collect();
+                BinAdherence { name: bin.name.clone(), description: bin.description(), tests: in_bin.len(), days_tested: dates.len() }
+            })
+            .collect();
+
+        Some(Self {
+            target_per_day,
+            days: (last - first).num_days() as usize + 1,
+            tests: times.len(),
+            zero_test_days,
+            days_on_target,
+            current_streak: streak,
+            longest_streak,
+            by_time_bin,
+            longest_gap: gaps.iter().max_by(|a, b| a.hours.total_cmp(&b.hours)).cloned(),
+            long_gaps: gaps.into_iter().filter(|g| g.hours >= LONG_GAP_HOURS).collect(),
+        })
+    }
+
+    pub fn tests_per_day(&self) -> f64 {
+        self.tests as f64 / self.days as f64
+    }
+
+    pub fn days_on_target_percent(&self) -> f64 {
+        self.days_on_target as f64 / self.days as f64 * 100.0
+    }
+
+    pub fn meets_target(&self) -> bool {
+        self.tests_per_day() >= self.target_per_day as f64
+    }
+}
+
+impl BinAdherence {
+    /// Average tests per day in this bin over `days`
+    pub fn per_day(&self, days: usize) -> f64 {
+        if days == 0 { 0.0 } else { self.tests as f64 / days as f64 }
+    }
+
+    /// Share of `days` with a test in this bin
+    pub fn days_percent(&self, days: usize) -> f64 {
+        if days == 0 { 0.0 } else { self.days_tested as f64 / days as f64 * 100.0 }
+    }
+}
+
+// ============= Period Comparison =============
+
+/// |z| above which a difference between periods is called significant (p < 0.05, two-sided)
+const SIGNIFICANT_Z: f64 = 1.96;
+
+/// An inclusive range of dates, stored zero-padded ("YYYY/MM/DD") so they
+/// compare like the dates of reading timestamps
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DateRange {
+    pub start: String,
+    pub end: String,
+}
+
+impl DateRange {
+    /// Range between two dates such as "2024/1/5"; `None` unless both parse and `start <= end`
+    pub fn new(start: &str, end: &str) -> Option<Self> {
+        let (start, end) = (parse_date(start.trim())?, parse_date(end.trim())?);
+        let range = Self { start: start.format("%Y/%m/%d").to_string(), end: end.format("%Y/%m/%d").to_string() };
+        (range.days()? > 0).then_some(range)
+    }
+
+    /// The `days` days ending on `end`
+    pub fn last_days(end: &str, days: u32) -> Option<Self> {
+        let end_date = parse_date(end)?;
+        let start = end_date - chrono::Duration::days(days.max(1) as i64 - 1);
+        Self::new(&start.format("%Y/%m/%d").to_string(), end)
+    }
+
+    /// The range of the same length ending the day before this one starts
+    pub fn preceding(&self) -> Option<Self> {
+        let end = parse_date(&self.start)? - chrono::Duration::days(1);
+        Self::last_days(&end.format("%Y/%m/%d").to_string(), self.days()? as u32)
+    }
+
+    /// Number of days in the range, counting both ends
+    pub fn days(&self) -> Option<i64> {
+        Some((parse_date(&self.end)? - parse_date(&self.start)?).num_days() + 1)
+    }
+
+    pub fn contains(&self, timestamp: &str) -> bool {
+        timestamp.get(0..10).is_some_and(|date| date >= self.start.as_str() && date <= self.end.as_str())
+    }
+
+    pub fn description(&self) -> String {
+        format!("{} to {}", self.start, self.end)
+    }
+}
+
+fn parse_date(date: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date, "%Y/%m/%d").ok()
+}
+
+/// Statistics of the readings inside one date range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodStats {
+    pub range: DateRange,
+    pub basic: Option<BasicStats>,
+    pub tir: TimeInRange,
+    pub variability: Option<VariabilityStats>,
+}
+
+impl PeriodStats {
+    pub fn from_readings<R: ReadingData>(readings: &[R], range: DateRange, thresholds: Thresholds) -> Self {
+        let in_range: Vec<&R> = readings.iter().filter(|r| range.contains(r.timestamp())).collect();
+        let mgdl: Vec<u16> = in_range.iter().map(|r| r.mg_dl()).collect();
+        let mmol: Vec<f64> = in_range.iter().map(|r| r.mmol_l()).collect();
+
+        Self {
+            range,
+            basic: BasicStats::from_values(&mgdl, &mmol),
+            tir: TimeInRange::from_values(&mgdl, thresholds),
+            variability: Analysis::calculate_variability(&in_range),
+        }
+    }
+}
+
+/// Whether a change between periods is for the better
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Improved,
+    Worsened,
+    Unchanged,
+}
+
+/// Change in one metric from the previous to the current period
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricDelta {
+    pub metric: &'static str,
+    /// "%" or the glucose unit label
+    pub unit: &'static str,
+    pub previous: f64,
+    pub current: f64,
+    pub change: f64,
+    pub direction: Direction,
+    /// Whether the change is statistically significant; `None` where no test applies
+    pub significant: Option<bool>,
+}
+
+impl MetricDelta {
+    fn new(metric: &'static str, unit: &'static str, previous: f64, current: f64, lower_is_better: bool, z: Option<f64>) -> Self {
+        let change = current - previous;
+        // Changes that round to zero at one decimal are no change
+        let direction = if change.abs() < 0.05 {
+            Direction::Unchanged
+        } else if (change < 0.0) == lower_is_better {
+            Direction::Improved
+        } else {
+            Direction::Worsened
+        };
+        Self { metric, unit, previous, current, change, direction, significant: z.map(|z| z.abs() >= SIGNIFICANT_Z) }
+    }
+}
+
+/// Welch z statistic for the difference of two means
+fn mean_difference_z(mean1: f64, sd1: f64, n1: usize, mean2: f64, sd2: f64, n2: usize) -> Option<f64> {
+    if n1 < 2 || n2 < 2 {
+        return None;
+    }
+    let se = (sd1 * sd1 / n1 as f64 + sd2 * sd2 / n2 as f64).sqrt();
+    (se > 0.0).then(|| (mean2 - mean1) / se)
+}
+
+/// Pooled two-proportion z statistic
+fn proportion_difference_z(count1: usize, n1: usize, count2: usize, n2: usize) -> Option<f64> {
+    if n1 == 0 || n2 == 0 {
+        return None;
+    }
+    let pooled = (count1 + count2) as f64 / (n1 + n2) as f64;
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 as f64 + 1.0 / n2 as f64)).sqrt();
+    (se > 0.0).then(|| (count2 as f64 / n2 as f64 - count1 as f64 / n1 as f64) / se)
+}
+
+/// Two periods side by side, e.g. the last 14 days against the 14 before
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodComparison {
+    pub previous: PeriodStats,
+    pub current: PeriodStats,
+}
+
+impl PeriodComparison {
+    pub fn new<R: ReadingData>(readings: &[R], previous: DateRange, current: DateRange, thresholds: Thresholds) -> Self {
+        Self {
+            previous: PeriodStats::from_readings(readings, previous, thresholds),
+            current: PeriodStats::from_readings(readings, current, thresholds),
+        }
+    }
+
+    /// The last `days` days of readings against the `days` days before them
+    ///
+    /// `readings` must be sorted by time. Returns `None` if there are no readings.
+    pub fn last_days<R: ReadingData>(readings: &[R], days: u32, thresholds: Thresholds) -> Option<Self> {
+        let current = DateRange::last_days(readings.last()?.timestamp().get(0..10)?, days)?;
+        let previous = current.preceding()?;
+        Some(Self::new(readings, previous, current, thresholds))
+    }
+
+    /// Deltas of every metric both periods have, in user's preferred unit
+    pub fn deltas(&self, unit: GlucoseUnit) -> Vec<MetricDelta> {
+        let mut deltas = Vec::new();
+        let (prev, cur) = (&self.previous, &self.current);
+
+        if let (Some(p), Some(c)) = (&prev.basic, &cur.basic) {
+            let z = mean_difference_z(p.mean(unit), p.std_dev(unit), p.count(), c.mean(unit), c.std_dev(unit), c.count());
+            deltas.push(MetricDelta::new("Mean glucose", unit.label(), p.mean(unit), c.mean(unit), true, z));
+            deltas.push(MetricDelta::new("Standard deviation", unit.label(), p.std_dev(unit), c.std_dev(unit), true, None));
+            deltas.push(MetricDelta::new("GMI", "%", gmi_percent(p.mgdl.mean), gmi_percent(c.mgdl.mean), true, None));
+        }
+
+        let (pt, ct) = (&prev.tir, &cur.tir);
+        if pt.total > 0 && ct.total > 0 {
+            deltas.push(MetricDelta::new("Time in range", "%", pt.in_range_percent(), ct.in_range_percent(), false,
+                proportion_difference_z(pt.in_range, pt.total, ct.in_range, ct.total)));
+            deltas.push(MetricDelta::new("Time below range", "%", pt.low_percent(), ct.low_percent(), true,
+                proportion_difference_z(pt.total_low(), pt.total, ct.total_low(), ct.total)));
+            deltas.push(MetricDelta::new("Time above range", "%", pt.high_percent(), ct.high_percent(), true,
+                proportion_difference_z(pt.total_high(), pt.total, ct.total_high(), ct.total)));
+        }
+
+        if let (Some(p), Some(c)) = (&prev.variability, &cur.variability) {
+            deltas.push(MetricDelta::new("CV", "%", p.cv_percent, c.cv_percent, true, None));
+            deltas.push(MetricDelta::new("LBGI", "", p.lbgi, c.lbgi, true, None));
+            deltas.push(MetricDelta::new("HBGI", "", p.hbgi, c.hbgi, true, None));
+            deltas.push(MetricDelta::new("GRI", "", p.gri, c.gri, true, None));
+        }
+
+        deltas
+    }
+}
+
+// ============= Analysis Engine =============
+
+/// Settings that shape an analysis
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    pub thresholds: Thresholds,
+    pub time_bins: Vec<TimeBin>,
+    /// Histogram bin width in mg/dL
+    pub histogram_bin_width: u16,
+    /// Width of the AGP modal-day bins in minutes
+    pub agp_bin_minutes: u16,
+    /// Longest time from a before_meal to its after_meal reading
+    pub meal_pair_window_minutes: u16,
+    /// Tests per day the adherence metrics are measured against
+    pub tests_per_day_target: u32,
+}
+
+impl AnalysisOptions {
+    pub fn new(thresholds: Thresholds, time_bins: &[TimeBin]) -> Self {
+        Self { thresholds, time_bins: time_bins.to_vec(), histogram_bin_width: 20, agp_bin_minutes: 60, meal_pair_window_minutes: 180, tests_per_day_target: DEFAULT_TESTS_PER_DAY }
+    }
+}
+
+/// Every aggregate the GUI, PDF export and CLI show, computed in one place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Analysis {
+    pub basic: Option<BasicStats>,
+    pub tir: TimeInRange,
+    pub daily: Vec<DailyStats>,
+    pub hourly: Vec<HourlyStats>,
+    pub time_bins: Vec<TimeBinStats>,
+    pub histogram: Vec<HistogramBin>,
+    pub calendar: Vec<CalendarDay>,
+    /// ISO weeks with readings, in date order
+    pub weekly: Vec<WeeklyStats>,
+    /// Days of the week with readings, Monday first
+    pub weekdays: Vec<WeekdayStats>,
+    pub variability: Option<VariabilityStats>,
+    /// One estimate per window in [`A1C_WINDOWS`] that has readings
+    pub a1c: Vec<A1cEstimate>,
+    pub agp: AgpReport,
+    pub episodes: EpisodeReport,
+    pub meal_pairs: MealPairReport,
+    /// Testing frequency, if there are readings
+    pub adherence: Option<Adherence>,
+    pub trend: TrendReport,
+}
+
+impl Analysis {
+    /// Analyse readings sorted by time
+    pub fn run<R: ReadingData>(readings: &[R], options: &AnalysisOptions) -> Self {
+        let mgdl_values: Vec<u16> = readings.iter().map(|r| r.mg_dl()).collect();
+        let mmol_values: Vec<f64> = readings.iter().map(|r| r.mmol_l()).collect();
+        let hourly = Self::calculate_hourly(readings);
+
+        Self {
+            basic: BasicStats::from_values(&mgdl_values, &mmol_values),
+            tir: TimeInRange::from_values(&mgdl_values, options.thresholds),
+            daily: Self::calculate_daily(readings, options.thresholds),
+            agp: AgpReport::new(readings, &hourly, options.agp_bin_minutes),
+            hourly,
+            time_bins: Self::calculate_time_bins(readings, &options.time_bins),
+            histogram: Self::calculate_histogram(&mgdl_values, options.histogram_bin_width),
+            calendar: Self::calculate_calendar(readings, options.thresholds),
+            weekly: Self::calculate_weekly(readings, options.thresholds),
+            weekdays: Self::calculate_weekdays(readings, options.thresholds),
+            variability: Self::calculate_variability(readings),
+            a1c: A1C_WINDOWS.iter().filter_map(|&days| A1cEstimate::from_readings(readings, days)).collect(),
+            episodes: EpisodeReport::from_readings(readings, options.thresholds),
+            meal_pairs: MealPairReport::from_readings(readings, &options.time_bins, options.meal_pair_window_minutes),
+            adherence: Adherence::from_readings(readings, &options.time_bins, options.tests_per_day_target),
+            trend: TrendReport::from_readings(readings, options.thresholds),
+        }
+    }
+
+    /// The A1c estimate over the given window, if there are readings
+    pub fn a1c_for(&self, window_days: u32) -> Option<&A1cEstimate> {
+        self.a1c.iter().find(|e| e.window_days == window_days)
+    }
+
+    /// Readings grouped by date ("YYYY/MM/DD"), in date order
+    fn group_by_date<R: ReadingData>(readings: &[R]) -> std::collections::BTreeMap<String, Vec<&R>> {
+        let mut days: std::collections::BTreeMap<String, Vec<&R>> = std::collections::BTreeMap::new();
+        for reading in readings {
+            if let Some(date) = reading.timestamp().get(0..10) {
+                days.entry(date.to_string()).or_default().push(reading);
+            }
+        }
+        days
+    }
+
+    fn calculate_daily<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<DailyStats> {
+        Self::group_by_date(readings).into_iter()
+            .map(|(date, day)| {
+                let mgdl: Vec<u16> = day.iter().map(|r| r.mg_dl()).collect();
+                let mmol: Vec<f64> = day.iter().map(|r| r.mmol_l()).collect();
+                DailyStats::new(date, &mgdl, &mmol, thresholds)
+            })
+            .collect()
+    }
+
+    fn calculate_variability<R: ReadingData>(readings: &[R]) -> Option<VariabilityStats> {
+        let days: Vec<(Vec<u16>, Vec<f64>)> = Self::group_by_date(readings).into_values()
+            .map(|day| (day.iter().map(|r| r.mg_dl()).collect(), day.iter().map(|r| r.mmol_l()).collect()))
+            .collect();
+        VariabilityStats::from_days(&days)
+    }
+
+    /// Number of days whose CV is within the stability target, and days with a CV
+    pub fn stable_days(&self) -> (usize, usize) {
+        let with_cv: Vec<&VariabilityStats> = self.daily.iter().filter_map(|d| d.variability.as_ref()).collect();
+        (with_cv.iter().filter(|v| v.is_stable()).count(), with_cv.len())
+    }
+
+    fn calculate_hourly<R: ReadingData>(readings: &[R]) -> Vec<HourlyStats> {
+        bin_by_minute(readings, 60).into_iter()
+            .enumerate()
+            .map(|(hour, (mgdl, mmol))| HourlyStats::new(hour as u8, mgdl, mmol))
+            .collect()
+    }
+
+    fn calculate_time_bins<R: ReadingData>(readings: &[R], bins: &[TimeBin]) -> Vec<TimeBinStats> {
+        bins.iter().map(|bin| {
+            let filtered: Vec<_> = readings.iter()
+                .filter(|r| bin.contains(r.timestamp()))
+                .collect();
+            let mgdl: Vec<u16> = filtered.iter().map(|r| r.mg_dl()).collect();
+            let mmol: Vec<f64> = filtered.iter().map(|r| r.mmol_l()).collect();
+            TimeBinStats::new(bin, mgdl, mmol)
+        }).collect()
+    }
+
+    /// Histogram from 40 to 400 mg/dL; empty when there are no readings
+    fn calculate_histogram(values: &[u16], bin_width: u16) -> Vec<HistogramBin> {
+        let total = values.len();
+        if total == 0 || bin_width == 0 {
+            return Vec::new();
+        }
+
+        let mut bins: Vec<HistogramBin> = Vec::new();
+        let mut start = 40u16;
+        while start < 400 {
+            let end = start + bin_width;
+            let count = values.iter().filter(|&&v| v >= start && v < end).count();
+            bins.push(HistogramBin {
+                range_start: start,
+                range_end: end,
+                count,
+                percentage: (count as f64 / total as f64) * 100.0,
+            });
+            start = end;
+        }
+
+        bins
+    }
+
+    fn calculate_calendar<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<CalendarDay> {
+        Self::group_by_date(readings).into_iter()
+            .map(|(date, day)| {
+                let values: Vec<(u8, u16, f64)> = day.iter()
+                    .filter_map(|r| Some(((minute_of_day(r.timestamp())? / 60) as u8, r.mg_dl(), r.mmol_l())))
+                    .collect();
+                let (day_of_week, week_of_year) = parse_date_info(&date);
+                CalendarDay::new(date, day_of_week, week_of_year, values, thresholds)
+            })
+            .collect()
+    }
+
+    fn calculate_weekly<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<WeeklyStats> {
+        use chrono::Datelike;
+        let mut weeks: std::collections::BTreeMap<(i32, u32), Vec<&R>> = std::collections::BTreeMap::new();
+        for reading in readings {
+            if let Some(time) = parse_timestamp(reading.timestamp()) {
+                let week = time.date().iso_week();
+                weeks.entry((week.year(), week.week())).or_default().push(reading);
+            }
+        }
+        weeks.into_iter()
+            .filter_map(|((iso_year, week), week_readings)| {
+                let monday = chrono::NaiveDate::from_isoywd_opt(iso_year, week, chrono::Weekday::Mon)?;
+                let (days_with_readings, stats, tir, variability) = Self::aggregate(&week_readings, thresholds)?;
+                Some(WeeklyStats {
+                    iso_year,
+                    week,
+                    week_start: monday.format("%Y/%m/%d").to_string(),
+                    days_with_readings,
+                    stats,
+                    tir,
+                    variability,
+                })
+            })
+            .collect()
+    }
+
+    fn calculate_weekdays<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Vec<WeekdayStats> {
+        use chrono::Datelike;
+        let mut weekdays: Vec<Vec<&R>> = vec![Vec::new(); 7];
+        for reading in readings {
+            if let Some(time) = parse_timestamp(reading.timestamp()) {
+                weekdays[time.weekday().num_days_from_monday() as usize].push(reading);
+            }
+        }
+        weekdays.into_iter().enumerate()
+            .filter_map(|(day, day_readings)| {
+                let (days_with_readings, stats, tir, variability) = Self::aggregate(&day_readings, thresholds)?;
+                Some(WeekdayStats { day_of_week: day as u8, days_with_readings, stats, tir, variability })
+            })
+            .collect()
+    }
+
+    /// Days with readings, basic stats, TIR and variability of a group of readings
+    fn aggregate<R: ReadingData>(readings: &[R], thresholds: Thresholds) -> Option<(usize, BasicStats, TimeInRange, Option<VariabilityStats>)> {
+        let mgdl: Vec<u16> = readings.iter().map(|r| r.mg_dl()).collect();
+        let mmol: Vec<f64> = readings.iter().map(|r| r.mmol_l()).collect();
+        Some((
+            Self::group_by_date(readings).len(),
+            BasicStats::from_values(&mgdl, &mmol)?,
+            TimeInRange::from_values(&mgdl, thresholds),
+            Self::calculate_variability(readings),
+        ))
+    }
+}
+
+/// Day of week (0 = Monday) and ISO week of a "YYYY/MM/DD" or "YYYY-MM-DD" date
+fn parse_date_info(date: &str) -> (u8, u32) {
+    use chrono::Datelike;
+    chrono::NaiveDate::parse_from_str(date, "%Y/%m/%d")
+        .or_else(|_| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .map(|d| (d.weekday().num_days_from_monday() as u8, d.iso_week().week()))
+        .unwrap_or((0, 0))
+}
+
+/// Trait for reading data access (both units)
+pub trait ReadingData {
+    fn mg_dl(&self) -> u16;
+    fn mmol_l(&self) -> f64;
+    fn timestamp(&self) -> &str;
+    /// Comma-separated tags, if the reading has any
+    fn tags(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<R: ReadingData> ReadingData for &R {
+    fn mg_dl(&self) -> u16 {
+        (*self).mg_dl()
+    }
+    fn mmol_l(&self) -> f64 {
+        (*self).mmol_l()
+    }
+    fn timestamp(&self) -> &str {
+        (*self).timestamp()
+    }
+    fn tags(&self) -> Option<&str> {
+        (*self).tags()
+    }
+}
+
+/// Readings for the tests of this module and of the modules built on it
+#[cfg(test)]
+pub mod fixtures {
+    use super::ReadingData;
+
+    pub struct TestReading(pub &'static str, pub u16);
+
+    impl ReadingData for TestReading {
+        fn mg_dl(&self) -> u16 { self.1 }
+        fn mmol_l(&self) -> f64 { self.1 as f64 / 18.0 }
+        fn timestamp(&self) -> &str { self.0 }
+    }
+
+    /// A reading with a comma-separated tag list
+    pub struct TaggedReading(pub &'static str, pub u16, pub &'static str);
+
+    impl ReadingData for TaggedReading {
+        fn mg_dl(&self) -> u16 { self.1 }
+        fn mmol_l(&self) -> f64 { self.1 as f64 / 18.0 }
+        fn timestamp(&self) -> &str { self.0 }
+        fn tags(&self) -> Option<&str> { Some(self.2) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::{TaggedReading, TestReading};
+
+    #[test]
+    fn test_basic_stats() {
+        let mgdl_values = vec![100, 120, 140, 160, 180];
+        let mmol_values = vec![5.6, 6.7, 7.8, 8.9, 10.0];
+        let stats = BasicStats::from_values(&mgdl_values, &mmol_values).unwrap();
+        
+        // mg/dL stats
+        assert_eq!(stats.mgdl.count, 5);
+        assert!((stats.mgdl.mean - 140.0).abs() < 0.01);
+        assert_eq!(stats.mgdl.min, 100);
+        assert_eq!(stats.mgdl.max, 180);
+        
+        // mmol/L stats
+        assert_eq!(stats.mmol.count, 5);
+        assert!((stats.mmol.mean - 7.8).abs() < 0.01);
+        assert!((stats.mmol.min - 5.6).abs() < 0.01);
+        assert!((stats.mmol.max - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_time_in_range() {
+        let values = vec![50, 65, 100, 150, 200, 300];
+        let tir = TimeInRange::from_values(&values, Thresholds::default());
+        assert_eq!(tir.very_low, 1);
+        assert_eq!(tir.low, 1);
+        assert_eq!(tir.in_range, 2);
+        assert_eq!(tir.high, 1);
+        assert_eq!(tir.very_high, 1);
+    }
+
+    #[test]
+    fn test_time_bin_wraps_midnight() {
+        let night_shift = TimeBin::new("Night shift", 22 * 60, 6 * 60);
+        assert!(night_shift.contains("2024/01/01 23:15"));
+        assert!(night_shift.contains("2024/01/02 05:59"));
+        assert!(!night_shift.contains("2024/01/02 06:00"));
+        assert!(!night_shift.contains("2024/01/02 12:00"));
+        assert_eq!(night_shift.description(), "22:00-06:00");
+
+        let evening = TimeBin::new("Evening", 18 * 60, 24 * 60);
+        assert!(evening.contains("2024/01/01 23:59"));
+        assert!(!evening.contains("2024/01/01 00:00"));
+    }
+
+    #[test]
+    fn test_analysis_aggregates() {
+        let readings = [
+            TestReading("2024/01/01 07:00", 90),
+            TestReading("2024/01/01 13:30", 200),
+            TestReading("2024/01/02 07:15", 60),
+            TestReading("2024/01/02 22:45", 150),
+        ];
+        let options = AnalysisOptions::new(Thresholds::default(), &default_time_bins());
+        let analysis = Analysis::run(&readings, &options);
+
+        assert_eq!(analysis.basic.as_ref().unwrap().mgdl.count, 4);
+        assert_eq!((analysis.tir.low, analysis.tir.in_range, analysis.tir.high), (1, 2, 1));
+
+        assert_eq!(analysis.daily.len(), 2);
+        assert_eq!(analysis.daily[0].date, "2024/01/01");
+        assert_eq!(analysis.calendar.len(), 2);
+        assert_eq!(analysis.calendar[0].day_of_week, 0);
+        assert_eq!(analysis.calendar[1].readings, vec![(7, 60, 60.0 / 18.0), (22, 150, 150.0 / 18.0)]);
+
+        assert_eq!(analysis.hourly.len(), 24);
+        assert_eq!(analysis.hourly[7].count(), 2);
+        let bin_counts: Vec<usize> = analysis.time_bins.iter().map(|b| b.mgdl_readings.len()).collect();
+        assert_eq!(bin_counts, vec![0, 2, 0, 1, 0, 1]);
+
+        assert_eq!(analysis.histogram.len(), 18);
+        assert_eq!(analysis.histogram.iter().map(|b| b.count).sum::<usize>(), 4);
+        assert!((analysis.histogram.iter().map(|b| b.percentage).sum::<f64>() - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_analysis_of_no_readings() {
+        let readings: [TestReading; 0] = [];
+        let analysis = Analysis::run(&readings, &AnalysisOptions::new(Thresholds::default(), &default_time_bins()));
+        assert!(analysis.basic.is_none());
+        assert!(analysis.histogram.is_empty());
+        assert!(analysis.daily.is_empty());
+        assert_eq!(analysis.time_bins.len(), 6);
+    }
+
+    #[test]
+    fn test_a1c_reference_values() {
+        // 154 mg/dL corresponds to an A1c of about 7% (53 mmol/mol)
+        assert!((adag_ea1c_percent(154.0) - 6.99).abs() < 0.01);
+        assert!((gmi_percent(154.0) - 6.99).abs() < 0.01);
+        assert!((a1c_percent_to_mmol_mol(7.0) - 53.0).abs() < 0.1);
+        assert!((adag_ea1c_percent(126.0) - 6.02).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_a1c_window_and_density() {
+        let readings = [
+            TestReading("2024/01/01 08:00", 300),
+            TestReading("2024/03/10 08:00", 140),
+            TestReading("2024/03/11 08:00", 168),
+        ];
+        let estimate = A1cEstimate::from_readings(&readings, 14).unwrap();
+        assert_eq!(estimate.readings, 2);
+        assert_eq!(estimate.start_date, "2024/02/27");
+        assert!((estimate.mean_mgdl - 154.0).abs() < 1e-9);
+        assert!(estimate.density_warning().is_some());
+
+        let empty: [TestReading; 0] = [];
+        assert!(A1cEstimate::from_readings(&empty, 14).is_none());
+    }
+
+    fn day(values: &[u16]) -> (Vec<u16>, Vec<f64>) {
+        (values.to_vec(), values.iter().map(|&v| v as f64 / 18.0).collect())
+    }
+
+    #[test]
+    fn test_risk_indices_reference_values() {
+        // The risk function is zero at 112.5 mg/dL
+        let (low, high) = bg_risk(112);
+        assert!(low < 0.01 && high < 0.01);
+        assert!((bg_risk(50).0 - 22.5).abs() < 0.1);
+        assert!((bg_risk(300).1 - 33.9).abs() < 0.1);
+
+        let v = VariabilityStats::from_days(&[day(&[50, 300]), day(&[112, 113])]).unwrap();
+        assert!((v.lbgi - 22.5 / 4.0).abs() < 0.05);
+        assert!((v.hbgi - 33.9 / 4.0).abs() < 0.05);
+        assert!((v.adrr - (22.5 + 33.9) / 2.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_cv_j_index_and_mage() {
+        // mean 150, SD 50 -> CV 33.3%, J-index 40
+        let v = VariabilityStats::from_days(&[day(&[100, 150, 200])]).unwrap();
+        assert!((v.cv_percent - 33.33).abs() < 0.01);
+        assert!(v.is_stable());
+        assert!((v.j_index - 40.0).abs() < 1e-9);
+
+        // Small wiggles below one SD are not excursions
+        let v = VariabilityStats::from_days(&[day(&[100, 110, 100, 200, 100, 200])]).unwrap();
+        assert_eq!(v.mage_mgdl, Some(100.0));
+        assert!(VariabilityStats::from_days(&[day(&[100])]).is_none());
+
+        // A 20 mg/dL dip (SD 73) inside a rise is smoothed out before pairing
+        let v = VariabilityStats::from_days(&[day(&[100, 220, 200, 260, 100])]).unwrap();
+        assert_eq!(v.mage_mgdl, Some(160.0));
+    }
+
+    #[test]
+    fn test_mage_per_day() {
+        // The fall from 250 on one day to 60 on a later day is no excursion
+        let v = VariabilityStats::from_days(&[day(&[100, 250]), day(&[60, 200])]).unwrap();
+        assert_eq!(v.mage_mgdl, Some(145.0));
+        assert!((v.mage_mmol.unwrap() - 145.0 / 18.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gri() {
+        // 2% very low, 3% low, 10% high, 5% very high -> 3 x 4.4 + 1.6 x 10 = 29.2
+        let mut values = vec![50; 2];
+        values.extend([60; 3]);
+        values.extend([200; 10]);
+        values.extend([300; 5]);
+        values.extend([120; 80]);
+        let v = VariabilityStats::from_days(&[day(&values)]).unwrap();
+        assert!((v.gri - 29.2).abs() < 1e-9);
+        assert_eq!(v.gri_zone(), 'B');
+    }
+
+    #[test]
+    fn test_interpolated_percentiles() {
+        // Matches spreadsheet PERCENTILE / QUARTILE.INC
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&values, 25.0), 1.75);
+        assert_eq!(percentile(&values, 50.0), 2.5);
+        assert_eq!(percentile(&values, 75.0), 3.25);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 100.0), 4.0);
+        assert!((percentile(&values, 95.0) - 3.85).abs() < 1e-9);
+        assert_eq!(percentile(&[7.0], 90.0), 7.0);
+
+        let unsorted: [u16; 5] = [180, 100, 140, 120, 160];
+        assert_eq!(percentiles(&unsorted, &STANDARD_PERCENTILES), vec![104.0, 108.0, 120.0, 140.0, 160.0, 172.0, 176.0]);
+
+        let hour = HourlyStats::new(8, vec![100, 120], vec![5.5, 6.7]);
+        assert_eq!(hour.stats.as_ref().unwrap().mgdl.median, 110.0);
+        let band = hour.band(GlucoseUnit::MgDl).unwrap();
+        assert_eq!((band.p5, band.p95), (101.0, 119.0));
+    }
+
+    #[test]
+    fn test_agp_bins_and_consensus_metrics() {
+        let readings = [
+            TestReading("2024/01/01 07:05", 50),
+            TestReading("2024/01/01 07:20", 100),
+            TestReading("2024/01/03 07:50", 120),
+            TestReading("2024/01/03 23:59", 300),
+        ];
+
+        let hourly = AgpReport::from_readings(&readings, 60);
+        assert_eq!(hourly.bins.len(), 24);
+        assert_eq!(hourly.bins[7].count, 3);
+        assert_eq!(hourly.bins[7].band(GlucoseUnit::MgDl).unwrap().p50, 100.0);
+        assert_eq!(hourly.bins[23].count, 1);
+        // Hourly bins are the hourly stats
+        let hours = Analysis::calculate_hourly(&readings);
+        assert!(hourly.bins.iter().zip(&hours).all(|(bin, hour)| bin.band(GlucoseUnit::MgDl) == hour.band(GlucoseUnit::MgDl)));
+
+        let quarters = AgpReport::from_readings(&readings, 15);
+        assert_eq!(quarters.bins.len(), 96);
+        let counts: Vec<usize> = quarters.bins[28..32].iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![1, 1, 0, 1]);
+        assert_eq!(quarters.bins[95].start_minute, 23 * 60 + 45);
+
+        let metrics = hourly.metrics.unwrap();
+        assert_eq!((metrics.days, metrics.days_with_readings), (3, 2));
+        assert_eq!(metrics.mean_mgdl, 142.5);
+        let met: Vec<bool> = metrics.range_targets().iter().map(|t| t.met).collect();
+        // 25% very high, 25% very low, 50% in range: every target missed
+        assert_eq!(met, vec![false, false, false, false, false]);
+        assert!(AgpReport::from_readings::<TestReading>(&[], 60).metrics.is_none());
+    }
+
+    #[test]
+    fn test_period_comparison() {
+        let range = DateRange::last_days("2024/03/01", 14).unwrap();
+        assert_eq!((range.start.as_str(), range.days()), ("2024/02/17", Some(14)));
+        let before = range.preceding().unwrap();
+        assert_eq!((before.start.as_str(), before.end.as_str()), ("2024/02/03", "2024/02/16"));
+        assert!(DateRange::new("2024/03/02", "2024/03/01").is_none());
+        assert!(range.contains("2024/03/01 23:59") && !range.contains("2024/03/02 00:00"));
+
+        // Dates without leading zeros are stored padded
+        let range = DateRange::new(" 2024/1/5", "2024/1/12").unwrap();
+        assert_eq!((range.start.as_str(), range.end.as_str()), ("2024/01/05", "2024/01/12"));
+        assert!(range.contains("2024/01/10 08:00") && !range.contains("2024/01/13 08:00"));
+        assert!(DateRange::new("2024/13/01", "2024/12/31").is_none());
+
+        // Two high days, then two days in range
+        let readings = [
+            TestReading("2024/01/01 07:00", 220), TestReading("2024/01/01 12:00", 240),
+            TestReading("2024/01/01 18:00", 230), TestReading("2024/01/01 22:00", 250),
+            TestReading("2024/01/02 07:00", 220), TestReading("2024/01/02 12:00", 240),
+            TestReading("2024/01/02 18:00", 230), TestReading("2024/01/02 22:00", 250),
+            TestReading("2024/01/03 07:00", 110), TestReading("2024/01/03 12:00", 130),
+            TestReading("2024/01/03 18:00", 120), TestReading("2024/01/03 22:00", 140),
+            TestReading("2024/01/04 07:00", 110), TestReading("2024/01/04 12:00", 130),
+            TestReading("2024/01/04 18:00", 120), TestReading("2024/01/04 22:00", 140),
+        ];
+
+        let comparison = PeriodComparison::last_days(&readings, 2, Thresholds::default()).unwrap();
+        assert_eq!(comparison.previous.range.start, "2024/01/01");
+        assert_eq!((comparison.previous.tir.total, comparison.current.tir.total), (8, 8));
+
+        let deltas = comparison.deltas(GlucoseUnit::MgDl);
+        let mean = deltas.iter().find(|d| d.metric == "Mean glucose").unwrap();
+        assert_eq!((mean.previous, mean.current, mean.change), (235.0, 125.0, -110.0));
+        assert_eq!((mean.direction, mean.significant), (Direction::Improved, Some(true)));
+        let tir = deltas.iter().find(|d| d.metric == "Time in range").unwrap();
+        assert_eq!((tir.change, tir.direction, tir.significant), (100.0, Direction::Improved, Some(true)));
+        let below = deltas.iter().find(|d| d.metric == "Time below range").unwrap();
+        assert_eq!((below.direction, below.significant), (Direction::Unchanged, None));
+    }
+
+    #[test]
+    fn test_episode_detection() {
+        let readings = [
+            TestReading("2024/01/01 02:00", 65),
+            TestReading("2024/01/01 02:30", 50),
+            TestReading("2024/01/01 03:00", 68),
+            TestReading("2024/01/01 08:00", 65),   // more than 3 h later: new episode
+            TestReading("2024/01/01 09:00", 200),  // switches straight to a hyper
+            TestReading("2024/01/01 10:00", 120),
+            TestReading("2024/01/08 13:00", 190),
+            TestReading("2024/01/08 14:00", 260),
+        ];
+        let report = EpisodeReport::from_readings(&readings, Thresholds::default());
+
+        let summary: Vec<(EpisodeKind, &str, usize, u16)> = report.episodes.iter()
+            .map(|e| (e.kind, e.start.as_str(), e.readings, e.extreme_mgdl))
+            .collect();
+        assert_eq!(summary, vec![
+            (EpisodeKind::Hypo, "2024/01/01 02:00", 3, 50),
+            (EpisodeKind::Hypo, "2024/01/01 08:00", 1, 65),
+            (EpisodeKind::Hyper, "2024/01/01 09:00", 1, 200),
+            (EpisodeKind::Hyper, "2024/01/08 13:00", 2, 260),
+        ]);
+        assert_eq!(report.episodes[0].duration_minutes(), 60);
+        assert_eq!(report.episodes[0].extreme_time, "2024/01/01 02:30");
+        assert_eq!((report.severe_count(EpisodeKind::Hypo), report.severe_count(EpisodeKind::Hyper)), (1, 1));
+        assert_eq!(report.nocturnal_hypo_count(), 1);
+
+        assert_eq!(report.weeks, 8.0 / 7.0);
+        assert!((report.count_per_week(EpisodeKind::Hypo) - 1.75).abs() < 1e-9);
+        let weekly: Vec<(&str, usize, usize)> = report.weekly.iter().map(|w| (w.week_start.as_str(), w.hypo, w.hyper)).collect();
+        assert_eq!(weekly, vec![("2024/01/01", 2, 1), ("2024/01/08", 0, 1)]);
+    }
+
+    #[test]
+    fn test_weekly_and_weekday_stats() {
+        let readings = [
+            TestReading("2023/12/31 08:00", 100),  // Sunday of 2023-W52
+            TestReading("2024/01/01 08:00", 200),  // Monday of 2024-W01
+            TestReading("2024/01/01 20:00", 100),
+            TestReading("2024/01/07 08:00", 60),   // Sunday of 2024-W01
+            TestReading("2024/01/08 08:00", 120),  // Monday of 2024-W02
+        ];
+        let analysis = Analysis::run(&readings, &AnalysisOptions::new(Thresholds::default(), &[]));
+
+        let weekly: Vec<(String, &str, usize, usize, f64)> = analysis.weekly.iter()
+            .map(|w| (w.label(), w.week_start.as_str(), w.days_with_readings, w.stats.count(), w.stats.mgdl.mean))
+            .collect();
+        assert_eq!(weekly, vec![
+            ("2023-W52".to_string(), "2023/12/25", 1, 1, 100.0),
+            ("2024-W01".to_string(), "2024/01/01", 2, 3, 120.0),
+            ("2024-W02".to_string(), "2024/01/08", 1, 1, 120.0),
+        ]);
+        assert_eq!(analysis.weekly[1].tir.in_range, 1);
+
+        let weekdays: Vec<(&str, usize, usize, f64)> = analysis.weekdays.iter()
+            .map(|d| (d.label(), d.days_with_readings, d.stats.count(), d.stats.mgdl.mean))
+            .collect();
+        assert_eq!(weekdays, vec![("Mon", 2, 3, 140.0), ("Sun", 2, 2, 80.0)]);
+        assert_eq!(analysis.weekdays[1].tir.total_low(), 1);
+    }
+
+    #[test]
+    fn test_adherence() {
+        let readings = [
+            TestReading("2024/01/01 07:00", 100),
+            TestReading("2024/01/01 19:00", 100),
+            TestReading("2024/01/02 07:00", 100),
+            TestReading("2024/01/02 19:00", 100),
+            // No tests on 01/03
+            TestReading("2024/01/04 08:00", 100),
+            TestReading("2024/01/05 07:00", 100),
+            TestReading("2024/01/05 19:00", 100),
+        ];
+        let bins = [TimeBin::new("Morning", 6 * 60, 12 * 60), TimeBin::new("Evening", 18 * 60, 21 * 60)];
+        let adherence = Adherence::from_readings(&readings, &bins, 2).unwrap();
+
+        assert_eq!((adherence.days, adherence.tests), (5, 7));
+        assert!((adherence.tests_per_day() - 1.4).abs() < 1e-9);
+        assert!(!adherence.meets_target());
+        assert_eq!(adherence.zero_test_days, vec!["2024/01/03"]);
+        assert_eq!((adherence.days_on_target, adherence.current_streak, adherence.longest_streak), (3, 1, 2));
+
+        let gap = adherence.longest_gap.as_ref().unwrap();
+        assert_eq!((gap.from.as_str(), gap.to.as_str(), gap.hours), ("2024/01/02 19:00", "2024/01/04 08:00", 37.0));
+        assert_eq!(adherence.long_gaps.len(), 1);
+
+        let bins: Vec<(&str, usize, usize)> = adherence.by_time_bin.iter()
+            .map(|b| (b.name.as_str(), b.tests, b.days_tested))
+            .collect();
+        assert_eq!(bins, vec![("Morning", 4, 4), ("Evening", 3, 3)]);
+        assert_eq!(adherence.by_time_bin[1].days_percent(adherence.days), 60.0);
+
+        assert!(Adherence::from_readings::<TestReading>(&[], &[], 4).is_none());
+    }
+
+    #[test]
+    fn test_trend() {
+        let readings = [
+            TestReading("2024/01/01 08:00", 100),
+            TestReading("2024/01/01 09:00", 220),  // +2.0 mg/dL/min, rapid
+            TestReading("2024/01/01 12:00", 130),  // 180 min apart, no rate
+            TestReading("2024/01/02 08:00", 114),
+            TestReading("2024/01/02 08:30", 99),   // -0.5 mg/dL/min
+            TestReading("2024/01/04 08:00", 60),
+        ];
+        let trend = TrendReport::from_readings(&readings, Thresholds::default());
+
+        let rates: Vec<(&str, i64, f64)> = trend.rates.iter().map(|r| (r.to.as_str(), r.minutes, r.mgdl_per_min)).collect();
+        assert_eq!(rates, vec![("2024/01/01 09:00", 60, 2.0), ("2024/01/02 08:30", 30, -0.5)]);
+        assert_eq!(trend.rapid_count(), 1);
+        assert_eq!(trend.fastest_rise().unwrap().from, "2024/01/01 08:00");
+        assert_eq!(trend.fastest_fall().unwrap().mgdl_per_min, -0.5);
+
+        // Every day from the first to the last reading, skipping days with no readings in the window
+        let seven_day: Vec<(&str, usize, f64)> = trend.rolling[0].points.iter()
+            .map(|p| (p.date.as_str(), p.count, p.mean_mgdl))
+            .collect();
+        assert_eq!(trend.rolling[0].window_days, 7);
+        assert_eq!(seven_day, vec![
+            ("2024/01/01", 3, 150.0),
+            ("2024/01/02", 5, 132.6),
+            ("2024/01/03", 5, 132.6),
+            ("2024/01/04", 6, 120.5),
+        ]);
+        assert!((trend.rolling[0].points[0].tir_percent - 200.0 / 3.0).abs() < 1e-9);
+
+        let line = trend.regression.as_ref().unwrap();
+        assert!(line.slope_per_week(GlucoseUnit::MgDl) < 0.0);
+        assert!((line.value_at(line.end_epoch, GlucoseUnit::MgDl) - (line.start_mgdl + line.slope_mgdl_per_day * 3.0)).abs() < 1e-9);
+
+        // A single reading has no slope
+        assert!(TrendReport::from_readings(&readings[..1], Thresholds::default()).regression.is_none());
+    }
+
+    #[test]
+    fn test_trend_line_fit() {
+        // 10 mg/dL per day exactly
+        let points = [(0, 100.0, 5.5), (86400, 110.0, 6.0), (2 * 86400, 120.0, 6.5)];
+        let line = TrendLine::fit(&points).unwrap();
+        assert!((line.slope_per_week(GlucoseUnit::MgDl) - 70.0).abs() < 1e-9);
+        assert!((line.slope_per_week(GlucoseUnit::MmolL) - 3.5).abs() < 1e-9);
+        assert!((line.start_mgdl - 100.0).abs() < 1e-9);
+        assert!((line.r_squared - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tag_segments() {
+        let readings = [
+            TaggedReading("2024/01/01 07:00", 90, "fasting, before_meal"),
+            TaggedReading("2024/01/01 09:00", 200, "after_meal"),
+            TaggedReading("2024/01/02 07:00", 110, " fasting"),
+            TaggedReading("2024/01/02 12:00", 150, ""),
+        ];
+        assert_eq!(distinct_tags(&readings), vec!["after_meal", "before_meal", "fasting"]);
+        assert!(!has_tag(&readings[1], "after"));
+
+        let fasting: Vec<&TaggedReading> = readings.iter().filter(|r| has_tag(*r, "fasting")).collect();
+        let analysis = Analysis::run(&fasting, &AnalysisOptions::new(Thresholds::default(), &[]));
+        assert_eq!(analysis.basic.as_ref().map(|b| b.mgdl.mean), Some(100.0));
+        assert_eq!(analysis.tir.in_range, 2);
+    }
+
+    #[test]
+    fn test_meal_pairs() {
+        let readings = [
+            TaggedReading("2024/01/01 07:30", 100, "fasting, before_meal"),
+            TaggedReading("2024/01/01 09:00", 180, "after_meal"),
+            TaggedReading("2024/01/01 12:00", 110, "before_meal"),   // replaced by the next one
+            TaggedReading("2024/01/01 12:20", 120, "before_meal"),
+            TaggedReading("2024/01/01 14:00", 150, "after_meal"),
+            TaggedReading("2024/01/01 15:00", 140, "after_meal"),    // nothing waiting
+            TaggedReading("2024/01/02 07:00", 90, "before_meal"),
+            TaggedReading("2024/01/02 11:00", 200, "after_meal"),    // outside the 3 h window
+            TaggedReading("2024/01/02 18:00", 130, ""),
+        ];
+        let bins = [TimeBin::new("Breakfast", 6 * 60, 10 * 60), TimeBin::new("Lunch", 11 * 60, 14 * 60)];
+        let report = MealPairReport::from_readings(&readings, &bins, 180);
+
+        let pairs: Vec<(&str, i32, Option<&str>)> = report.pairs.iter()
+            .map(|p| (p.before_time.as_str(), p.excursion_mgdl(), p.time_bin.as_deref()))
+            .collect();
+        assert_eq!(pairs, vec![
+            ("2024/01/01 07:30", 80, Some("Breakfast")),
+            ("2024/01/01 12:20", 30, Some("Lunch")),
+        ]);
+        assert_eq!(report.pairs[0].minutes_apart(), 90);
+        assert_eq!((report.unpaired_before, report.unpaired_after), (2, 2));
+
+        let overall = report.overall.as_ref().unwrap();
+        assert_eq!((overall.pairs, overall.mean_excursion_mgdl, overall.max_excursion_mgdl, overall.large), (2, 55.0, 80, 1));
+        let by_bin: Vec<(&str, f64)> = report.by_time_bin.iter().map(|g| (g.label.as_str(), g.mean_excursion_mgdl)).collect();
+        assert_eq!(by_bin, vec![("Breakfast", 80.0), ("Lunch", 30.0)]);
+        let by_weekday: Vec<(&str, usize)> = report.by_weekday.iter().map(|g| (g.label.as_str(), g.pairs)).collect();
+        assert_eq!(by_weekday, vec![("Mon", 2)]);
     }
 }
@@ -1,12 +1,19 @@
 //! SQLite storage for glucose readings with notes support
 
-use rusqlite::{Connection, Result, params};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Result, params};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
 
-use crate::device::GlucoseReading;
-use crate::units::Thresholds;
-use crate::stats::{ReadingData, BasicStats, TimeInRange, DailyStats, HourlyStats, TimeBinStats, HistogramBin, CalendarDay};
+use crate::device::{Download, GlucoseReading};
+use crate::error::AccuChekError;
+use crate::stats::{parse_timestamp, ReadingData};
+use crate::tagging::{self, TagRule};
 
 /// Extended reading with notes and tags for storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +28,11 @@ pub struct StoredReading {
     pub note: Option<String>,
     pub tags: Option<String>,
     pub imported_at: String,
+    /// Excluded readings are kept in the database but skipped by statistics
+    #[serde(default)]
+    pub excluded: bool,
+    #[serde(default)]
+    pub exclusion_reason: Option<String>,
 }
 
 impl ReadingData for StoredReading {
@@ -35,17 +47,331 @@ impl ReadingData for StoredReading {
     fn timestamp(&self) -> &str {
         &self.timestamp
     }
+    
+    fn tags(&self) -> Option<&str> {
+        self.tags.as_deref()
+    }
+}
+
+/// Origin of a change to a reading's annotations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeSource {
+    Gui,
+    Cli,
+    Import,
+    /// Automatic tagging rules
+    Rules,
+}
+
+impl ChangeSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeSource::Gui => "gui",
+            ChangeSource::Cli => "cli",
+            ChangeSource::Import => "import",
+            ChangeSource::Rules => "rules",
+        }
+    }
+}
+
+/// One recorded change of a note, tag list or exclusion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub reading_id: i64,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_at: String,
+    pub source: String,
+}
+
+/// Kind of a logged event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Carbs,
+    Insulin,
+    Exercise,
+    Medication,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 4] = [EventKind::Carbs, EventKind::Insulin, EventKind::Exercise, EventKind::Medication];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Carbs => "carbs",
+            EventKind::Insulin => "insulin",
+            EventKind::Exercise => "exercise",
+            EventKind::Medication => "medication",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EventKind::Carbs => "Carbs",
+            EventKind::Insulin => "Insulin",
+            EventKind::Exercise => "Exercise",
+            EventKind::Medication => "Medication",
+        }
+    }
+
+    /// Unit of the amount: grams, units or minutes (medications have none)
+    pub fn unit(self) -> Option<&'static str> {
+        match self {
+            EventKind::Carbs => Some("g"),
+            EventKind::Insulin => Some("U"),
+            EventKind::Exercise => Some("min"),
+            EventKind::Medication => None,
+        }
+    }
+}
+
+/// A meal, insulin dose, exercise session or medication, with its time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub id: i64,
+    pub epoch: i64,
+    pub timestamp: String,
+    pub kind: EventKind,
+    /// Carbohydrate grams, insulin units or exercise minutes
+    pub amount: Option<f64>,
+    /// Insulin type, exercise type or medication name
+    pub detail: Option<String>,
+    pub note: Option<String>,
+    /// Reading the event belongs to, if linked
+    pub reading_id: Option<i64>,
+}
+
+impl Event {
+    /// Create an unsaved event at a "YYYY/MM/DD HH:MM" timestamp
+    pub fn new(kind: EventKind, timestamp: &str, amount: Option<f64>, detail: Option<String>) -> Option<Self> {
+        Some(Self {
+            id: 0,
+            epoch: timestamp_to_epoch(timestamp)?,
+            timestamp: timestamp.to_string(),
+            kind,
+            amount,
+            detail: detail.filter(|d| !d.trim().is_empty()),
+            note: None,
+            reading_id: None,
+        })
+    }
+
+    /// Short description, e.g. "45 g", "6 U rapid" or "metformin"
+    pub fn summary(&self) -> String {
+        let amount = match (self.amount, self.kind.unit()) {
+            (Some(amount), Some(unit)) => Some(format!("{} {}", amount, unit)),
+            (Some(amount), None) => Some(amount.to_string()),
+            (None, _) => None,
+        };
+        match (amount, &self.detail) {
+            (Some(amount), Some(detail)) => format!("{} {}", amount, detail),
+            (Some(amount), None) => amount,
+            (None, Some(detail)) => detail.clone(),
+            (None, None) => self.kind.label().to_string(),
+        }
+    }
+}
+
+/// Lowest and highest values an Accu-Chek meter reports (LO/HI outside this)
+const MIN_VALID_MGDL: u16 = 10;
+const MAX_VALID_MGDL: u16 = 600;
+
+/// Outcome of importing readings downloaded from a meter
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    /// Readings not yet in the database
+    pub inserted: usize,
+    /// Readings already stored with the same epoch and value
+    pub duplicates: usize,
+    /// Readings whose epoch is stored with a different value; the stored value is kept
+    pub conflicts: usize,
+    /// Readings with an invalid timestamp or out-of-range value
+    pub rejected: usize,
+}
+
+/// One download from a meter, successful or not
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSession {
+    pub id: i64,
+    pub started_at: String,
+    pub finished_at: String,
+    pub meter: Option<String>,
+    pub serial: Option<String>,
+    pub received: usize,
+    pub inserted: usize,
+    pub duplicates: usize,
+    pub conflicts: usize,
+    pub rejected: usize,
+    /// Meter clock minus computer clock, in seconds
+    pub clock_drift_secs: Option<i64>,
+    /// Why the sync failed, if it did
+    pub error: Option<String>,
+}
+
+impl SyncSession {
+    /// Start a session at the current local time
+    pub fn begin() -> Self {
+        Self { started_at: session_time(), ..Default::default() }
+    }
+
+    /// Record what the meter returned
+    pub fn set_download(&mut self, download: &Download) {
+        self.meter = Some(download.meter.product.clone());
+        self.serial = download.meter.serial.clone();
+        self.clock_drift_secs = download.meter.clock_drift_secs;
+        self.received = download.readings.len();
+    }
+
+    /// Mark the session as finished with the given import result
+    pub fn complete(&mut self, report: &ImportReport) {
+        self.inserted = report.inserted;
+        self.duplicates = report.duplicates;
+        self.conflicts = report.conflicts;
+        self.rejected = report.rejected;
+        self.finished_at = session_time();
+    }
+
+    /// Mark the session as failed
+    pub fn fail(&mut self, error: impl ToString) {
+        self.error = Some(error.to_string());
+        self.finished_at = session_time();
+    }
+
+    /// Meter name with serial number, if known
+    pub fn meter_label(&self) -> String {
+        match (&self.meter, &self.serial) {
+            (Some(meter), Some(serial)) => format!("{} ({})", meter, serial),
+            (Some(meter), None) => meter.clone(),
+            (None, _) => "-".to_string(),
+        }
+    }
+
+    /// Clock drift as text, e.g. "+3 min" (meter ahead) or "-45 s"
+    pub fn clock_drift_label(&self) -> String {
+        match self.clock_drift_secs {
+            Some(secs) if secs.abs() >= 120 => format!("{:+} min", secs / 60),
+            Some(secs) => format!("{:+} s", secs),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Number of readings that could not be imported as-is, or 1 for a failed sync
+    pub fn error_count(&self) -> usize {
+        if self.error.is_some() {
+            1
+        } else {
+            self.conflicts + self.rejected
+        }
+    }
+}
+
+/// Current local time in the format used for sync sessions
+fn session_time() -> String {
+    chrono::Local::now().format("%Y/%m/%d %H:%M:%S").to_string()
+}
+
+/// A reading whose two copies disagree during a merge
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub timestamp: String,
+    pub field: &'static str,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Outcome of merging another database into this one
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    /// Readings only present in the other database
+    pub inserted: usize,
+    /// Readings with the same epoch and value in both databases
+    pub duplicates: usize,
+    /// Notes, tags or exclusions copied onto readings that had none
+    pub annotations_adopted: usize,
+    /// Same epoch but a different glucose value; our value is kept
+    pub value_conflicts: Vec<MergeConflict>,
+    /// Both sides annotated the same reading differently; ours is kept
+    pub annotation_conflicts: Vec<MergeConflict>,
+}
+
+// ============= Encryption =============
+
+/// Passphrases of the encrypted databases unlocked in this process, by path
+static PASSPHRASES: RwLock<BTreeMap<PathBuf, String>> = RwLock::new(BTreeMap::new());
+
+/// Header every unencrypted SQLite file starts with
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Set the passphrase used to open the database at `path` (None to forget it)
+pub fn set_passphrase<P: AsRef<Path>>(path: P, passphrase: Option<String>) {
+    let mut passphrases = PASSPHRASES.write().unwrap_or_else(|e| e.into_inner());
+    match passphrase {
+        Some(passphrase) => passphrases.insert(path.as_ref().to_path_buf(), passphrase),
+        None => passphrases.remove(path.as_ref()),
+    };
 }
 
+/// Whether a passphrase has been set for the database at `path`
+pub fn has_passphrase<P: AsRef<Path>>(path: P) -> bool {
+    passphrase(path.as_ref()).is_some()
+}
+
+fn passphrase(path: &Path) -> Option<String> {
+    PASSPHRASES.read().unwrap_or_else(|e| e.into_inner()).get(path).cloned()
+}
+
+/// Whether a database file exists and is encrypted (lacks the plain SQLite header)
+pub fn is_encrypted<P: AsRef<Path>>(path: P) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Open a database file, unlocking it with `passphrase` if it is encrypted
+fn open_connection(path: &Path, flags: OpenFlags, passphrase: Option<&str>) -> Result<Connection> {
+    let encrypted = is_encrypted(path);
+    let conn = Connection::open_with_flags(path, flags)?;
+    if encrypted {
+        apply_key(&conn, passphrase.unwrap_or_default())?;
+    }
+    Ok(conn)
+}
+
+/// Set the SQLCipher key and fail now, rather than on first use, if it is wrong
+fn apply_key(conn: &Connection, passphrase: &str) -> Result<()> {
+    // SQLCipher otherwise logs a decryption error to stderr for a wrong key
+    conn.pragma_update(None, "cipher_log_level", "NONE")?;
+    conn.pragma_update(None, "key", passphrase)?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+}
+
+// ============= Storage =============
+
 /// SQLite database for storing readings
 pub struct Storage {
     conn: Connection,
+    /// Passphrase of the database file, if it is encrypted
+    passphrase: Option<String>,
 }
 
 impl Storage {
     /// Create or open a database at the given path
+    ///
+    /// Encrypted databases are unlocked with the passphrase set for their path
+    /// by [`set_passphrase`].
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let conn = Connection::open(path)?;
+        let path = path.as_ref();
+        let passphrase = is_encrypted(path).then(|| passphrase(path).unwrap_or_default());
+        let conn = open_connection(path, OpenFlags::default(), passphrase.as_deref())?;
         
         conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS readings (
@@ -56,7 +382,10 @@ impl Storage {
                 mmol_l REAL NOT NULL,
                 note TEXT,
                 tags TEXT,
-                imported_at TEXT DEFAULT CURRENT_TIMESTAMP
+                imported_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                excluded INTEGER NOT NULL DEFAULT 0,
+                exclusion_reason TEXT,
+                tag_source TEXT
             );
             
             CREATE INDEX IF NOT EXISTS idx_readings_epoch 
@@ -66,240 +395,654 @@ impl Storage {
                 ON readings(mg_dl);
                 
             CREATE INDEX IF NOT EXISTS idx_readings_timestamp 
-                ON readings(timestamp);"
+                ON readings(timestamp);
+            
+            CREATE TABLE IF NOT EXISTS reading_history (
+                id INTEGER PRIMARY KEY,
+                reading_id INTEGER NOT NULL,
+                field TEXT NOT NULL,
+                old_value TEXT,
+                new_value TEXT,
+                changed_at TEXT DEFAULT CURRENT_TIMESTAMP,
+                source TEXT NOT NULL
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_reading_history_reading 
+                ON reading_history(reading_id);
+            
+            CREATE TRIGGER IF NOT EXISTS reading_history_no_update 
+                BEFORE UPDATE ON reading_history
+                BEGIN SELECT RAISE(ABORT, 'reading_history is append-only'); END;
+            
+            CREATE TRIGGER IF NOT EXISTS reading_history_no_delete 
+                BEFORE DELETE ON reading_history
+                BEGIN SELECT RAISE(ABORT, 'reading_history is append-only'); END;
+            
+            CREATE TABLE IF NOT EXISTS sync_sessions (
+                id INTEGER PRIMARY KEY,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                meter TEXT,
+                serial TEXT,
+                received INTEGER NOT NULL DEFAULT 0,
+                inserted INTEGER NOT NULL DEFAULT 0,
+                duplicates INTEGER NOT NULL DEFAULT 0,
+                conflicts INTEGER NOT NULL DEFAULT 0,
+                rejected INTEGER NOT NULL DEFAULT 0,
+                clock_drift_secs INTEGER,
+                error TEXT
+            );
+            
+            CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY,
+                epoch INTEGER NOT NULL,
+                timestamp TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                amount REAL,
+                detail TEXT,
+                note TEXT,
+                reading_id INTEGER REFERENCES readings(id),
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_events_epoch 
+                ON events(epoch);"
         )?;
+
+        // Databases created before exclusion support lack these columns
+        add_column_if_missing(&conn, "readings", "excluded", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "readings", "exclusion_reason", "TEXT")?;
+        // Who set the tags: NULL (nobody yet), 'rule' or 'manual'
+        add_column_if_missing(&conn, "readings", "tag_source", "TEXT")?;
         
-        Ok(Self { conn })
+        Ok(Self { conn, passphrase })
     }
 
-    /// Insert a reading, ignoring duplicates based on epoch timestamp
-    pub fn insert_reading(&self, reading: &GlucoseReading) -> Result<Option<i64>> {
-        let result = self.conn.execute(
-            "INSERT OR IGNORE INTO readings (epoch, timestamp, mg_dl, mmol_l) 
-             VALUES (?1, ?2, ?3, ?4)",
-            params![
-                reading.epoch,
-                reading.timestamp,
-                reading.mg_dl,
-                reading.mmol_l,
-            ],
-        )?;
-        
-        if result > 0 {
-            Ok(Some(self.conn.last_insert_rowid()))
+    /// Whether the database file is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    /// Copy the database to a file using SQLite's online backup API
+    ///
+    /// Backups of an encrypted database are encrypted with the same passphrase.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut dst = Connection::open(path)?;
+        if let Some(passphrase) = &self.passphrase {
+            dst.pragma_update(None, "key", passphrase)?;
+        }
+        let backup = Backup::new(&self.conn, &mut dst)?;
+        backup.run_to_completion(100, Duration::ZERO, None)
+    }
+
+    /// Replace the database contents with those of a backup file
+    ///
+    /// The backup must be encrypted exactly when the database is, since SQLite's
+    /// backup API cannot copy between an encrypted and a plain database. An
+    /// encrypted backup is opened with the database's passphrase. A damaged
+    /// backup is refused.
+    pub fn restore_from<P: AsRef<Path>>(&mut self, path: P) -> std::result::Result<(), AccuChekError> {
+        match (is_encrypted(&path), self.is_encrypted()) {
+            (true, false) => {
+                return Err(AccuChekError::Restore(
+                    "the backup is encrypted (or not a database) but the database is not; run 'accuchek db encrypt' first".to_string()
+                ));
+            }
+            (false, true) => {
+                return Err(AccuChekError::Restore(
+                    "the backup is not encrypted but the database is; run 'accuchek db decrypt' first".to_string()
+                ));
+            }
+            _ => {}
+        }
+        let src = open_connection(path.as_ref(), OpenFlags::SQLITE_OPEN_READ_ONLY, self.passphrase.as_deref())?;
+        let problems = integrity_check(&src)?;
+        if !problems.is_empty() {
+            return Err(AccuChekError::Restore(format!("the backup failed the integrity check: {}", problems.join("; "))));
+        }
+        let backup = Backup::new(&src, &mut self.conn)?;
+        backup.run_to_completion(100, Duration::ZERO, None)?;
+        Ok(())
+    }
+
+    /// Rewrite a database file encrypted with `passphrase`, or unencrypted if `None`
+    ///
+    /// The file is opened with the passphrase set for it. On success the new
+    /// passphrase replaces it.
+    pub fn change_encryption<P: AsRef<Path>>(path: P, passphrase: Option<&str>) -> std::result::Result<(), AccuChekError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("db.tmp");
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+        }
+
+        {
+            let storage = Self::new(path)?;
+            storage.conn.execute(
+                "ATTACH DATABASE ?1 AS converted KEY ?2",
+                params![tmp.to_string_lossy(), passphrase.unwrap_or_default()],
+            )?;
+            storage.conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()))?;
+            storage.conn.execute("DETACH DATABASE converted", [])?;
+        }
+
+        fs::rename(&tmp, path)?;
+        set_passphrase(path, passphrase.map(str::to_string));
+        Ok(())
+    }
+
+    /// Run `PRAGMA integrity_check`, returning the problems found (empty if healthy)
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        integrity_check(&self.conn)
+    }
+
+    /// Write a timestamped backup into `dir`, keeping only the newest `keep` files
+    pub fn rotating_backup(&self, dir: &Path, keep: usize) -> std::result::Result<PathBuf, AccuChekError> {
+        fs::create_dir_all(dir)?;
+        // Step past a backup written in the same millisecond so the name stays in order
+        let mut time = chrono::Local::now();
+        let path = loop {
+            let path = dir.join(format!("accuchek-{}.db", time.format("%Y%m%d-%H%M%S-%3f")));
+            if !path.exists() {
+                break path;
+            }
+            time += chrono::Duration::milliseconds(1);
+        };
+        self.backup_to(&path)?;
+
+        // Timestamped names sort chronologically, so the oldest come first
+        let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("accuchek-") && n.ends_with(".db"))
+            })
+            .collect();
+        backups.sort();
+
+        let excess = backups.len().saturating_sub(keep.max(1));
+        for old in &backups[..excess] {
+            fs::remove_file(old)?;
+        }
+
+        Ok(path)
+    }
+
+    /// Rotating backup before a change to the database, skipped while it holds no readings
+    pub fn auto_backup(&self, dir: &Path, keep: usize) -> std::result::Result<Option<PathBuf>, AccuChekError> {
+        if self.count()? == 0 {
+            return Ok(None);
+        }
+        self.rotating_backup(dir, keep).map(Some)
+    }
+
+    /// Import readings, notes, tags and exclusions from another database
+    ///
+    /// Readings are matched by epoch. Annotations missing on our side are adopted;
+    /// differing annotations and values are reported and left untouched. An
+    /// encrypted file is opened with the passphrase set for it, else with ours.
+    pub fn merge_from<P: AsRef<Path>>(&self, path: P) -> Result<MergeReport> {
+        let path = path.as_ref();
+        let passphrase = passphrase(path).or_else(|| self.passphrase.clone());
+        let other = open_connection(path, OpenFlags::SQLITE_OPEN_READ_ONLY, passphrase.as_deref())?;
+        let exclusion_columns = if has_column(&other, "readings", "excluded")? {
+            "excluded, exclusion_reason"
+        } else {
+            "0, NULL"
+        };
+        let mut stmt = other.prepare(&format!(
+            "SELECT id, epoch, timestamp, mg_dl, mmol_l, note, tags, imported_at, {} 
+             FROM readings ORDER BY epoch",
+            exclusion_columns
+        ))?;
+        let theirs = stmt.query_map([], Self::row_to_stored_reading)?
+            .collect::<Result<Vec<_>>>()?;
+        // Who set their tags, carried over with the tags so rules keep off manual ones
+        let tag_sources: BTreeMap<i64, Option<String>> = if has_column(&other, "readings", "tag_source")? {
+            other.prepare("SELECT id, tag_source FROM readings")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<_>>()?
         } else {
-            Ok(None) // Duplicate, not inserted
+            BTreeMap::new()
+        };
+        let tag_source = |reading: &StoredReading| tag_sources.get(&reading.id).cloned().flatten();
+
+        let mut report = MergeReport::default();
+        let tx = self.conn.unchecked_transaction()?;
+
+        for reading in &theirs {
+            let ours: Option<StoredReading> = tx
+                .prepare_cached(
+                    "SELECT id, epoch, timestamp, mg_dl, mmol_l, note, tags, imported_at, excluded, exclusion_reason 
+                     FROM readings WHERE epoch = ?1"
+                )?
+                .query_row(params![reading.epoch], Self::row_to_stored_reading)
+                .optional()?;
+
+            let Some(ours) = ours else {
+                tx.prepare_cached(
+                    "INSERT INTO readings (epoch, timestamp, mg_dl, mmol_l, note, tags, tag_source, excluded, exclusion_reason) 
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+                )?
+                .execute(params![
+                    reading.epoch,
+                    reading.timestamp,
+                    reading.mg_dl,
+                    reading.mmol_l,
+                    reading.note,
+                    reading.tags,
+                    tag_source(reading),
+                    reading.excluded,
+                    reading.exclusion_reason,
+                ])?;
+                let id = tx.last_insert_rowid();
+                self.record_change(id, "note", None, reading.note.as_deref(), ChangeSource::Import)?;
+                self.record_change(id, "tags", None, reading.tags.as_deref(), ChangeSource::Import)?;
+                if reading.excluded {
+                    self.record_change(id, "excluded", None, Some(reading.exclusion_reason.as_deref().unwrap_or_default()), ChangeSource::Import)?;
+                }
+                report.inserted += 1;
+                continue;
+            };
+
+            if ours.mg_dl != reading.mg_dl {
+                report.value_conflicts.push(MergeConflict {
+                    timestamp: ours.timestamp.clone(),
+                    field: "value",
+                    ours: format!("{} mg/dL", ours.mg_dl),
+                    theirs: format!("{} mg/dL", reading.mg_dl),
+                });
+                continue;
+            }
+            report.duplicates += 1;
+
+            let our_note = ours.note.as_deref().filter(|n| !n.is_empty());
+            match (our_note, reading.note.as_deref().filter(|n| !n.is_empty())) {
+                (None, Some(theirs)) => {
+                    tx.execute("UPDATE readings SET note = ?1 WHERE id = ?2", params![theirs, ours.id])?;
+                    self.record_change(ours.id, "note", ours.note.as_deref(), Some(theirs), ChangeSource::Import)?;
+                    report.annotations_adopted += 1;
+                }
+                (Some(mine), Some(theirs)) if mine != theirs => {
+                    report.annotation_conflicts.push(MergeConflict {
+                        timestamp: ours.timestamp.clone(),
+                        field: "note",
+                        ours: mine.to_string(),
+                        theirs: theirs.to_string(),
+                    });
+                }
+                _ => {}
+            }
+
+            let our_tags = ours.tags.as_deref().filter(|t| !t.is_empty());
+            match (our_tags, reading.tags.as_deref().filter(|t| !t.is_empty())) {
+                (None, Some(theirs)) => {
+                    tx.execute(
+                        "UPDATE readings SET tags = ?1, tag_source = ?2 WHERE id = ?3",
+                        params![theirs, tag_source(reading), ours.id],
+                    )?;
+                    self.record_change(ours.id, "tags", ours.tags.as_deref(), Some(theirs), ChangeSource::Import)?;
+                    report.annotations_adopted += 1;
+                }
+                (Some(mine), Some(theirs)) if normalize_tags(mine) != normalize_tags(theirs) => {
+                    report.annotation_conflicts.push(MergeConflict {
+                        timestamp: ours.timestamp.clone(),
+                        field: "tags",
+                        ours: mine.to_string(),
+                        theirs: theirs.to_string(),
+                    });
+                }
+                _ => {}
+            }
+
+            if reading.excluded && !ours.excluded {
+                let reason = reading.exclusion_reason.as_deref().unwrap_or_default();
+                tx.execute(
+                    "UPDATE readings SET excluded = 1, exclusion_reason = ?1 WHERE id = ?2",
+                    params![reason, ours.id],
+                )?;
+                self.record_change(ours.id, "excluded", None, Some(reason), ChangeSource::Import)?;
+                report.annotations_adopted += 1;
+            }
         }
+
+        tx.commit()?;
+        Ok(report)
     }
 
-    /// Bulk import readings, returns count of new entries
-    pub fn import_readings(&self, readings: &[GlucoseReading]) -> Result<usize> {
-        let mut count = 0;
-        for reading in readings {
-            if self.insert_reading(reading)?.is_some() {
-                count += 1;
+    /// Bulk import readings in a single transaction, ignoring duplicates based on epoch
+    pub fn import_readings(&self, readings: &[GlucoseReading]) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR IGNORE INTO readings (epoch, timestamp, mg_dl, mmol_l) 
+                 VALUES (?1, ?2, ?3, ?4)"
+            )?;
+            let mut existing = tx.prepare_cached("SELECT mg_dl FROM readings WHERE epoch = ?1")?;
+
+            for reading in readings {
+                if reading.epoch <= 0 || !(MIN_VALID_MGDL..=MAX_VALID_MGDL).contains(&reading.mg_dl) {
+                    log::warn!("Rejected reading {} ({} mg/dL)", reading.timestamp, reading.mg_dl);
+                    report.rejected += 1;
+                    continue;
+                }
+
+                let inserted = insert.execute(params![
+                    reading.epoch,
+                    reading.timestamp,
+                    reading.mg_dl,
+                    reading.mmol_l,
+                ])?;
+                if inserted > 0 {
+                    report.inserted += 1;
+                    continue;
+                }
+
+                let stored: u16 = existing.query_row(params![reading.epoch], |row| row.get(0))?;
+                if stored == reading.mg_dl {
+                    report.duplicates += 1;
+                } else {
+                    log::warn!("Reading {} already stored as {} mg/dL, meter reports {}", reading.timestamp, stored, reading.mg_dl);
+                    report.conflicts += 1;
+                }
             }
         }
-        Ok(count)
+        tx.commit()?;
+        Ok(report)
     }
 
     /// Update note for a reading by database ID
-    pub fn update_note(&self, id: i64, note: &str) -> Result<usize> {
-        let updated = self.conn.execute(
+    pub fn update_note(&self, id: i64, note: &str, source: ChangeSource) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let old: Option<Option<String>> = tx
+            .query_row("SELECT note FROM readings WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        
+        let updated = tx.execute(
             "UPDATE readings SET note = ?1 WHERE id = ?2",
             params![note, id],
         )?;
+        if let Some(old) = old {
+            self.record_change(id, "note", old.as_deref(), Some(note), source)?;
+        }
+        tx.commit()?;
         Ok(updated)
     }
 
     /// Add tags to a reading
-    pub fn update_tags(&self, id: i64, tags: &str) -> Result<usize> {
-        let updated = self.conn.execute(
-            "UPDATE readings SET tags = ?1 WHERE id = ?2",
+    pub fn update_tags(&self, id: i64, tags: &str, source: ChangeSource) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let old: Option<Option<String>> = tx
+            .query_row("SELECT tags FROM readings WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        
+        let updated = tx.execute(
+            "UPDATE readings SET tags = ?1, tag_source = 'manual' WHERE id = ?2",
             params![tags, id],
         )?;
+        if let Some(old) = old {
+            self.record_change(id, "tags", old.as_deref(), Some(tags), source)?;
+        }
+        tx.commit()?;
         Ok(updated)
     }
 
-    /// Get all readings
-    pub fn get_all_readings(&self) -> Result<Vec<StoredReading>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, epoch, timestamp, mg_dl, mmol_l, note, tags, imported_at 
-             FROM readings ORDER BY epoch"
-        )?;
+    /// Apply automatic tagging rules, returning the number of readings retagged
+    ///
+    /// Readings tagged by hand are never changed. On import only untagged
+    /// readings are considered; with `rerun` readings tagged by an earlier
+    /// run are re-evaluated too, so edited rules take effect on history.
+    pub fn apply_tag_rules(&self, rules: &[TagRule], rerun: bool) -> Result<usize> {
+        let readings = self.get_all_readings()?;
+        let carb_epochs: Vec<i64> = self.get_events()?
+            .iter()
+            .filter(|e| e.kind == EventKind::Carbs)
+            .map(|e| e.epoch)
+            .collect();
+        let tags = tagging::evaluate(rules, &readings, &carb_epochs);
 
-        let readings = stmt.query_map([], Self::row_to_stored_reading)?
-            .collect::<Result<Vec<_>>>()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let mut changed = 0;
+        for (reading, tag) in readings.iter().zip(tags) {
+            let source: Option<String> = tx
+                .prepare_cached("SELECT tag_source FROM readings WHERE id = ?1")?
+                .query_row(params![reading.id], |row| row.get(0))?;
+            let untagged = source.is_none() && reading.tags.as_deref().unwrap_or_default().is_empty();
+            let eligible = untagged || (rerun && source.as_deref() == Some("rule"));
+            if !eligible || reading.tags.as_deref().filter(|t| !t.is_empty()) == tag.as_deref() {
+                continue;
+            }
 
-        Ok(readings)
+            tx.prepare_cached("UPDATE readings SET tags = ?1, tag_source = ?2 WHERE id = ?3")?
+                .execute(params![tag, tag.as_ref().map(|_| "rule"), reading.id])?;
+            self.record_change(reading.id, "tags", reading.tags.as_deref(), tag.as_deref(), ChangeSource::Rules)?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
     }
 
-    /// Get total reading count
-    pub fn count(&self) -> Result<i64> {
-        self.conn.query_row("SELECT COUNT(*) FROM readings", [], |row| row.get(0))
+    /// Mark a reading as excluded from statistics, keeping the row
+    pub fn exclude_reading(&self, id: i64, reason: &str, source: ChangeSource) -> Result<usize> {
+        self.set_exclusion(id, Some(reason), source)
     }
 
-    /// Get all mg/dL values as a vector
-    pub fn get_all_values(&self) -> Result<Vec<u16>> {
-        let mut stmt = self.conn.prepare("SELECT mg_dl FROM readings ORDER BY epoch")?;
-        let values = stmt.query_map([], |row| row.get::<_, u16>(0))?
-            .collect::<Result<Vec<_>>>()?;
-        Ok(values)
+    /// Clear the exclusion flag of a reading
+    pub fn restore_reading(&self, id: i64, source: ChangeSource) -> Result<usize> {
+        self.set_exclusion(id, None, source)
     }
 
-    /// Get all values in both units
-    pub fn get_all_values_both(&self) -> Result<(Vec<u16>, Vec<f64>)> {
-        let mut stmt = self.conn.prepare("SELECT mg_dl, mmol_l FROM readings ORDER BY epoch")?;
-        let rows = stmt.query_map([], |row| {
-            Ok((row.get::<_, u16>(0)?, row.get::<_, f64>(1)?))
-        })?;
+    /// Set or clear the exclusion of a reading, recording the change
+    fn set_exclusion(&self, id: i64, reason: Option<&str>, source: ChangeSource) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let old: Option<(bool, Option<String>)> = tx
+            .query_row(
+                "SELECT excluded, exclusion_reason FROM readings WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
         
-        let mut mgdl: Vec<u16> = Vec::new();
-        let mut mmol: Vec<f64> = Vec::new();
-        for row in rows {
-            let (mg, mm) = row?;
-            mgdl.push(mg);
-            mmol.push(mm);
+        let updated = tx.execute(
+            "UPDATE readings SET excluded = ?1, exclusion_reason = ?2 WHERE id = ?3",
+            params![reason.is_some(), reason, id],
+        )?;
+        if let Some((was_excluded, old_reason)) = old {
+            // An excluded reading without a reason is still recorded as excluded
+            let old_value = if was_excluded { Some(old_reason.unwrap_or_default()) } else { None };
+            self.record_change(id, "excluded", old_value.as_deref(), reason, source)?;
         }
-        Ok((mgdl, mmol))
+        tx.commit()?;
+        Ok(updated)
     }
 
-    /// Get basic statistics
-    pub fn get_basic_stats(&self) -> Result<Option<BasicStats>> {
-        let (mgdl, mmol) = self.get_all_values_both()?;
-        Ok(BasicStats::from_values(&mgdl, &mmol))
+    /// Append an entry to the audit log if the value actually changed
+    fn record_change(&self, reading_id: i64, field: &str, old: Option<&str>, new: Option<&str>, source: ChangeSource) -> Result<()> {
+        // Treat an empty note or tag list the same as none at all
+        let old = old.filter(|v| !v.is_empty() || field == "excluded");
+        let new = new.filter(|v| !v.is_empty() || field == "excluded");
+        if old == new {
+            return Ok(());
+        }
+        self.conn.execute(
+            "INSERT INTO reading_history (reading_id, field, old_value, new_value, source) 
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![reading_id, field, old, new, source.as_str()],
+        )?;
+        Ok(())
     }
 
-    /// Get time-in-range statistics
-    pub fn get_time_in_range(&self, thresholds: Thresholds) -> Result<TimeInRange> {
-        let values = self.get_all_values()?;
-        Ok(TimeInRange::from_values(&values, thresholds))
+    /// Get the change history of a reading, oldest first
+    pub fn get_history(&self, reading_id: i64) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, reading_id, field, old_value, new_value, changed_at, source 
+             FROM reading_history WHERE reading_id = ?1 ORDER BY id"
+        )?;
+
+        let entries = stmt.query_map(params![reading_id], |row| {
+            Ok(HistoryEntry {
+                id: row.get(0)?,
+                reading_id: row.get(1)?,
+                field: row.get(2)?,
+                old_value: row.get(3)?,
+                new_value: row.get(4)?,
+                changed_at: row.get(5)?,
+                source: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+        Ok(entries)
     }
 
-    /// Get daily statistics
-    pub fn get_daily_stats(&self, thresholds: Thresholds) -> Result<Vec<DailyStats>> {
-        let readings = self.get_all_readings()?;
-        
-        use std::collections::BTreeMap;
-        let mut daily_readings: BTreeMap<String, (Vec<u16>, Vec<f64>)> = BTreeMap::new();
-
-        for reading in &readings {
-            if let Some(date) = reading.timestamp.get(0..10) {
-                let entry = daily_readings.entry(date.to_string()).or_default();
-                entry.0.push(reading.mg_dl);
-                entry.1.push(reading.mmol_l);
-            }
-        }
+    /// Store a finished sync session
+    pub fn record_sync_session(&self, session: &SyncSession) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO sync_sessions (started_at, finished_at, meter, serial, received, inserted, 
+                                        duplicates, conflicts, rejected, clock_drift_secs, error) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                session.started_at,
+                session.finished_at,
+                session.meter,
+                session.serial,
+                session.received as i64,
+                session.inserted as i64,
+                session.duplicates as i64,
+                session.conflicts as i64,
+                session.rejected as i64,
+                session.clock_drift_secs,
+                session.error,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
 
-        Ok(daily_readings.into_iter()
-            .map(|(date, (mgdl, mmol))| DailyStats::new(date, &mgdl, &mmol, thresholds))
-            .collect())
+    /// Get all sync sessions, newest first
+    pub fn get_sync_sessions(&self) -> Result<Vec<SyncSession>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, started_at, finished_at, meter, serial, received, inserted, 
+                    duplicates, conflicts, rejected, clock_drift_secs, error 
+             FROM sync_sessions ORDER BY started_at DESC, id DESC"
+        )?;
+
+        let sessions = stmt.query_map([], |row| {
+            Ok(SyncSession {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                finished_at: row.get(2)?,
+                meter: row.get(3)?,
+                serial: row.get(4)?,
+                received: row.get::<_, i64>(5)? as usize,
+                inserted: row.get::<_, i64>(6)? as usize,
+                duplicates: row.get::<_, i64>(7)? as usize,
+                conflicts: row.get::<_, i64>(8)? as usize,
+                rejected: row.get::<_, i64>(9)? as usize,
+                clock_drift_secs: row.get(10)?,
+                error: row.get(11)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+        Ok(sessions)
     }
 
-    /// Get hourly statistics
-    pub fn get_hourly_stats(&self) -> Result<Vec<HourlyStats>> {
-        let readings = self.get_all_readings()?;
-        let mut hourly_data: Vec<(Vec<u16>, Vec<f64>)> = vec![(Vec::new(), Vec::new()); 24];
-
-        for reading in &readings {
-            if let Some(hour_str) = reading.timestamp.get(11..13) {
-                if let Ok(hour) = hour_str.parse::<usize>() {
-                    if hour < 24 {
-                        hourly_data[hour].0.push(reading.mg_dl);
-                        hourly_data[hour].1.push(reading.mmol_l);
-                    }
-                }
-            }
-        }
+    /// Store an event, returning its ID
+    pub fn add_event(&self, event: &Event) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO events (epoch, timestamp, kind, amount, detail, note, reading_id) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                event.epoch,
+                event.timestamp,
+                event.kind.as_str(),
+                event.amount,
+                event.detail,
+                event.note,
+                event.reading_id,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
 
-        Ok(hourly_data.into_iter()
-            .enumerate()
-            .map(|(hour, (mgdl, mmol))| HourlyStats::new(hour as u8, mgdl, mmol))
-            .collect())
+    /// Delete an event, returning the number of rows removed
+    pub fn delete_event(&self, id: i64) -> Result<usize> {
+        self.conn.execute("DELETE FROM events WHERE id = ?1", params![id])
     }
 
-    /// Get time bin statistics
-    pub fn get_time_bin_stats(&self) -> Result<Vec<TimeBinStats>> {
-        let readings = self.get_all_readings()?;
-        
-        let bins = [
-            ("Overnight", "12AM-6AM", 0u8, 6u8),
-            ("Fasting/Morning", "6AM-9AM", 6, 9),
-            ("Mid-Morning", "9AM-12PM", 9, 12),
-            ("Afternoon", "12PM-6PM", 12, 18),
-            ("Evening", "6PM-9PM", 18, 21),
-            ("Night", "9PM-12AM", 21, 24),
-        ];
-
-        Ok(bins.iter().map(|(name, desc, start, end)| {
-            let filtered: Vec<_> = readings.iter()
-                .filter(|r| {
-                    if let Some(hour_str) = r.timestamp.get(11..13) {
-                        if let Ok(hour) = hour_str.parse::<u8>() {
-                            return hour >= *start && hour < *end;
-                        }
-                    }
-                    false
-                })
-                .collect();
-            let mgdl: Vec<u16> = filtered.iter().map(|r| r.mg_dl).collect();
-            let mmol: Vec<f64> = filtered.iter().map(|r| r.mmol_l).collect();
-            TimeBinStats::new(name, desc, *start, *end, mgdl, mmol)
-        }).collect())
-    }
-
-    /// Get histogram bins
-    pub fn get_histogram(&self, bin_width: u16) -> Result<Vec<HistogramBin>> {
-        let readings = self.get_all_readings()?;
-        if readings.is_empty() {
-            return Ok(Vec::new());
-        }
+    /// Get all events, oldest first
+    pub fn get_events(&self) -> Result<Vec<Event>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, epoch, timestamp, kind, amount, detail, note, reading_id 
+             FROM events ORDER BY epoch, id"
+        )?;
 
-        let mut bins: Vec<HistogramBin> = Vec::new();
-        let mut start = 40u16;
-        let total = readings.len();
-
-        while start < 400 {
-            let end = start + bin_width;
-            let count = readings.iter().filter(|r| r.mg_dl >= start && r.mg_dl < end).count();
-            bins.push(HistogramBin {
-                range_start: start,
-                range_end: end,
-                count,
-                percentage: (count as f64 / total as f64) * 100.0,
-            });
-            start = end;
-        }
+        let events = stmt.query_map([], |row| {
+            let kind: String = row.get(3)?;
+            Ok(Event {
+                id: row.get(0)?,
+                epoch: row.get(1)?,
+                timestamp: row.get(2)?,
+                kind: EventKind::parse(&kind).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, format!("unknown event kind {}", kind).into())
+                })?,
+                amount: row.get(4)?,
+                detail: row.get(5)?,
+                note: row.get(6)?,
+                reading_id: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
 
-        Ok(bins)
+        Ok(events)
     }
 
-    /// Get calendar data
-    pub fn get_calendar_data(&self, thresholds: Thresholds) -> Result<Vec<CalendarDay>> {
-        let readings = self.get_all_readings()?;
-        if readings.is_empty() {
-            return Ok(Vec::new());
-        }
+    /// ID of the reading closest in time to `epoch`, if one lies within `window_secs`
+    pub fn nearest_reading(&self, epoch: i64, window_secs: i64) -> Result<Option<i64>> {
+        self.conn.query_row(
+            "SELECT id FROM readings WHERE ABS(epoch - ?1) <= ?2 
+             ORDER BY ABS(epoch - ?1) LIMIT 1",
+            params![epoch, window_secs],
+            |row| row.get(0),
+        ).optional()
+    }
 
-        use std::collections::BTreeMap;
-        let mut daily_readings: BTreeMap<String, Vec<(u8, u16, f64)>> = BTreeMap::new();
+    /// Get all readings, including excluded ones
+    pub fn get_all_readings(&self) -> Result<Vec<StoredReading>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, epoch, timestamp, mg_dl, mmol_l, note, tags, imported_at, excluded, exclusion_reason 
+             FROM readings ORDER BY epoch"
+        )?;
 
-        for reading in &readings {
-            if let (Some(date), Some(hour_str)) = (reading.timestamp.get(0..10), reading.timestamp.get(11..13)) {
-                if let Ok(hour) = hour_str.parse::<u8>() {
-                    daily_readings.entry(date.to_string())
-                        .or_default()
-                        .push((hour, reading.mg_dl, reading.mmol_l));
-                }
-            }
-        }
+        let readings = stmt.query_map([], Self::row_to_stored_reading)?
+            .collect::<Result<Vec<_>>>()?;
 
-        let results: Vec<CalendarDay> = daily_readings.into_iter().map(|(date, readings)| {
-            let (day_of_week, week_of_year) = parse_date_info(&date);
-            CalendarDay::new(date, day_of_week, week_of_year, readings, thresholds)
-        }).collect();
+        Ok(readings)
+    }
 
-        Ok(results)
+    /// Get readings used for statistics, leaving out excluded ones
+    pub fn get_readings(&self) -> Result<Vec<StoredReading>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, epoch, timestamp, mg_dl, mmol_l, note, tags, imported_at, excluded, exclusion_reason 
+             FROM readings WHERE excluded = 0 ORDER BY epoch"
+        )?;
+
+        let readings = stmt.query_map([], Self::row_to_stored_reading)?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(readings)
+    }
+
+    /// Get total reading count
+    pub fn count(&self) -> Result<i64> {
+        self.conn.query_row("SELECT COUNT(*) FROM readings", [], |row| row.get(0))
+    }
+
+    /// Get count of excluded readings
+    pub fn count_excluded(&self) -> Result<i64> {
+        self.conn.query_row("SELECT COUNT(*) FROM readings WHERE excluded = 1", [], |row| row.get(0))
     }
 
     fn row_to_stored_reading(row: &rusqlite::Row) -> Result<StoredReading> {
@@ -312,21 +1055,380 @@ impl Storage {
             note: row.get(5)?,
             tags: row.get(6)?,
             imported_at: row.get(7)?,
+            excluded: row.get(8)?,
+            exclusion_reason: row.get(9)?,
         })
     }
 }
 
-/// Parse date string to get day of week and week of year
-fn parse_date_info(date: &str) -> (u8, u32) {
-    // Try YYYY-MM-DD format
-    if let Ok(parsed) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
-        use chrono::Datelike;
-        return (parsed.weekday().num_days_from_monday() as u8, parsed.iso_week().week());
+/// Convert a "YYYY/MM/DD HH:MM" timestamp to epoch seconds (local time taken as UTC)
+pub fn timestamp_to_epoch(timestamp: &str) -> Option<i64> {
+    parse_timestamp(timestamp).map(|dt| dt.and_utc().timestamp())
+}
+
+fn integrity_check(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let messages = stmt.query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
+
+/// Sorted, trimmed tag list so that "a,b" and "b, a" compare equal
+fn normalize_tags(tags: &str) -> Vec<&str> {
+    let mut list: Vec<&str> = tags.split(',').map(str::trim).filter(|t| !t.is_empty()).collect();
+    list.sort_unstable();
+    list.dedup();
+    list
+}
+
+/// Check whether a table has a given column
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    Ok(exists)
+}
+
+/// Add a column to an existing table if an older schema lacks it
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(timestamp: &str, mg_dl: u16) -> GlucoseReading {
+        GlucoseReading {
+            id: 0,
+            epoch: timestamp_to_epoch(timestamp).unwrap_or(0),
+            timestamp: timestamp.to_string(),
+            mg_dl,
+            mmol_l: mg_dl as f64 / 18.0,
+        }
+    }
+
+    fn memory_storage(readings: &[GlucoseReading]) -> Storage {
+        let storage = Storage::new(":memory:").unwrap();
+        storage.import_readings(readings).unwrap();
+        storage
+    }
+
+    /// Empty scratch directory under the system temp directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("accuchek-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
     }
-    // Try YYYY/MM/DD format
-    if let Ok(parsed) = chrono::NaiveDate::parse_from_str(date, "%Y/%m/%d") {
-        use chrono::Datelike;
-        return (parsed.weekday().num_days_from_monday() as u8, parsed.iso_week().week());
+
+    #[test]
+    fn test_import_report() {
+        let batch = || vec![reading("2024/01/01 08:00", 100), reading("2024/01/01 12:00", 140)];
+        let storage = memory_storage(&[]);
+        let first = storage.import_readings(&batch()).unwrap();
+        assert_eq!((first.inserted, first.duplicates, first.conflicts, first.rejected), (2, 0, 0, 0));
+
+        let mut second = batch();
+        second.push(reading("2024/01/01 08:00", 105));
+        second.push(reading("2024/01/01 18:00", 601));
+        second.push(reading("2024/01/01 19:00", 9));
+        second.push(reading("2024/01/01 20:00", 600));
+        second.push(reading("not a date", 120));
+        let report = storage.import_readings(&second).unwrap();
+        assert_eq!((report.inserted, report.duplicates, report.conflicts, report.rejected), (1, 2, 1, 3));
+
+        // The stored value wins over a conflicting one
+        let readings = storage.get_readings().unwrap();
+        assert_eq!(readings.len(), 3);
+        assert_eq!(readings[0].mg_dl, 100);
+    }
+
+    #[test]
+    fn test_exclude_and_restore() {
+        let storage = memory_storage(&[reading("2024/01/01 08:00", 100), reading("2024/01/01 12:00", 250)]);
+        let id = storage.get_readings().unwrap()[1].id;
+
+        storage.exclude_reading(id, "meter error", ChangeSource::Cli).unwrap();
+        let readings = storage.get_readings().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].mg_dl, 100);
+        assert_eq!(storage.get_all_readings().unwrap().len(), 2);
+        assert_eq!(storage.count_excluded().unwrap(), 1);
+
+        storage.restore_reading(id, ChangeSource::Cli).unwrap();
+        assert_eq!(storage.get_readings().unwrap().len(), 2);
+        assert_eq!(storage.count_excluded().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_history() {
+        let storage = memory_storage(&[reading("2024/01/01 08:00", 100)]);
+        let id = storage.get_readings().unwrap()[0].id;
+
+        storage.update_note(id, "after run", ChangeSource::Gui).unwrap();
+        storage.update_note(id, "after run", ChangeSource::Gui).unwrap();
+        storage.update_tags(id, "exercise", ChangeSource::Cli).unwrap();
+        storage.exclude_reading(id, "", ChangeSource::Cli).unwrap();
+        storage.restore_reading(id, ChangeSource::Gui).unwrap();
+
+        let history = storage.get_history(id).unwrap();
+        let changes: Vec<(&str, Option<&str>, Option<&str>, &str)> = history.iter()
+            .map(|h| (h.field.as_str(), h.old_value.as_deref(), h.new_value.as_deref(), h.source.as_str()))
+            .collect();
+        assert_eq!(changes, vec![
+            ("note", None, Some("after run"), "gui"),
+            ("tags", None, Some("exercise"), "cli"),
+            ("excluded", None, Some(""), "cli"),
+            ("excluded", Some(""), None, "gui"),
+        ]);
+
+        // The audit log is append-only
+        assert!(storage.conn.execute("UPDATE reading_history SET new_value = 'x'", []).is_err());
+        assert!(storage.conn.execute("DELETE FROM reading_history", []).is_err());
+        assert_eq!(storage.get_history(id).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = temp_dir("backup");
+        let file = dir.join("backup.db");
+        let mut storage = memory_storage(&[reading("2024/01/01 08:00", 100), reading("2024/01/01 12:00", 140)]);
+        storage.backup_to(&file).unwrap();
+
+        storage.import_readings(&[reading("2024/01/02 08:00", 90)]).unwrap();
+        assert_eq!(storage.count().unwrap(), 3);
+        storage.restore_from(&file).unwrap();
+        assert_eq!(storage.count().unwrap(), 2);
+        assert!(storage.integrity_check().unwrap().is_empty());
+
+        // A file that is not a database is refused
+        let garbage = dir.join("garbage.db");
+        fs::write(&garbage, vec![0x5a; 4096]).unwrap();
+        assert!(storage.restore_from(&garbage).is_err());
+        assert_eq!(storage.count().unwrap(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_change_encryption() {
+        let dir = temp_dir("encryption");
+        let path = dir.join("accuchek.db");
+        let plain_backup = dir.join("plain.db");
+        Storage::new(&path).unwrap().import_readings(&[reading("2024/01/01 08:00", 100)]).unwrap();
+        Storage::new(&path).unwrap().backup_to(&plain_backup).unwrap();
+
+        Storage::change_encryption(&path, Some("secret")).unwrap();
+        assert!(is_encrypted(&path));
+        let mut storage = Storage::new(&path).unwrap();
+        assert!(storage.is_encrypted());
+        assert_eq!(storage.count().unwrap(), 1);
+        assert!(matches!(storage.restore_from(&plain_backup), Err(AccuChekError::Restore(_))));
+
+        // An encrypted backup restores into the encrypted database
+        let encrypted_backup = dir.join("encrypted.db");
+        storage.backup_to(&encrypted_backup).unwrap();
+        assert!(is_encrypted(&encrypted_backup));
+        storage.restore_from(&encrypted_backup).unwrap();
+        drop(storage);
+
+        Storage::change_encryption(&path, None).unwrap();
+        assert!(!is_encrypted(&path));
+        let mut storage = Storage::new(&path).unwrap();
+        assert_eq!(storage.count().unwrap(), 1);
+        assert!(matches!(storage.restore_from(&encrypted_backup), Err(AccuChekError::Restore(_))));
+        storage.restore_from(&plain_backup).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_backup() {
+        let dir = temp_dir("rotate");
+        let storage = memory_storage(&[reading("2024/01/01 08:00", 100)]);
+        let paths: Vec<PathBuf> = (0..4).map(|_| storage.rotating_backup(&dir, 2).unwrap()).collect();
+
+        // Backups made within the same second get distinct, ordered names
+        let mut sorted = paths.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted, paths);
+        let mut kept: Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        kept.sort();
+        assert_eq!(kept, paths[2..]);
+
+        assert!(Storage::new(":memory:").unwrap().auto_backup(&dir, 2).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = temp_dir("merge");
+        let file = dir.join("other.db");
+        let ours = memory_storage(&[
+            reading("2024/01/01 08:00", 100),
+            reading("2024/01/01 12:00", 140),
+            reading("2024/01/01 18:00", 160),
+            reading("2024/01/01 22:00", 120),
+        ]);
+        let theirs = Storage::new(&file).unwrap();
+        theirs.import_readings(&[
+            reading("2024/01/01 08:00", 100),
+            reading("2024/01/01 12:00", 145),
+            reading("2024/01/01 18:00", 160),
+            reading("2024/01/01 22:00", 120),
+            reading("2024/01/02 08:00", 95),
+        ]).unwrap();
+
+        let id = |storage: &Storage, timestamp: &str| {
+            storage.get_all_readings().unwrap().into_iter().find(|r| r.timestamp == timestamp).unwrap().id
+        };
+        // 08:00: note only on their side; 18:00: same tags in another order; 22:00: different notes;
+        // 2024/01/02 08:00: tagged by a rule on their side
+        theirs.update_note(id(&theirs, "2024/01/01 08:00"), "fasting", ChangeSource::Gui).unwrap();
+        theirs.update_tags(id(&theirs, "2024/01/01 08:00"), "fasting", ChangeSource::Gui).unwrap();
+        ours.update_tags(id(&ours, "2024/01/01 18:00"), "dinner,exercise", ChangeSource::Gui).unwrap();
+        theirs.update_tags(id(&theirs, "2024/01/01 18:00"), "exercise, dinner", ChangeSource::Gui).unwrap();
+        ours.update_note(id(&ours, "2024/01/01 22:00"), "snack", ChangeSource::Gui).unwrap();
+        theirs.update_note(id(&theirs, "2024/01/01 22:00"), "late snack", ChangeSource::Gui).unwrap();
+        let breakfast = tagging::RuleCondition::InWindow { start_minute: 6 * 60, end_minute: 10 * 60 };
+        assert_eq!(theirs.apply_tag_rules(&[TagRule::new("breakfast", breakfast)], false).unwrap(), 1);
+        drop(theirs);
+
+        let report = ours.merge_from(&file).unwrap();
+        assert_eq!(report.inserted, 1);
+        assert_eq!(report.duplicates, 3);
+        assert_eq!(report.annotations_adopted, 2);
+        assert_eq!(report.value_conflicts.len(), 1);
+        assert_eq!(report.value_conflicts[0].timestamp, "2024/01/01 12:00");
+        assert_eq!(report.value_conflicts[0].theirs, "145 mg/dL");
+        assert_eq!(report.annotation_conflicts.len(), 1);
+        assert_eq!(report.annotation_conflicts[0].field, "note");
+        assert_eq!(report.annotation_conflicts[0].ours, "snack");
+
+        let merged = ours.get_all_readings().unwrap();
+        assert_eq!(merged.len(), 5);
+        assert_eq!(merged[0].note.as_deref(), Some("fasting"));
+        assert_eq!(merged[0].tags.as_deref(), Some("fasting"));
+        assert_eq!(merged[1].mg_dl, 140);
+        assert_eq!(merged[4].tags.as_deref(), Some("breakfast"));
+
+        // Tags keep their source: rerunning changed rules retags the rule-tagged
+        // reading and leaves the one tagged by hand alone
+        let morning = tagging::RuleCondition::InWindow { start_minute: 6 * 60, end_minute: 10 * 60 };
+        ours.apply_tag_rules(&[TagRule::new("morning", morning)], true).unwrap();
+        let retagged = ours.get_all_readings().unwrap();
+        assert_eq!(retagged[0].tags.as_deref(), Some("fasting"));
+        assert_eq!(retagged[4].tags.as_deref(), Some("morning"));
+        assert_eq!(merged[2].tags.as_deref(), Some("dinner,exercise"));
+        assert_eq!(merged[3].note.as_deref(), Some("snack"));
+
+        // Merging again only finds duplicates
+        let again = ours.merge_from(&file).unwrap();
+        assert_eq!((again.inserted, again.duplicates, again.annotations_adopted), (0, 4, 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_sessions() {
+        let storage = memory_storage(&[]);
+        let download = Download {
+            meter: crate::device::MeterInfo {
+                product: "Accu-Chek Guide".to_string(),
+                serial: Some("12345678".to_string()),
+                clock_drift_secs: Some(-95),
+            },
+            readings: vec![reading("2024/01/01 08:00", 100), reading("2024/01/01 12:00", 140)],
+        };
+
+        let mut completed = SyncSession { started_at: "2024/01/01 12:05:00".to_string(), ..Default::default() };
+        completed.set_download(&download);
+        completed.complete(&storage.import_readings(&download.readings).unwrap());
+        let mut failed = SyncSession { started_at: "2024/01/02 09:00:00".to_string(), ..Default::default() };
+        failed.fail("No Accu-Chek device found");
+        let first = storage.record_sync_session(&completed).unwrap();
+        let second = storage.record_sync_session(&failed).unwrap();
+
+        // Newest first, every field read back as written
+        let sessions = storage.get_sync_sessions().unwrap();
+        assert_eq!(sessions.iter().map(|s| s.id).collect::<Vec<_>>(), vec![second, first]);
+        let failed = &sessions[0];
+        assert_eq!(failed.error.as_deref(), Some("No Accu-Chek device found"));
+        assert_eq!((failed.meter.as_deref(), failed.clock_drift_secs, failed.received), (None, None, 0));
+        let completed = &sessions[1];
+        assert_eq!(completed.error, None);
+        assert_eq!(completed.meter_label(), "Accu-Chek Guide (12345678)");
+        assert_eq!(completed.clock_drift_secs, Some(-95));
+        assert_eq!((completed.received, completed.inserted, completed.duplicates), (2, 2, 0));
+        assert!(!completed.finished_at.is_empty());
+    }
+
+    #[test]
+    fn test_events() {
+        let storage = memory_storage(&[
+            reading("2024/01/01 08:00", 100),
+            reading("2024/01/01 08:50", 150),
+            reading("2024/01/01 12:00", 140),
+        ]);
+
+        // The closest reading within the window, none outside it
+        let at = |timestamp: &str| timestamp_to_epoch(timestamp).unwrap();
+        let readings = storage.get_all_readings().unwrap();
+        assert_eq!(storage.nearest_reading(at("2024/01/01 08:20"), 3600).unwrap(), Some(readings[0].id));
+        assert_eq!(storage.nearest_reading(at("2024/01/01 08:30"), 3600).unwrap(), Some(readings[1].id));
+        assert_eq!(storage.nearest_reading(at("2024/01/01 10:30"), 3600).unwrap(), None);
+
+        let mut lunch = Event::new(EventKind::Carbs, "2024/01/01 12:10", Some(60.0), None).unwrap();
+        lunch.reading_id = Some(readings[2].id);
+        let lunch_id = storage.add_event(&lunch).unwrap();
+        let insulin = Event::new(EventKind::Insulin, "2024/01/01 07:55", Some(4.0), Some("rapid".to_string())).unwrap();
+        let insulin_id = storage.add_event(&insulin).unwrap();
+
+        // Oldest first, whatever the order they were logged in
+        let events = storage.get_events().unwrap();
+        assert_eq!(events.iter().map(|e| e.id).collect::<Vec<_>>(), vec![insulin_id, lunch_id]);
+        assert_eq!((events[0].kind, events[0].amount, events[0].detail.as_deref()), (EventKind::Insulin, Some(4.0), Some("rapid")));
+        assert_eq!(events[0].reading_id, None);
+        assert_eq!(events[1].reading_id, Some(readings[2].id));
+
+        assert_eq!(storage.delete_event(insulin_id).unwrap(), 1);
+        assert_eq!(storage.delete_event(insulin_id).unwrap(), 0);
+        assert_eq!(storage.get_events().unwrap().iter().map(|e| e.id).collect::<Vec<_>>(), vec![lunch_id]);
+        // Deleting an event leaves its reading alone
+        assert_eq!(storage.count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_apply_tag_rules() {
+        let storage = memory_storage(&[
+            reading("2024/01/01 07:00", 100),
+            reading("2024/01/01 08:00", 110),
+            reading("2024/01/01 12:00", 140),
+        ]);
+        let ids: Vec<i64> = storage.get_all_readings().unwrap().iter().map(|r| r.id).collect();
+        let morning = |tag: &str| TagRule::new(tag, tagging::RuleCondition::InWindow { start_minute: 6 * 60, end_minute: 9 * 60 });
+        let tags = || storage.get_all_readings().unwrap().into_iter().map(|r| r.tags).collect::<Vec<_>>();
+
+        storage.update_tags(ids[0], "fasting", ChangeSource::Gui).unwrap();
+        assert_eq!(storage.apply_tag_rules(&[morning("breakfast")], false).unwrap(), 1);
+        assert_eq!(tags(), vec![Some("fasting".to_string()), Some("breakfast".to_string()), None]);
+
+        // Without rerun, readings already tagged by a rule keep their tag
+        assert_eq!(storage.apply_tag_rules(&[morning("morning")], false).unwrap(), 0);
+        // A rerun retags only what the rules set, never the tag set by hand
+        assert_eq!(storage.apply_tag_rules(&[morning("morning")], true).unwrap(), 1);
+        assert_eq!(tags(), vec![Some("fasting".to_string()), Some("morning".to_string()), None]);
+
+        // A rule-tagged reading no rule matches any more loses its tag
+        assert_eq!(storage.apply_tag_rules(&[], true).unwrap(), 1);
+        assert_eq!(tags(), vec![Some("fasting".to_string()), None, None]);
+        // ...and is untagged again, so the next import's rules may tag it
+        assert_eq!(storage.apply_tag_rules(&[morning("breakfast")], false).unwrap(), 1);
+        // The rules never touched the reading tagged by hand
+        assert!(storage.get_history(ids[0]).unwrap().iter().all(|h| h.source != "rules"));
     }
-    (0, 0)
 }
@@ -0,0 +1,202 @@
+//! Automatic meal-context tagging from time of day and logged carbs
+//!
+//! Rules are evaluated in order and the first matching rule decides the
+//! reading's tag. Tags set by hand are never touched; see
+//! `Storage::apply_tag_rules`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::stats::{format_minute, in_time_window, minute_of_day};
+use crate::storage::StoredReading;
+use crate::timeaxis::DAY;
+
+/// What a reading must satisfy for a rule to apply
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    /// First reading of the day taken inside the window
+    FirstInWindow { start_minute: u16, end_minute: u16 },
+    /// Any reading taken inside the window
+    InWindow { start_minute: u16, end_minute: u16 },
+    /// Reading taken up to `minutes` after a carb event
+    AfterCarbs { minutes: u16 },
+    /// Reading taken up to `minutes` before a carb event
+    BeforeCarbs { minutes: u16 },
+}
+
+impl RuleCondition {
+    /// Every condition type with example parameters, for "add rule" menus
+    pub const TEMPLATES: [RuleCondition; 4] = [
+        RuleCondition::FirstInWindow { start_minute: 5 * 60, end_minute: 9 * 60 },
+        RuleCondition::InWindow { start_minute: 22 * 60, end_minute: 24 * 60 },
+        RuleCondition::AfterCarbs { minutes: 120 },
+        RuleCondition::BeforeCarbs { minutes: 30 },
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RuleCondition::FirstInWindow { .. } => "First reading between",
+            RuleCondition::InWindow { .. } => "Any reading between",
+            RuleCondition::AfterCarbs { .. } => "Within minutes after carbs",
+            RuleCondition::BeforeCarbs { .. } => "Within minutes before carbs",
+        }
+    }
+
+    /// Human readable description, e.g. "first reading between 05:00 and 09:00"
+    pub fn describe(&self) -> String {
+        match self {
+            RuleCondition::FirstInWindow { start_minute, end_minute } => format!(
+                "first reading between {} and {}", format_minute(*start_minute), format_minute(*end_minute)
+            ),
+            RuleCondition::InWindow { start_minute, end_minute } => format!(
+                "any reading between {} and {}", format_minute(*start_minute), format_minute(*end_minute)
+            ),
+            RuleCondition::AfterCarbs { minutes } => format!("within {} min after a carb event", minutes),
+            RuleCondition::BeforeCarbs { minutes } => format!("within {} min before a carb event", minutes),
+        }
+    }
+}
+
+/// A tag to apply when a condition holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRule {
+    pub tag: String,
+    pub condition: RuleCondition,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TagRule {
+    pub fn new(tag: &str, condition: RuleCondition) -> Self {
+        Self { tag: tag.to_string(), condition, enabled: true }
+    }
+}
+
+/// Rules used until the user changes them
+pub fn default_rules() -> Vec<TagRule> {
+    vec![
+        TagRule::new("fasting", RuleCondition::FirstInWindow { start_minute: 5 * 60, end_minute: 9 * 60 }),
+        TagRule::new("after_meal", RuleCondition::AfterCarbs { minutes: 120 }),
+        TagRule::new("before_meal", RuleCondition::BeforeCarbs { minutes: 30 }),
+    ]
+}
+
+/// Tag the rules assign to each reading, in the same order as `readings`
+///
+/// `readings` must be sorted by epoch; `carb_epochs` are the times of logged
+/// carb events. A reading no rule matches gets `None`.
+pub fn evaluate(rules: &[TagRule], readings: &[StoredReading], carb_epochs: &[i64]) -> Vec<Option<String>> {
+    // IDs of the first reading inside each occurrence of each first-in-window rule's
+    // window; a window wrapping past midnight belongs to the day it starts on
+    let first_in_window: Vec<HashSet<i64>> = rules
+        .iter()
+        .map(|rule| match rule.condition {
+            RuleCondition::FirstInWindow { start_minute, end_minute } => {
+                let mut seen_windows = HashSet::new();
+                readings
+                    .iter()
+                    .filter(|r| minute_of_day(&r.timestamp).is_some_and(|m| in_time_window(m, start_minute, end_minute)))
+                    .filter(|r| seen_windows.insert((r.epoch - start_minute as i64 * 60).div_euclid(DAY)))
+                    .map(|r| r.id)
+                    .collect()
+            }
+            _ => HashSet::new(),
+        })
+        .collect();
+
+    readings
+        .iter()
+        .map(|reading| {
+            rules
+                .iter()
+                .zip(&first_in_window)
+                .filter(|(rule, _)| rule.enabled && !rule.tag.trim().is_empty())
+                .find(|(rule, firsts)| match rule.condition {
+                    RuleCondition::FirstInWindow { .. } => firsts.contains(&reading.id),
+                    RuleCondition::InWindow { start_minute, end_minute } => minute_of_day(&reading.timestamp)
+                        .is_some_and(|m| in_time_window(m, start_minute, end_minute)),
+                    RuleCondition::AfterCarbs { minutes } => carb_epochs.iter().any(|&carbs| {
+                        (0..=minutes as i64 * 60).contains(&(reading.epoch - carbs))
+                    }),
+                    RuleCondition::BeforeCarbs { minutes } => carb_epochs.iter().any(|&carbs| {
+                        (1..=minutes as i64 * 60).contains(&(carbs - reading.epoch))
+                    }),
+                })
+                .map(|(rule, _)| rule.tag.trim().to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::timestamp_to_epoch;
+
+    fn reading(id: i64, timestamp: &str) -> StoredReading {
+        StoredReading {
+            id,
+            epoch: timestamp_to_epoch(timestamp).unwrap(),
+            timestamp: timestamp.to_string(),
+            mg_dl: 100,
+            mmol_l: 5.5,
+            imported_at: String::new(),
+            note: None,
+            tags: None,
+            excluded: false,
+            exclusion_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_first_reading_in_window_is_fasting() {
+        let readings = vec![
+            reading(1, "2024/01/01 04:30"),
+            reading(2, "2024/01/01 06:15"),
+            reading(3, "2024/01/01 08:00"),
+            reading(4, "2024/01/02 07:00"),
+        ];
+        let tags = evaluate(&default_rules(), &readings, &[]);
+        assert_eq!(tags, vec![None, Some("fasting".into()), None, Some("fasting".into())]);
+    }
+
+    #[test]
+    fn test_carb_rules() {
+        let carbs = timestamp_to_epoch("2024/01/01 12:00").unwrap();
+        let readings = vec![
+            reading(1, "2024/01/01 11:40"),
+            reading(2, "2024/01/01 13:30"),
+            reading(3, "2024/01/01 14:30"),
+        ];
+        let tags = evaluate(&default_rules(), &readings, &[carbs]);
+        assert_eq!(tags, vec![Some("before_meal".into()), Some("after_meal".into()), None]);
+    }
+
+    #[test]
+    fn test_window_wraps_midnight_and_disabled_rules_are_skipped() {
+        let mut rules = vec![TagRule::new("bedtime", RuleCondition::InWindow { start_minute: 22 * 60, end_minute: 60 })];
+        let readings = vec![reading(1, "2024/01/01 23:30"), reading(2, "2024/01/02 00:30"), reading(3, "2024/01/02 02:00")];
+        assert_eq!(evaluate(&rules, &readings, &[]), vec![Some("bedtime".into()), Some("bedtime".into()), None]);
+
+        rules[0].enabled = false;
+        assert_eq!(evaluate(&rules, &readings, &[]), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_first_in_window_wrapping_midnight() {
+        let rules = vec![TagRule::new("night", RuleCondition::FirstInWindow { start_minute: 22 * 60, end_minute: 2 * 60 })];
+        let readings = vec![
+            reading(1, "2024/01/01 01:00"),
+            reading(2, "2024/01/01 23:00"),
+            reading(3, "2024/01/02 01:00"),
+            reading(4, "2024/01/02 22:30"),
+            reading(5, "2024/01/03 03:00"),
+        ];
+        let night = Some("night".to_string());
+        assert_eq!(evaluate(&rules, &readings, &[]), vec![night.clone(), night.clone(), None, night, None]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Time axes for the trend charts in the GUI and the PDF report
+//!
+//! Readings are stored with the meter's local time written as if it were UTC,
+//! so whole multiples of a day in epoch seconds fall on local midnight and
+//! ticks can be placed without a time zone.
+
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+pub const HOUR: i64 = 3600;
+pub const DAY: i64 = 24 * HOUR;
+pub const WEEK: i64 = 7 * DAY;
+
+/// Readings further apart than this are not joined by a line
+pub const READING_LINE_BREAK: i64 = DAY;
+/// 1970/01/05 was a Monday; weekly ticks are offset by it
+const FIRST_MONDAY: i64 = 4 * DAY;
+
+/// Distance between two ticks on a time axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickStep {
+    Seconds(i64),
+    Months(u32),
+}
+
+/// Tick steps from finest to coarsest
+const STEPS: [TickStep; 13] = [
+    TickStep::Seconds(HOUR),
+    TickStep::Seconds(2 * HOUR),
+    TickStep::Seconds(3 * HOUR),
+    TickStep::Seconds(6 * HOUR),
+    TickStep::Seconds(12 * HOUR),
+    TickStep::Seconds(DAY),
+    TickStep::Seconds(2 * DAY),
+    TickStep::Seconds(WEEK),
+    TickStep::Seconds(2 * WEEK),
+    TickStep::Months(1),
+    TickStep::Months(3),
+    TickStep::Months(6),
+    TickStep::Months(12),
+];
+
+impl TickStep {
+    /// Length of the step in seconds, taking a month as 30.44 days
+    pub fn approx_secs(self) -> f64 {
+        match self {
+            TickStep::Seconds(secs) => secs as f64,
+            TickStep::Months(months) => months as f64 * 30.44 * DAY as f64,
+        }
+    }
+
+    /// The finest step at least `min_secs` long, or the coarsest step
+    pub fn at_least(min_secs: f64) -> Self {
+        STEPS.into_iter().find(|step| step.approx_secs() >= min_secs).unwrap_or(STEPS[STEPS.len() - 1])
+    }
+
+    /// The step whose length is closest to `secs`, e.g. to format a grid mark
+    pub fn nearest(secs: f64) -> Self {
+        STEPS.into_iter()
+            .min_by(|a, b| (a.approx_secs() - secs).abs().total_cmp(&(b.approx_secs() - secs).abs()))
+            .unwrap_or(STEPS[0])
+    }
+
+    /// This step and the next two coarser ones, as the fine, medium and bold grid lines
+    pub fn with_coarser(self) -> impl Iterator<Item = TickStep> {
+        STEPS.into_iter().skip_while(move |step| *step != self).take(3)
+    }
+
+    /// Tick positions from `start` to `end` inclusive; weeks start on Monday, months on the 1st
+    pub fn ticks(self, start: i64, end: i64) -> Vec<i64> {
+        match self {
+            TickStep::Seconds(step) => {
+                let offset = if step % WEEK == 0 { FIRST_MONDAY } else { 0 };
+                let first = (start - offset).div_euclid(step) * step + offset;
+                let first = if first < start { first + step } else { first };
+                (0..).map(|i| first + i * step).take_while(|t| *t <= end).collect()
+            }
+            TickStep::Months(months) => {
+                let Some(start_date) = chrono::DateTime::from_timestamp(start, 0).map(|t| t.date_naive()) else {
+                    return Vec::new();
+                };
+                // Months since year 0, rounded down to a multiple of the step
+                let mut month = (start_date.year() * 12 + start_date.month0() as i32).div_euclid(months as i32) * months as i32;
+                let mut ticks = Vec::new();
+                while let Some(date) = NaiveDate::from_ymd_opt(month.div_euclid(12), month.rem_euclid(12) as u32 + 1, 1) {
+                    let tick = date.and_hms_opt(0, 0, 0).map_or(i64::MAX, |t| t.and_utc().timestamp());
+                    if tick > end {
+                        break;
+                    }
+                    if tick >= start {
+                        ticks.push(tick);
+                    }
+                    month += months as i32;
+                }
+                ticks
+            }
+        }
+    }
+
+    /// Tick label: the time of day within a day, else the date, month or year
+    pub fn format(self, epoch: i64) -> String {
+        let Some(time) = chrono::DateTime::from_timestamp(epoch, 0).map(|t| t.naive_utc()) else {
+            return String::new();
+        };
+        let format = match self {
+            TickStep::Seconds(step) if step < DAY && epoch.rem_euclid(DAY) != 0 => "%H:%M",
+            TickStep::Seconds(_) => "%m/%d",
+            TickStep::Months(12) => "%Y",
+            TickStep::Months(_) => "%Y/%m",
+        };
+        time.format(format).to_string()
+    }
+}
+
+/// Most ticks generated for one step level; denser levels are left out
+const MAX_TICKS: f64 = 1000.0;
+
+/// Ticks for every step level visible at `min_secs` spacing, with each position's coarsest step
+pub fn grid_ticks(start: i64, end: i64, min_secs: f64) -> Vec<(i64, TickStep)> {
+    let mut ticks: BTreeMap<i64, TickStep> = BTreeMap::new();
+    for step in TickStep::at_least(min_secs).with_coarser() {
+        if (end - start) as f64 / step.approx_secs() > MAX_TICKS {
+            continue;
+        }
+        for tick in step.ticks(start, end) {
+            ticks.insert(tick, step);
+        }
+    }
+    ticks.into_iter().collect()
+}
+
+/// Epoch seconds of noon on a "YYYY/MM/DD" date, where daily values are plotted
+pub fn day_midpoint(date: &str) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(date, "%Y/%m/%d").ok()?;
+    Some(date.and_hms_opt(12, 0, 0)?.and_utc().timestamp())
+}
+
+/// Date and time of an epoch, as readings show it
+pub fn format_epoch(epoch: i64) -> String {
+    chrono::DateTime::from_timestamp(epoch, 0)
+        .map(|t| t.naive_utc().format("%Y/%m/%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// Runs of sorted times with no step longer than `max_gap`, so a line can break between them
+pub fn segments(times: &[i64], max_gap: i64) -> Vec<Range<usize>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for i in 1..times.len() {
+        if times[i] - times[i - 1] > max_gap {
+            segments.push(start..i);
+            start = i;
+        }
+    }
+    if start < times.len() {
+        segments.push(start..times.len());
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::timestamp_to_epoch;
+
+    fn epoch(timestamp: &str) -> i64 {
+        timestamp_to_epoch(timestamp).unwrap()
+    }
+
+    #[test]
+    fn test_tick_steps() {
+        assert_eq!(TickStep::at_least(5000.0), TickStep::Seconds(2 * HOUR));
+        assert_eq!(TickStep::at_least(3.0 * DAY as f64), TickStep::Seconds(WEEK));
+        assert_eq!(TickStep::at_least(1e12), TickStep::Months(12));
+        assert_eq!(TickStep::nearest(29.0 * DAY as f64), TickStep::Months(1));
+        let levels: Vec<TickStep> = TickStep::Seconds(WEEK).with_coarser().collect();
+        assert_eq!(levels, vec![TickStep::Seconds(WEEK), TickStep::Seconds(2 * WEEK), TickStep::Months(1)]);
+    }
+
+    #[test]
+    fn test_ticks() {
+        // 2024/01/03 is a Wednesday
+        let (start, end) = (epoch("2024/01/03 10:00"), epoch("2024/01/16 00:00"));
+        let weeks: Vec<String> = TickStep::Seconds(WEEK).ticks(start, end).into_iter().map(format_epoch).collect();
+        assert_eq!(weeks, vec!["2024/01/08 00:00", "2024/01/15 00:00"]);
+
+        let hours: Vec<String> = TickStep::Seconds(6 * HOUR).ticks(start, epoch("2024/01/04 00:00")).into_iter()
+            .map(|t| TickStep::Seconds(6 * HOUR).format(t))
+            .collect();
+        assert_eq!(hours, vec!["12:00", "18:00", "01/04"]);
+
+        let quarters: Vec<String> = TickStep::Months(3).ticks(epoch("2023/11/15 00:00"), epoch("2024/08/01 00:00")).into_iter()
+            .map(|t| TickStep::Months(3).format(t))
+            .collect();
+        assert_eq!(quarters, vec!["2024/01", "2024/04", "2024/07"]);
+
+        // Coarser levels win where ticks coincide
+        let grid = grid_ticks(epoch("2024/01/31 00:00"), epoch("2024/02/02 00:00"), DAY as f64);
+        assert_eq!(grid, vec![
+            (epoch("2024/01/31 00:00"), TickStep::Seconds(DAY)),
+            (epoch("2024/02/01 00:00"), TickStep::Seconds(2 * DAY)),
+            (epoch("2024/02/02 00:00"), TickStep::Seconds(DAY)),
+        ]);
+    }
+
+    #[test]
+    fn test_segments() {
+        let times = [0, HOUR, 2 * HOUR, 2 * HOUR + 2 * DAY, 3 * HOUR + 2 * DAY, 20 * DAY];
+        assert_eq!(segments(&times, DAY), vec![0..3, 3..5, 5..6]);
+        assert_eq!(segments(&[], DAY), Vec::<Range<usize>>::new());
+        assert_eq!(day_midpoint("2024/01/02"), Some(epoch("2024/01/02 12:00")));
+    }
+}